@@ -0,0 +1,207 @@
+//! Command-line interface for the Oracle HCM MCP server.
+//!
+//! Every flag can also be supplied through its matching environment variable,
+//! so container deployments can keep using `.env` files while desktop MCP
+//! clients pass flags when they launch the server as a child process.
+//! Both override the `--config` file, see `config`.
+
+use anyhow::Result;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::{net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, path::PathBuf, time::Duration};
+
+use crate::{
+    config::{self, Config},
+    server::{
+        ListenerConfig,
+        auth::{AuthConfig, JwksSource},
+        health::{HealthConfig, Required},
+        tls::TlsFiles,
+    },
+};
+
+/// Command-line arguments for the server binary.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// Run a one-off command instead of serving
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// TOML configuration file; environment variables and flags override its values
+    #[arg(long, env = "MCP_CONFIG_FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Transport used to serve the MCP protocol
+    #[arg(long, env = "MCP_TRANSPORT", value_enum, default_value_t = Transport::Http)]
+    pub transport: Transport,
+
+    /// Address the HTTP listener binds to
+    #[arg(long, env = "MCP_HOST", default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    pub host: IpAddr,
+
+    /// Port the HTTP listener binds to
+    #[arg(long, env = "MCP_PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// PEM certificate chain; enables native HTTPS together with `--tls-key`
+    #[arg(long, env = "MCP_TLS_CERT_FILE", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1) for `--tls-cert`
+    #[arg(long, env = "MCP_TLS_KEY_FILE", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Seconds between checks for a rotated certificate/key
+    #[arg(long, env = "MCP_TLS_RELOAD_INTERVAL_SECS", default_value_t = 30)]
+    pub tls_reload_interval: u64,
+
+    /// JWKS URL for validating bearer tokens on `/mcp`; enables authentication
+    #[arg(
+        long,
+        env = "MCP_AUTH_JWKS_URL",
+        conflicts_with = "auth_jwks_file",
+        requires_all = ["auth_issuer", "auth_audience"]
+    )]
+    pub auth_jwks_url: Option<String>,
+
+    /// Local JWKS file for validating bearer tokens on `/mcp`; enables authentication
+    #[arg(long, env = "MCP_AUTH_JWKS_FILE", requires_all = ["auth_issuer", "auth_audience"])]
+    pub auth_jwks_file: Option<PathBuf>,
+
+    /// Required `iss` claim of inbound bearer tokens
+    #[arg(long, env = "MCP_AUTH_ISSUER")]
+    pub auth_issuer: Option<String>,
+
+    /// Required `aud` claim of inbound bearer tokens
+    #[arg(long, env = "MCP_AUTH_AUDIENCE")]
+    pub auth_audience: Option<String>,
+
+    /// Clock skew in seconds tolerated when checking `exp`/`nbf`
+    #[arg(long, env = "MCP_AUTH_LEEWAY_SECS", default_value_t = 60)]
+    pub auth_leeway: u64,
+
+    /// Make `/readyz` also check that HCM is reachable and the credentials work
+    #[arg(long, env = "MCP_READYZ_CHECK_HCM")]
+    pub readyz_check_hcm: bool,
+
+    /// HCM environments whose check must pass for `/readyz` to succeed: `default` or `all`
+    #[arg(long, env = "MCP_READYZ_REQUIRE", value_enum, default_value_t = Required::Default)]
+    pub readyz_require: Required,
+
+    /// Seconds a `/readyz` result is reused before checking again
+    #[arg(long, env = "MCP_READYZ_CACHE_SECS", default_value_t = 30)]
+    pub readyz_cache_secs: u64,
+}
+
+impl Cli {
+    /// Parses the command line on top of the environment and the `--config`
+    /// file, returning the flags and the effective configuration.
+    ///
+    /// # Errors
+    /// Fails if the configuration file can't be loaded. Invalid flags exit
+    /// with clap's usage message, like `Cli::parse`.
+    pub fn load() -> Result<(Self, Config)> {
+        // The file must be loaded before the real parse, as it supplies flag defaults
+        let probe = Self::command().ignore_errors(true).try_get_matches().ok();
+        if let Some(path) = probe.as_ref().and_then(|matches| matches.get_one::<PathBuf>("config")) {
+            config::load_file(path)?;
+        }
+
+        let command = config::with_file_defaults(Self::command());
+        let matches = command.clone().get_matches();
+        let cli = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let config = Config::resolve(&command, &matches);
+        Ok((cli, config))
+    }
+
+    /// Listener settings for the streamable HTTP transport.
+    pub fn listener(&self) -> ListenerConfig {
+        let tls = self
+            .tls_cert
+            .clone()
+            .zip(self.tls_key.clone())
+            .map(|(cert, key)| TlsFiles {
+                cert,
+                key,
+                reload_interval: Duration::from_secs(self.tls_reload_interval.max(1)),
+            });
+
+        ListenerConfig {
+            addr: SocketAddr::new(self.host, self.port),
+            tls,
+        }
+    }
+
+    /// Readiness check settings for `/readyz`.
+    pub const fn health(&self) -> HealthConfig {
+        HealthConfig {
+            check_hcm: self.readyz_check_hcm,
+            require: self.readyz_require,
+            cache_for: Duration::from_secs(self.readyz_cache_secs),
+        }
+    }
+
+    /// URL of `path` on the local HTTP listener, as used by the `healthcheck` subcommand.
+    pub fn local_url(&self, path: &str) -> String {
+        // A wildcard bind address is reachable via loopback
+        let host = match self.host {
+            IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            host => host,
+        };
+        let scheme = if self.tls_cert.is_some() { "https" } else { "http" };
+        format!("{scheme}://{}{path}", SocketAddr::new(host, self.port))
+    }
+
+    /// Bearer-token settings, or `None` when no JWKS is configured.
+    pub fn auth(&self) -> Option<AuthConfig> {
+        let jwks = self
+            .auth_jwks_url
+            .clone()
+            .map(JwksSource::Url)
+            .or_else(|| self.auth_jwks_file.clone().map(JwksSource::File))?;
+
+        Some(AuthConfig {
+            jwks,
+            // Presence is enforced by clap's `requires_all`
+            issuer: self.auth_issuer.clone().unwrap_or_default(),
+            audience: self.auth_audience.clone().unwrap_or_default(),
+            leeway: Duration::from_secs(self.auth_leeway),
+        })
+    }
+}
+
+/// Transports the MCP tool router can be served over.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    /// Streamable HTTP (SSE-based) server on `/mcp`
+    Http,
+    /// JSON-RPC over stdin/stdout, for clients that spawn the server as a child process
+    Stdio,
+}
+
+/// One-off commands run instead of the server.
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Probe the local server's `/readyz` (or `/healthz`) and exit non-zero on failure.
+    /// Reads the same listener settings as the server, e.g. `MCP_PORT`.
+    Healthcheck {
+        /// Check liveness (`/healthz`) instead of readiness
+        #[arg(long)]
+        liveness: bool,
+    },
+    /// Check the hash chain of audit log files (`MCP_AUDIT_FILE` and its rotations)
+    /// and exit non-zero if any record was modified, removed or reordered.
+    VerifyAudit {
+        /// Audit files, oldest first, e.g. `audit.jsonl.2 audit.jsonl.1 audit.jsonl`
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// `seq` of the first record in the oldest file, when older files were rotated away;
+        /// without it the files must start at record 1
+        #[arg(long)]
+        from_seq: Option<u64>,
+    },
+    /// Print the effective configuration and where each value came from, with
+    /// secrets redacted, and exit non-zero if it is invalid.
+    PrintConfig,
+}
@@ -0,0 +1,1055 @@
+//! Layered configuration: a TOML file, then environment variables, then CLI flags.
+//!
+//! Every setting is named by its environment variable (see `SETTINGS`). A TOML
+//! file given with `--config` supplies the values the environment (including
+//! `.env`) doesn't set, and the `MCP_*` settings that have a CLI flag can be
+//! overridden on the command line.
+//!
+//! File keys map to variables by joining the table path with `_` and upper-casing:
+//! ```toml
+//! rest_framework_version = "9"
+//!
+//! [hcm]
+//! base_url = "https://<instance>.oraclecloud.com"
+//! retry.max_retries = 5
+//!
+//! [hcm.cache.ttls]          # HCM_CACHE_TTLS="publicWorkers=300,planBalances=60"
+//! publicWorkers = 300
+//! planBalances = 60
+//!
+//! [mcp.redact]
+//! fields = ["DisplayName", "Email"]  # arrays are joined with commas
+//! ```
+//! Keys that don't name a setting are rejected, so typos don't go unnoticed.
+//! Per-environment values (see `mcp::http::environment`) go in `[hcm.env.<name>]`,
+//! e.g. `[hcm.env.prod] base_url = "..."` sets `HCM_ENV_PROD_BASE_URL`.
+//! `OTEL_*` and `RUST_LOG` are read by their libraries and can only be set in
+//! the environment.
+//!
+//! The effective configuration is validated once at startup ([`Config::validate`]),
+//! reporting every bad value and where it came from instead of silently falling
+//! back to a default. `print-config` shows it with secrets redacted.
+//!
+//! Numbers, flags and choices are parsed once, by the same checks, into
+//! [`Tuning`]; modules read their values from [`tuning`] rather than parsing
+//! variables themselves.
+//!
+//! # Configuration
+//! - `MCP_CONFIG_FILE` (optional): TOML configuration file, same as `--config`
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{ArgMatches, Command, parser::ValueSource};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fmt, fs, iter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{LazyLock, OnceLock},
+    time::Duration,
+};
+use url::Url;
+
+use crate::mcp::{OracleHCMMCPFactory, redact::REDACTED};
+
+/// How a setting's value is checked.
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    /// Free-form text
+    Text,
+    /// A credential, redacted by `print-config`
+    Secret,
+    /// An absolute `http` or `https` URL
+    Url,
+    /// A whole number in a range
+    Integer { min: u64, max: u64 },
+    /// A decimal number in a range
+    Number { min: f64, max: f64 },
+    /// `true` or `false`
+    Flag,
+    /// Path of a file that must exist
+    File,
+    /// One of a fixed set of values
+    Choice(&'static [&'static str]),
+    /// Comma-separated `name=value` entries; a table in the configuration file
+    Pairs,
+}
+
+struct Setting {
+    name: &'static str,
+    kind: Kind,
+    /// Can be set per HCM environment, see [`scoped_name`]
+    scoped: bool,
+}
+
+const fn setting(name: &'static str, kind: Kind) -> Setting {
+    Setting { name, kind, scoped: false }
+}
+
+const fn scoped(name: &'static str, kind: Kind) -> Setting {
+    Setting { name, kind, scoped: true }
+}
+
+/// Any whole number.
+const INTEGER: Kind = Kind::Integer { min: 0, max: u64::MAX };
+
+/// A whole number of at least `min`.
+const fn at_least(min: u64) -> Kind {
+    Kind::Integer { min, max: u64::MAX }
+}
+
+/// A count of permits or requests, bounded by what a semaphore can hold.
+const PERMITS: Kind = Kind::Integer { min: 1, max: 100_000 };
+
+/// Slowest sustained HCM request rate accepted, one request every ~17 minutes;
+/// anything lower is treated as a typo rather than waited out.
+const MIN_RPS: f64 = 0.001;
+
+/// Every setting the server reads, in the order `print-config` shows them.
+///
+/// Settings with a CLI flag are parsed by clap, which reports their type
+/// errors itself; only their URLs and paths are checked again here.
+const SETTINGS: &[Setting] = &[
+    // HCM environments, connection and authentication
+    setting("HCM_ENVIRONMENTS", Kind::Text),
+    setting("HCM_DEFAULT_ENVIRONMENT", Kind::Text),
+    scoped("HCM_BASE_URL", Kind::Url),
+    scoped("HCM_API_VERSION", Kind::Text),
+    scoped("REST_FRAMEWORK_VERSION", Kind::Text),
+    scoped("HCM_AUTH_MODE", Kind::Choice(&["basic", "client_credentials", "jwt_assertion"])),
+    scoped("HCM_USERNAME", Kind::Text),
+    scoped("HCM_PASSWORD", Kind::Secret),
+    scoped("HCM_PASSWORD_FILE", Kind::File),
+    scoped("HCM_OAUTH_TOKEN_URL", Kind::Url),
+    scoped("HCM_OAUTH_CLIENT_ID", Kind::Text),
+    scoped("HCM_OAUTH_CLIENT_SECRET", Kind::Secret),
+    scoped("HCM_OAUTH_CLIENT_SECRET_FILE", Kind::File),
+    scoped("HCM_OAUTH_SCOPE", Kind::Text),
+    scoped("HCM_JWT_PRIVATE_KEY_FILE", Kind::File),
+    scoped("HCM_JWT_KEY_ID", Kind::Text),
+    scoped("HCM_JWT_SUBJECT", Kind::Text),
+    scoped("HCM_JWT_AUDIENCE", Kind::Text),
+    scoped("HCM_DELEGATION_MODE", Kind::Choice(&["off", "token_exchange", "jwt_user_assertion"])),
+    scoped("HCM_DELEGATION_SUBJECT_TOKEN_TYPE", Kind::Text),
+    scoped("HCM_DELEGATION_AUDIENCE", Kind::Text),
+    scoped("HCM_DELEGATION_USER_CLAIM", Kind::Text),
+    setting("HCM_SECRET_RELOAD_SECS", INTEGER),
+    // HCM resilience, paging and caching
+    setting("HCM_RETRY_MAX_RETRIES", Kind::Integer { min: 0, max: 100 }),
+    setting("HCM_RETRY_BASE_DELAY_MS", INTEGER),
+    setting("HCM_RETRY_MAX_DELAY_MS", INTEGER),
+    setting("HCM_RETRY_BUDGET_MS", INTEGER),
+    setting("HCM_BREAKER_WINDOW", Kind::Integer { min: 1, max: 10_000 }),
+    setting("HCM_BREAKER_MIN_CALLS", Kind::Integer { min: 1, max: 10_000 }),
+    setting("HCM_BREAKER_FAILURE_PERCENT", Kind::Integer { min: 1, max: 100 }),
+    setting("HCM_BREAKER_OPEN_SECS", INTEGER),
+    setting("HCM_BREAKER_HALF_OPEN_PROBES", PERMITS),
+    setting("HCM_BULKHEAD_LOOKUPS", PERMITS),
+    setting("HCM_BULKHEAD_PROJECTIONS", PERMITS),
+    setting("HCM_BULKHEAD_WAIT_MS", INTEGER),
+    setting("HCM_RATE_LIMIT_RPS", Kind::Number { min: MIN_RPS, max: 1e6 }),
+    setting("HCM_RATE_LIMIT_BURST", Kind::Number { min: 1.0, max: 1e6 }),
+    setting("HCM_MAX_IN_FLIGHT", PERMITS),
+    setting("HCM_RATE_LIMIT_WAIT_MS", INTEGER),
+    setting("HCM_RATE_LIMITS", Kind::Pairs),
+    setting("HCM_PAGE_SIZE", Kind::Integer { min: 1, max: 500 }),
+    setting("HCM_MAX_ITEMS", at_least(1)),
+    setting("HCM_CACHE_TTLS", Kind::Pairs),
+    setting("HCM_CACHE_ETAGS", Kind::Text),
+    setting("HCM_CACHE_MAX_ENTRIES", INTEGER),
+    setting("HCM_COALESCE_GETS", Kind::Flag),
+    setting("HCM_PROJECTION_UOM", Kind::Choice(&["H", "D"])),
+    setting("HCM_PROJECTION_HOURS_PER_DAY", Kind::Number { min: 0.1, max: 24.0 }),
+    // MCP listener and inbound authentication (CLI flags)
+    setting("MCP_CONFIG_FILE", Kind::File),
+    setting("MCP_TRANSPORT", Kind::Text),
+    setting("MCP_HOST", Kind::Text),
+    setting("MCP_PORT", Kind::Text),
+    setting("MCP_TLS_CERT_FILE", Kind::File),
+    setting("MCP_TLS_KEY_FILE", Kind::File),
+    setting("MCP_TLS_RELOAD_INTERVAL_SECS", Kind::Text),
+    setting("MCP_AUTH_JWKS_URL", Kind::Url),
+    setting("MCP_AUTH_JWKS_FILE", Kind::File),
+    setting("MCP_AUTH_ISSUER", Kind::Text),
+    setting("MCP_AUTH_AUDIENCE", Kind::Text),
+    setting("MCP_AUTH_LEEWAY_SECS", Kind::Text),
+    setting("MCP_READYZ_CHECK_HCM", Kind::Text),
+    setting("MCP_READYZ_REQUIRE", Kind::Text),
+    setting("MCP_READYZ_CACHE_SECS", Kind::Text),
+    // MCP authorization, redaction and audit
+    setting("MCP_AUTHZ_PERSON_CLAIM", Kind::Text),
+    setting("MCP_AUTHZ_PERSON_CLAIM_TYPE", Kind::Choice(&["worker_number", "person_id"])),
+    setting("MCP_AUTHZ_ADMIN_CLAIM", Kind::Text),
+    setting("MCP_AUTHZ_ADMIN_VALUE", Kind::Text),
+    setting("MCP_AUTHZ_MAX_CHAIN_DEPTH", Kind::Integer { min: 1, max: 100 }),
+    setting("MCP_ENVIRONMENT_CLAIM", Kind::Text),
+    setting("MCP_REDACT_HEADERS", Kind::Text),
+    setting("MCP_REDACT_FIELDS", Kind::Text),
+    setting("MCP_AUDIT_FILE", Kind::Text),
+    setting("MCP_AUDIT_MAX_BYTES", at_least(1)),
+    setting("MCP_AUDIT_MAX_FILES", Kind::Integer { min: 1, max: 1000 }),
+    setting("MCP_AUDIT_KEY", Kind::Secret),
+    setting("MCP_AUDIT_KEY_FILE", Kind::File),
+    setting("MCP_ORGANIZATION_NAME", Kind::Text),
+    setting("MCP_WORKER_NUMBER_LABEL", Kind::Text),
+    setting("MCP_WORKER_NUMBER_EXAMPLE", Kind::Text),
+    setting("MCP_PERSON_ID_EXAMPLE", Kind::Text),
+    setting("MCP_INSTRUCTIONS", Kind::Text),
+    setting("MCP_TOOL_NAMES", Kind::Pairs),
+];
+
+/// Settings that only make sense together: (set, requires).
+const REQUIRES: &[(&str, &str)] = &[
+    ("MCP_TLS_CERT_FILE", "MCP_TLS_KEY_FILE"),
+    ("MCP_TLS_KEY_FILE", "MCP_TLS_CERT_FILE"),
+    ("MCP_AUTH_JWKS_URL", "MCP_AUTH_ISSUER"),
+    ("MCP_AUTH_JWKS_URL", "MCP_AUTH_AUDIENCE"),
+    ("MCP_AUTH_JWKS_FILE", "MCP_AUTH_ISSUER"),
+    ("MCP_AUTH_JWKS_FILE", "MCP_AUTH_AUDIENCE"),
+];
+
+/// Settings that can't be used together.
+const CONFLICTS: &[(&str, &str)] = &[
+    ("MCP_AUTH_JWKS_URL", "MCP_AUTH_JWKS_FILE"),
+    ("HCM_PASSWORD", "HCM_PASSWORD_FILE"),
+    ("HCM_OAUTH_CLIENT_SECRET", "HCM_OAUTH_CLIENT_SECRET_FILE"),
+];
+
+/// Finds the setting a variable sets, including per-environment variants.
+fn find(name: &str) -> Option<&'static Setting> {
+    SETTINGS.iter().find(|setting| setting.name == name).or_else(|| {
+        let (environment, rest) = name.strip_prefix(SCOPED_PREFIX)?.split_once('_')?;
+        SETTINGS
+            .iter()
+            .filter(|setting| setting.scoped)
+            .find(|setting| !environment.is_empty() && rest == unscoped_suffix(setting.name))
+    })
+}
+
+/// Prefix of per-environment variables, e.g. `HCM_ENV_PROD_BASE_URL`.
+const SCOPED_PREFIX: &str = "HCM_ENV_";
+
+fn unscoped_suffix(name: &str) -> &str {
+    name.strip_prefix("HCM_").unwrap_or(name)
+}
+
+/// The variable holding `name` for one HCM environment: `HCM_BASE_URL` for
+/// `prod` is `HCM_ENV_PROD_BASE_URL`, `REST_FRAMEWORK_VERSION` is
+/// `HCM_ENV_PROD_REST_FRAMEWORK_VERSION`.
+pub fn scoped_name(environment: &str, name: &str) -> String {
+    format!("{SCOPED_PREFIX}{}_{}", environment.to_ascii_uppercase(), unscoped_suffix(name))
+}
+
+/// The HCM environments listed in `HCM_ENVIRONMENTS`, lower-cased, in order.
+pub fn environment_names() -> Vec<String> {
+    parse_environment_names(&lookup("HCM_ENVIRONMENTS").unwrap_or_default())
+}
+
+/// The names in an `HCM_ENVIRONMENTS` value.
+fn parse_environment_names(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|name| name.trim().trim_matches(|c| c == '"' || c == '\'').to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// ============================================================================
+// Configuration file layer
+// ============================================================================
+
+/// The loaded configuration file, if any.
+static FILE: OnceLock<ConfigFile> = OnceLock::new();
+
+struct ConfigFile {
+    path: PathBuf,
+    /// Values by variable name, with the key they were written as
+    values: HashMap<String, FileValue>,
+}
+
+struct FileValue {
+    key: String,
+    value: String,
+}
+
+/// Reads the TOML file at `path` as the lowest-priority layer of configuration.
+///
+/// # Errors
+/// Fails if the file can't be read or parsed, or has a key that doesn't name a setting.
+pub fn load_file(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path).with_context(|| format!("cannot read config file {}", path.display()))?;
+    let table: toml::Table = text
+        .parse()
+        .with_context(|| format!("config file {} is not valid TOML", path.display()))?;
+    let mut values = HashMap::new();
+    flatten(&table, "", &mut values).with_context(|| format!("invalid config file {}", path.display()))?;
+    FILE.set(ConfigFile { path: path.to_path_buf(), values })
+        .map_err(|_| anyhow!("config file loaded twice"))
+}
+
+/// Collects the settings of `table`, whose keys are below `prefix` (dotted).
+fn flatten(table: &toml::Table, prefix: &str, values: &mut HashMap<String, FileValue>) -> Result<()> {
+    for (key, value) in table {
+        let key = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+        let name = key.replace(['.', '-'], "_").to_ascii_uppercase();
+        let Some(setting) = find(&name) else {
+            if let toml::Value::Table(table) = value {
+                flatten(table, &key, values)?;
+                continue;
+            }
+            bail!("unknown key `{key}` (no setting named {name})");
+        };
+        let value = match value {
+            toml::Value::Table(entries) if matches!(setting.kind, Kind::Pairs) => entries
+                .iter()
+                .map(|(entry, value)| Ok(format!("{entry}={}", scalar(&key, value)?)))
+                .collect::<Result<Vec<_>>>()?
+                .join(","),
+            toml::Value::Array(items) => items
+                .iter()
+                .map(|item| scalar(&key, item))
+                .collect::<Result<Vec<_>>>()?
+                .join(","),
+            value => scalar(&key, value)?,
+        };
+        values.insert(name, FileValue { key, value });
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: &toml::Value) -> Result<String> {
+    match value {
+        toml::Value::String(text) => Ok(text.clone()),
+        toml::Value::Integer(number) => Ok(number.to_string()),
+        toml::Value::Float(number) => Ok(number.to_string()),
+        toml::Value::Boolean(flag) => Ok(flag.to_string()),
+        toml::Value::Datetime(datetime) => Ok(datetime.to_string()),
+        toml::Value::Array(_) | toml::Value::Table(_) => bail!("`{key}` must be a value or a list of values"),
+    }
+}
+
+/// Looks up a setting: the environment first, then the configuration file.
+pub fn lookup(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .or_else(|| Some(FILE.get()?.values.get(name)?.value.clone()))
+}
+
+/// Makes the configuration file's values the defaults of the CLI flags they
+/// set, so clap still prefers the command line and then the environment.
+pub fn with_file_defaults(command: Command) -> Command {
+    let Some(file) = FILE.get() else {
+        return command;
+    };
+    file.values.iter().fold(command, |command, (name, value)| {
+        let flag = command
+            .get_arguments()
+            .find(|arg| arg.get_env().is_some_and(|env| env == name.as_str()))
+            .map(|arg| arg.get_id().to_string());
+        if let Some(id) = flag {
+            command.mut_arg(id, |arg| arg.default_value(value.value.clone()))
+        } else {
+            command
+        }
+    })
+}
+
+// ============================================================================
+// Effective configuration
+// ============================================================================
+
+/// Where an effective value came from.
+enum Source {
+    /// The configuration file, under this key
+    File(String),
+    Env,
+    Cli,
+}
+
+struct Entry {
+    /// The variable, e.g. `HCM_ENV_PROD_BASE_URL` for a per-environment value
+    name: String,
+    setting: &'static Setting,
+    value: String,
+    source: Source,
+    /// Parsed by clap, which already checked its type
+    flag: bool,
+}
+
+impl Entry {
+    /// The setting, its value and its source, for messages and `print-config`.
+    fn describe(&self) -> String {
+        let value = if matches!(self.setting.kind, Kind::Secret) { REDACTED } else { self.value.as_str() };
+        let source = match &self.source {
+            Source::File(key) => {
+                let path = FILE.get().map(|file| file.path.display().to_string()).unwrap_or_default();
+                format!("`{key}` in {path}")
+            }
+            Source::Env => "environment".to_string(),
+            Source::Cli => "command line".to_string(),
+        };
+        format!("{}={value:?} (from {source})", self.name)
+    }
+
+    /// What's wrong with the value, if anything.
+    fn problem(&self) -> Option<String> {
+        // clap already checked the type of flags, but not their URLs and paths
+        if self.flag && !matches!(self.setting.kind, Kind::Url | Kind::File) {
+            return None;
+        }
+        let expected = self.setting.kind.check(&self.value).err()?;
+        Some(format!("{}: expected {expected}", self.describe()))
+    }
+}
+
+impl Kind {
+    /// Checks `value`, describing what was expected if it doesn't fit.
+    fn check(self, value: &str) -> Result<(), String> {
+        let expected = match self {
+            Self::Url => match Url::parse(value) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => return Ok(()),
+                Ok(url) => format!("an http or https URL, not {}", url.scheme()),
+                Err(e) => format!("an absolute URL ({e})"),
+            },
+            Self::File => {
+                if Path::new(value).is_file() {
+                    return Ok(());
+                }
+                "an existing file".to_string()
+            }
+            Self::Text | Self::Secret => return Ok(()),
+            Self::Integer { min, max } if value.parse::<u64>().is_ok_and(|n| (min..=max).contains(&n)) => return Ok(()),
+            Self::Integer { min: 0, max: u64::MAX } => "a whole number".to_string(),
+            Self::Integer { min, max: u64::MAX } => format!("a whole number of at least {min}"),
+            Self::Integer { min, max } => format!("a whole number from {min} to {max}"),
+            Self::Number { min, max } if value.parse::<f64>().is_ok_and(|n| (min..=max).contains(&n)) => return Ok(()),
+            Self::Number { min, max } => format!("a number from {min} to {max}"),
+            Self::Flag if matches!(value, "true" | "false") => return Ok(()),
+            Self::Flag => "true or false".to_string(),
+            Self::Choice(choices) if choices.contains(&value) => return Ok(()),
+            Self::Choice(choices) => format!("one of {}", choices.join(", ")),
+            Self::Pairs => {
+                let well_formed = |entry: &&str| {
+                    entry
+                        .split_once('=')
+                        .is_some_and(|(name, value)| !name.trim().is_empty() && !value.trim().is_empty())
+                };
+                let Some(malformed) = pairs(value).find(|entry| !well_formed(entry)) else {
+                    return Ok(());
+                };
+                format!("comma-separated name=value entries, not {malformed:?}")
+            }
+        };
+        Err(expected)
+    }
+}
+
+/// The non-empty entries of a comma-separated value.
+fn pairs(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|entry| !entry.is_empty())
+}
+
+/// The effective configuration: every setting that is set, and where from.
+/// Unset settings use the defaults documented in their modules.
+pub struct Config {
+    entries: Vec<Entry>,
+}
+
+impl Config {
+    /// Resolves every setting from the parsed command line, the environment and
+    /// the configuration file.
+    pub fn resolve(command: &Command, matches: &ArgMatches) -> Self {
+        Self::resolve_from(command, matches, FILE.get(), |name| env::var(name).ok())
+    }
+
+    /// Like [`Self::resolve`], with `file` and `env` as the file and environment layers.
+    fn resolve_from(
+        command: &Command,
+        matches: &ArgMatches,
+        file: Option<&ConfigFile>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let flags: HashMap<&str, &str> = command
+            .get_arguments()
+            .filter_map(|arg| Some((arg.get_env()?.to_str()?, arg.get_id().as_str())))
+            .collect();
+        let file_value = |name: &str| file.and_then(|file| file.values.get(name));
+
+        // Every setting, then the per-environment variants for each listed environment
+        let environments = parse_environment_names(
+            &env("HCM_ENVIRONMENTS")
+                .or_else(|| Some(file_value("HCM_ENVIRONMENTS")?.value.clone()))
+                .unwrap_or_default(),
+        );
+        let names = SETTINGS.iter().map(|setting| (setting.name.to_string(), setting)).chain(
+            environments.iter().flat_map(|environment| {
+                SETTINGS
+                    .iter()
+                    .filter(|setting| setting.scoped)
+                    .map(move |setting| (scoped_name(environment, setting.name), setting))
+            }),
+        );
+
+        let entries = names
+            .filter_map(|(name, setting)| {
+                let (value, source, flag) = if let Some(id) = flags.get(name.as_str()) {
+                    let value = matches
+                        .try_get_raw(id)
+                        .ok()
+                        .flatten()?
+                        .map(|raw| raw.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    let source = match matches.value_source(id)? {
+                        ValueSource::CommandLine => Source::Cli,
+                        ValueSource::EnvVariable => Source::Env,
+                        // A default only counts as configured when the file supplied it
+                        _ => Source::File(file_value(&name)?.key.clone()),
+                    };
+                    (value, source, true)
+                } else if let Some(value) = env(&name) {
+                    (value, Source::Env, false)
+                } else {
+                    let value = file_value(&name)?;
+                    (value.value.clone(), Source::File(value.key.clone()), false)
+                };
+                let value = value.trim_matches(|c| c == '"' || c == '\'').to_string();
+                Some(Entry { name, setting, value, source, flag })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    fn is_set(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+
+    /// Checks every value, settings that depend on each other, and the HCM
+    /// connection settings as a whole.
+    ///
+    /// # Errors
+    /// Lists every problem found, naming each setting and where its value came from.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems: Vec<String> = self.entries.iter().filter_map(Entry::problem).collect();
+        problems.extend(self.combination_problems(&environment_names()));
+        problems.extend(tuning().problems.iter().cloned());
+        problems.extend(unlisted_environments());
+        if let Err(e) = OracleHCMMCPFactory::new() {
+            problems.push(format!("{e:#}"));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("invalid configuration:\n  - {}", problems.join("\n  - "))
+        }
+    }
+
+    /// Settings set without the settings they require, or together with ones
+    /// they conflict with, also within each of `environments`.
+    fn combination_problems(&self, environments: &[String]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (set, required) in REQUIRES {
+            if self.is_set(set) && !self.is_set(required) {
+                problems.push(format!("{set} is set, so {required} must be set too"));
+            }
+        }
+        for (one, other) in CONFLICTS {
+            // Per-environment settings conflict within each environment too
+            let scoped = find(one)
+                .filter(|setting| setting.scoped)
+                .into_iter()
+                .flat_map(|_| environments)
+                .map(|environment| (scoped_name(environment, one), scoped_name(environment, other)));
+            for (one, other) in iter::once(((*one).to_string(), (*other).to_string())).chain(scoped) {
+                if self.is_set(&one) && self.is_set(&other) {
+                    problems.push(format!("{one} and {other} can't both be set"));
+                }
+            }
+        }
+        problems
+    }
+}
+
+/// Per-environment variables naming an environment missing from `HCM_ENVIRONMENTS`,
+/// which would otherwise be ignored.
+fn unlisted_environments() -> Vec<String> {
+    let file_names = FILE.get().into_iter().flat_map(|file| file.values.keys().cloned());
+    let names = env::vars().map(|(name, _)| name).chain(file_names);
+    unlisted_in(names, &environment_names())
+}
+
+/// Like [`unlisted_environments`], for the variables `names` and the environments `listed`.
+fn unlisted_in(names: impl Iterator<Item = String>, listed: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = names.filter(|name| name.starts_with(SCOPED_PREFIX)).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let Some(setting) = find(&name) else {
+                return Some(format!("{name} is not a per-environment setting"));
+            };
+            let environment = name.strip_prefix(SCOPED_PREFIX)?.strip_suffix(unscoped_suffix(setting.name))?;
+            let environment = environment.trim_end_matches('_').to_ascii_lowercase();
+            (!listed.contains(&environment))
+                .then(|| format!("{name} is set, but HCM_ENVIRONMENTS doesn't list {environment}"))
+        })
+        .collect()
+}
+
+/// Prints the effective configuration for `print-config`, secrets redacted.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = FILE.get() {
+            writeln!(f, "# Configuration file: {}", file.path.display())?;
+        }
+        writeln!(f, "# Settings not listed use their defaults")?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.describe())?;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Typed settings
+// ============================================================================
+
+/// Every number, flag and choice the server reads, parsed once. Defaults are
+/// documented with the modules that use them.
+pub struct Tuning {
+    /// How often secret files are checked for changes; zero turns reloading off
+    pub secret_reload: Duration,
+    pub retry: Retry,
+    pub breaker: Breaker,
+    pub bulkheads: Bulkheads,
+    pub rate_limits: RateLimits,
+    /// Items requested per page
+    pub page_size: usize,
+    /// Items collected from one collection at most
+    pub max_items: usize,
+    pub cache: Cache,
+    /// Whether identical in-flight `GET`s share one request
+    pub coalesce_gets: bool,
+    /// Unit of measure for projections when the absence type has none, "H" or "D"
+    pub projection_uom: &'static str,
+    /// Hours per day for projections when the worker's working hours aren't known
+    pub projection_hours_per_day: f64,
+    /// Whether the caller's identity claim holds a `PersonId` rather than a worker number
+    pub person_claim_is_person_id: bool,
+    /// Management levels searched above an authorization target
+    pub max_chain_depth: usize,
+    pub audit: Audit,
+    /// Problems only visible once values are read together, reported by [`Config::validate`]
+    problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub budget: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Breaker {
+    pub window: usize,
+    pub min_calls: usize,
+    pub failure_percent: usize,
+    pub open_for: Duration,
+    pub half_open_probes: u32,
+}
+
+pub struct Bulkheads {
+    pub lookups: usize,
+    pub projections: usize,
+    pub wait: Duration,
+}
+
+/// Rate and concurrency limits for one HCM resource.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub rps: f64,
+    pub burst: f64,
+    pub in_flight: usize,
+}
+
+pub struct RateLimits {
+    /// Limits of resources without an override
+    pub default: RateLimit,
+    /// Per-resource overrides from `HCM_RATE_LIMITS`
+    pub overrides: HashMap<String, RateLimit>,
+    /// How long a call may queue for a token or an in-flight slot
+    pub wait: Duration,
+}
+
+pub struct Cache {
+    /// Per-resource TTLs from `HCM_CACHE_TTLS`, overriding the built-in ones; zero turns one off
+    pub ttls: HashMap<String, Duration>,
+    /// Resources revalidated by `ETag` from `HCM_CACHE_ETAGS`, or `None` for the cached ones
+    pub etags: Option<HashSet<String>>,
+    pub max_entries: usize,
+}
+
+pub struct Audit {
+    pub max_bytes: u64,
+    pub max_files: usize,
+}
+
+/// The typed settings, parsed on first use from the environment and the configuration file.
+pub fn tuning() -> &'static Tuning {
+    static TUNING: LazyLock<Tuning> = LazyLock::new(Tuning::load);
+    &TUNING
+}
+
+impl Tuning {
+    fn load() -> Self {
+        let mut problems = Vec::new();
+        let raw = |name: &str| lookup(name).unwrap_or_default();
+        let window = parsed("HCM_BREAKER_WINDOW", 20);
+        let min_calls = parsed("HCM_BREAKER_MIN_CALLS", 10);
+        if min_calls > window {
+            problems.push(format!(
+                "HCM_BREAKER_MIN_CALLS ({min_calls}) can't be more than HCM_BREAKER_WINDOW ({window})"
+            ));
+        }
+        Self {
+            secret_reload: Duration::from_secs(parsed("HCM_SECRET_RELOAD_SECS", 30)),
+            retry: Retry {
+                max_retries: parsed("HCM_RETRY_MAX_RETRIES", 3),
+                base_delay: Duration::from_millis(parsed("HCM_RETRY_BASE_DELAY_MS", 200)),
+                max_delay: Duration::from_millis(parsed("HCM_RETRY_MAX_DELAY_MS", 10_000)),
+                budget: Duration::from_millis(parsed("HCM_RETRY_BUDGET_MS", 30_000)),
+            },
+            breaker: Breaker {
+                window,
+                min_calls: min_calls.min(window),
+                failure_percent: parsed("HCM_BREAKER_FAILURE_PERCENT", 50),
+                open_for: Duration::from_secs(parsed("HCM_BREAKER_OPEN_SECS", 30)),
+                half_open_probes: parsed("HCM_BREAKER_HALF_OPEN_PROBES", 1),
+            },
+            bulkheads: Bulkheads {
+                lookups: parsed("HCM_BULKHEAD_LOOKUPS", 32),
+                projections: parsed("HCM_BULKHEAD_PROJECTIONS", 4),
+                wait: Duration::from_millis(parsed("HCM_BULKHEAD_WAIT_MS", 10_000)),
+            },
+            rate_limits: RateLimits {
+                default: RateLimit {
+                    rps: parsed("HCM_RATE_LIMIT_RPS", 10.0),
+                    burst: parsed("HCM_RATE_LIMIT_BURST", 20.0),
+                    in_flight: parsed("HCM_MAX_IN_FLIGHT", 8),
+                },
+                overrides: parsed_pairs("HCM_RATE_LIMITS", &raw("HCM_RATE_LIMITS"), parse_rate_limit, &mut problems),
+                wait: Duration::from_millis(parsed("HCM_RATE_LIMIT_WAIT_MS", 5000)),
+            },
+            page_size: parsed("HCM_PAGE_SIZE", 100),
+            max_items: parsed("HCM_MAX_ITEMS", 2000),
+            cache: Cache {
+                ttls: parsed_pairs(
+                    "HCM_CACHE_TTLS",
+                    &raw("HCM_CACHE_TTLS"),
+                    |secs| secs.parse().ok().map(Duration::from_secs).ok_or("seconds"),
+                    &mut problems,
+                ),
+                etags: lookup("HCM_CACHE_ETAGS").map(|value| resources(&value)),
+                max_entries: parsed("HCM_CACHE_MAX_ENTRIES", 1000),
+            },
+            coalesce_gets: parsed("HCM_COALESCE_GETS", true),
+            projection_uom: if parsed("HCM_PROJECTION_UOM", "H".to_string()) == "D" { "D" } else { "H" },
+            projection_hours_per_day: parsed("HCM_PROJECTION_HOURS_PER_DAY", 7.6),
+            person_claim_is_person_id: parsed("MCP_AUTHZ_PERSON_CLAIM_TYPE", String::new()) == "person_id",
+            max_chain_depth: parsed("MCP_AUTHZ_MAX_CHAIN_DEPTH", 10),
+            audit: Audit {
+                max_bytes: parsed("MCP_AUDIT_MAX_BYTES", 104_857_600),
+                max_files: parsed("MCP_AUDIT_MAX_FILES", 10),
+            },
+            problems,
+        }
+    }
+}
+
+/// The value of setting `name` as `T`, or `default` when it is unset. A value
+/// its [`Kind`] rejects also reads as `default`, but [`Config::validate`]
+/// reports it, so the server doesn't start with it.
+fn parsed<T: FromStr>(name: &str, default: T) -> T {
+    let Some(setting) = find(name) else {
+        return default;
+    };
+    lookup(name)
+        .map(|value| value.trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|value| setting.kind.check(value).is_ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// The `resource=value` entries of setting `name`, keyed by resource, with
+/// `parse` reading each value or naming what it expected. Bad entries are left
+/// out and added to `problems`.
+fn parsed_pairs<T>(
+    name: &str,
+    value: &str,
+    parse: impl Fn(&str) -> Result<T, &'static str>,
+    problems: &mut Vec<String>,
+) -> HashMap<String, T> {
+    pairs(value.trim_matches(|c| c == '"' || c == '\''))
+        .filter_map(|entry| {
+            let (resource, value) = entry.split_once('=')?;
+            match parse(value.trim()) {
+                Ok(parsed) => Some((resource.trim().trim_matches('/').to_string(), parsed)),
+                Err(expected) => {
+                    problems.push(format!("{name}: expected resource={expected}, not {entry:?}"));
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// The resources named in a comma-separated value.
+fn resources(value: &str) -> HashSet<String> {
+    pairs(value.trim_matches(|c| c == '"' || c == '\''))
+        .map(|resource| resource.trim_matches('/').to_string())
+        .collect()
+}
+
+/// Parses `rps:burst:in_flight` with the same bounds as the default limits.
+fn parse_rate_limit(limits: &str) -> Result<RateLimit, &'static str> {
+    const EXPECTED: &str = "rps:burst:in_flight with rps of at least 0.001, burst of at least 1 and in_flight of at least 1";
+    let in_range = |name: &str, value: &str| find(name).is_some_and(|setting| setting.kind.check(value).is_ok());
+    let parts: Vec<&str> = limits.split(':').map(str::trim).collect();
+    let [rps, burst, in_flight] = parts.as_slice() else {
+        return Err(EXPECTED);
+    };
+    if !(in_range("HCM_RATE_LIMIT_RPS", rps)
+        && in_range("HCM_RATE_LIMIT_BURST", burst)
+        && in_range("HCM_MAX_IN_FLIGHT", in_flight))
+    {
+        return Err(EXPECTED);
+    }
+    match (rps.parse(), burst.parse(), in_flight.parse()) {
+        (Ok(rps), Ok(burst), Ok(in_flight)) => Ok(RateLimit { rps, burst, in_flight }),
+        _ => Err(EXPECTED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_outside_their_range_are_rejected() {
+        let kind = |name: &str| find(name).map_or(Kind::Text, |setting| setting.kind);
+        let percent = kind("HCM_BREAKER_FAILURE_PERCENT");
+        assert!(percent.check("50").is_ok());
+        assert_eq!(percent.check("500"), Err("a whole number from 1 to 100".to_string()));
+        assert!(percent.check("0").is_err());
+
+        let rps = kind("HCM_RATE_LIMIT_RPS");
+        assert!(rps.check("0.5").is_ok());
+        assert_eq!(rps.check("1e-20"), Err("a number from 0.001 to 1000000".to_string()));
+        assert!(rps.check("NaN").is_err());
+        assert!(rps.check("inf").is_err());
+
+        assert_eq!(kind("HCM_MAX_ITEMS").check("0"), Err("a whole number of at least 1".to_string()));
+        assert_eq!(kind("HCM_RETRY_BUDGET_MS").check("-1"), Err("a whole number".to_string()));
+        assert!(kind("HCM_PROJECTION_HOURS_PER_DAY").check("25").is_err());
+    }
+
+    #[test]
+    fn rate_limit_overrides_are_parsed_and_validated() {
+        let mut problems = Vec::new();
+        let overrides = parsed_pairs(
+            "HCM_RATE_LIMITS",
+            " /absences/action/loadProjectedBalance/ = 1:2:3, publicWorkers=1e-20:2:3",
+            parse_rate_limit,
+            &mut problems,
+        );
+        assert_eq!(
+            overrides.get("absences/action/loadProjectedBalance"),
+            Some(&RateLimit { rps: 1.0, burst: 2.0, in_flight: 3 })
+        );
+        assert!(!overrides.contains_key("publicWorkers"));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("\"publicWorkers=1e-20:2:3\""), "{problems:?}");
+
+        for limits in ["0:2:3", "1:0.5:3", "1:2:0", "1:2", "1:2:3:4", "fast:2:3"] {
+            assert!(parse_rate_limit(limits).is_err(), "{limits} must be rejected");
+        }
+    }
+
+    #[test]
+    fn cache_ttls_must_be_seconds() {
+        let mut problems = Vec::new();
+        let ttls = parsed_pairs(
+            "HCM_CACHE_TTLS",
+            "publicWorkers=300,planBalances=soon",
+            |secs| secs.parse().ok().map(Duration::from_secs).ok_or("seconds"),
+            &mut problems,
+        );
+        assert_eq!(ttls.get("publicWorkers"), Some(&Duration::from_mins(5)));
+        assert_eq!(problems, ["HCM_CACHE_TTLS: expected resource=seconds, not \"planBalances=soon\""]);
+    }
+
+    fn file(toml: &str) -> Result<ConfigFile> {
+        let table: toml::Table = toml.parse()?;
+        let mut values = HashMap::new();
+        flatten(&table, "", &mut values)?;
+        Ok(ConfigFile { path: PathBuf::from("mcp.toml"), values })
+    }
+
+    /// The file's value for each setting, by variable.
+    fn values(file: &ConfigFile) -> HashMap<&str, &str> {
+        file.values.iter().map(|(name, value)| (name.as_str(), value.value.as_str())).collect()
+    }
+
+    /// Resolves the configuration without a command line, from `env` and `file`.
+    fn resolved(env: &[(&str, &str)], file: Option<&ConfigFile>) -> Config {
+        let command = Command::new("test");
+        let matches = command.clone().get_matches_from(["test"]);
+        let env: HashMap<String, String> = env.iter().map(|(name, value)| ((*name).to_string(), (*value).to_string())).collect();
+        Config::resolve_from(&command, &matches, file, |name| env.get(name).cloned())
+    }
+
+    fn entry<'a>(config: &'a Config, name: &str) -> Option<&'a Entry> {
+        config.entries.iter().find(|entry| entry.name == name)
+    }
+
+    #[test]
+    fn file_tables_flatten_to_variable_names() -> Result<()> {
+        let file = file(
+            r#"
+            rest_framework_version = "9"
+
+            [hcm]
+            base_url = "https://hcm.example.com"
+            retry.max_retries = 5
+
+            [hcm.cache.ttls]
+            publicWorkers = 300
+            planBalances = 60
+
+            [hcm.env.prod]
+            base_url = "https://prod.example.com"
+            password-file = "/run/secrets/prod"
+
+            [mcp.redact]
+            fields = ["DisplayName", "Email"]
+            "#,
+        )?;
+        let values = values(&file);
+        assert_eq!(values["REST_FRAMEWORK_VERSION"], "9");
+        assert_eq!(values["HCM_BASE_URL"], "https://hcm.example.com");
+        assert_eq!(values["HCM_RETRY_MAX_RETRIES"], "5");
+        assert_eq!(values["HCM_ENV_PROD_BASE_URL"], "https://prod.example.com");
+        assert_eq!(values["HCM_ENV_PROD_PASSWORD_FILE"], "/run/secrets/prod");
+        assert_eq!(values["MCP_REDACT_FIELDS"], "DisplayName,Email");
+        let mut ttls: Vec<&str> = values["HCM_CACHE_TTLS"].split(',').collect();
+        ttls.sort_unstable();
+        assert_eq!(ttls, ["planBalances=60", "publicWorkers=300"]);
+        assert_eq!(file.values["HCM_RETRY_MAX_RETRIES"].key, "hcm.retry.max_retries");
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        for toml in [
+            "[hcm]\nbase_ur = \"https://hcm.example.com\"",
+            "[hcm.env.prod]\ncolour = \"blue\"",
+            "[mcp.redact]\nfields = [[\"DisplayName\"]]",
+        ] {
+            assert!(file(toml).is_err(), "{toml} was accepted");
+        }
+        let Err(e) = file("[hcm]\nbase_ur = \"https://hcm.example.com\"") else {
+            unreachable!("base_ur was accepted")
+        };
+        assert_eq!(e.to_string(), "unknown key `hcm.base_ur` (no setting named HCM_BASE_UR)");
+    }
+
+    #[test]
+    fn command_line_beats_environment_beats_file() -> Result<()> {
+        let file = file(
+            r#"
+            [hcm]
+            page_size = 50
+            max_items = 100
+            environments = "prod"
+            env.prod.base_url = "https://prod.example.com"
+
+            [mcp]
+            auth.leeway_secs = 10
+            readyz.cache_secs = 20
+            "#,
+        )?;
+        // Flags take the file's values as defaults, see `with_file_defaults`
+        let command = Command::new("test")
+            .arg(clap::Arg::new("leeway").long("leeway").env("MCP_AUTH_LEEWAY_SECS").default_value("10"))
+            .arg(clap::Arg::new("readyz_cache").long("readyz-cache").env("MCP_READYZ_CACHE_SECS").default_value("20"))
+            .arg(clap::Arg::new("port").long("port").env("MCP_PORT").default_value("8080"));
+        let matches = command.clone().try_get_matches_from(["test", "--leeway", "5"])?;
+        let env = |name: &str| (name == "HCM_PAGE_SIZE").then(|| "200".to_string());
+        let config = Config::resolve_from(&command, &matches, Some(&file), env);
+
+        let value = |name: &str| entry(&config, name).map(|entry| (entry.value.as_str(), entry.describe()));
+        assert!(matches!(value("MCP_AUTH_LEEWAY_SECS"), Some(("5", from)) if from.ends_with("(from command line)")));
+        assert!(matches!(value("HCM_PAGE_SIZE"), Some(("200", from)) if from.ends_with("(from environment)")));
+        assert!(matches!(value("HCM_MAX_ITEMS"), Some(("100", from)) if from.contains("`hcm.max_items`")));
+        assert!(matches!(value("MCP_READYZ_CACHE_SECS"), Some(("20", from)) if from.contains("`mcp.readyz.cache_secs`")));
+        // A built-in default isn't configuration
+        assert!(value("MCP_PORT").is_none());
+        // Per-environment values of the environments the file lists
+        assert!(matches!(value("HCM_ENV_PROD_BASE_URL"), Some(("https://prod.example.com", _))));
+        Ok(())
+    }
+
+    #[test]
+    fn settings_that_depend_on_each_other_are_checked_per_environment() {
+        let config = resolved(
+            &[
+                ("MCP_TLS_CERT_FILE", "/tls/cert.pem"),
+                ("HCM_PASSWORD", "hunter2"),
+                ("HCM_PASSWORD_FILE", "/run/secrets/hcm"),
+                ("HCM_ENVIRONMENTS", "prod,test"),
+                ("HCM_ENV_PROD_OAUTH_CLIENT_SECRET", "s3cret"),
+                ("HCM_ENV_PROD_OAUTH_CLIENT_SECRET_FILE", "/run/secrets/prod"),
+                // Overriding the shared file with a value of its own is fine
+                ("HCM_ENV_TEST_PASSWORD", "test"),
+            ],
+            None,
+        );
+        let problems = config.combination_problems(&["prod".to_string(), "test".to_string()]);
+        assert_eq!(
+            problems,
+            [
+                "MCP_TLS_CERT_FILE is set, so MCP_TLS_KEY_FILE must be set too",
+                "HCM_PASSWORD and HCM_PASSWORD_FILE can't both be set",
+                "HCM_ENV_PROD_OAUTH_CLIENT_SECRET and HCM_ENV_PROD_OAUTH_CLIENT_SECRET_FILE can't both be set",
+            ]
+        );
+    }
+
+    #[test]
+    fn variables_of_unlisted_environments_are_reported() {
+        let names = ["HCM_BASE_URL", "HCM_ENV_PROD_BASE_URL", "HCM_ENV_TEST_BASE_URL", "HCM_ENV_PROD_COLOUR"];
+        let problems = unlisted_in(names.into_iter().map(str::to_string), &["prod".to_string()]);
+        assert_eq!(
+            problems,
+            [
+                "HCM_ENV_PROD_COLOUR is not a per-environment setting",
+                "HCM_ENV_TEST_BASE_URL is set, but HCM_ENVIRONMENTS doesn't list test",
+            ]
+        );
+    }
+
+    #[test]
+    fn print_config_redacts_secrets() {
+        let config = resolved(
+            &[("HCM_USERNAME", "HCM.INTEGRATION"), ("HCM_PASSWORD", "hunter2"), ("MCP_AUDIT_KEY", "audit-key")],
+            None,
+        );
+        let printed = config.to_string();
+        assert!(printed.contains("HCM_USERNAME=\"HCM.INTEGRATION\" (from environment)"), "{printed}");
+        assert!(printed.contains(&format!("HCM_PASSWORD={REDACTED:?} (from environment)")), "{printed}");
+        assert!(!printed.contains("hunter2") && !printed.contains("audit-key"), "{printed}");
+    }
+}
@@ -4,22 +4,77 @@
 //! Provides AI assistants with tools to query employee data, absence balances, and leave information.
 //!
 //! # Architecture
-//! - **Transport**: Streamable HTTP (SSE-based) for real-time communication
+//! - **Transport**: Streamable HTTP (SSE-based) for real-time communication, or stdio
+//!   for desktop MCP clients that launch the server as a child process
 //! - **Protocol**: MCP 1.0 with JSON-RPC 2.0
 //! - **API**: Oracle HCM REST API with rustls-native TLS
-//! - **Observability**: Structured logging with tracing
+//! - **Observability**: Structured logging with tracing, with credentials and PII redacted,
+//!   optional OTLP export of traces and logs with W3C trace context propagation,
+//!   and Prometheus metrics
+//! - **Audit**: Optional hash-chained JSONL record of every tool call (see `mcp::audit`)
 //!
 //! # Configuration
-//! All configuration is via environment variables (see `.env.example`):
+//! All configuration is via environment variables (see `.env.example`), a TOML
+//! file given with `--config`, or both; environment variables win (see `config`):
+//! - `MCP_CONFIG_FILE`: TOML configuration file (optional, same as `--config`)
 //! - `HCM_BASE_URL`: Your Oracle HCM instance URL (required)
-//! - `HCM_PASSWORD`: API password (required)
-//! - `HCM_USERNAME`: API username (optional, defaults to "`WBC_HR_AGENT`")
+//! - `HCM_PASSWORD`: API password (required for Basic auth), or `HCM_PASSWORD_FILE` holding it
+//! - `HCM_SECRET_RELOAD_SECS`: How often secret files are checked for rotation (optional, defaults to 30)
+//! - `HCM_AUTH_MODE`: `basic`, `client_credentials` or `jwt_assertion` (optional, defaults to "basic")
+//! - `HCM_USERNAME`: API username (required for Basic auth)
 //! - `HCM_API_VERSION`: API version (optional, defaults to "11.13.18.05")
 //! - `REST_FRAMEWORK_VERSION`: Framework version (optional, defaults to "9")
+//! - `HCM_ENVIRONMENTS` / `HCM_DEFAULT_ENVIRONMENT`: Named HCM pods served side by side, each
+//!   configured by `HCM_ENV_<NAME>_*` variables (optional, see `mcp::http::environment`)
+//! - `HCM_PROJECTION_UOM` / `HCM_PROJECTION_HOURS_PER_DAY`: Unit of measure and hours per day for
+//!   balance projections when HCM has none (optional, defaults to "H" and 7.6)
 //! - `RUST_LOG`: Logging level (optional, defaults to "info")
+//! - `MCP_TRANSPORT`: `http` or `stdio` (optional, defaults to "http", same as `--transport`)
+//! - `MCP_HOST` / `MCP_PORT`: HTTP listener address (optional, defaults to `0.0.0.0:8080`)
+//! - `MCP_TLS_CERT_FILE` / `MCP_TLS_KEY_FILE`: PEM files enabling native HTTPS (optional)
+//! - `MCP_TLS_RELOAD_INTERVAL_SECS`: How often rotated certificates are picked up (optional, defaults to 30)
+//! - `MCP_AUTH_JWKS_URL` / `MCP_AUTH_JWKS_FILE`: JWKS enabling bearer-token auth on `/mcp` (optional)
+//! - `MCP_AUTH_ISSUER` / `MCP_AUTH_AUDIENCE`: Required `iss`/`aud` claims (required with a JWKS)
+//! - `MCP_AUTH_LEEWAY_SECS`: Clock skew tolerated for `exp`/`nbf` (optional, defaults to 60)
+//! - `MCP_ENVIRONMENT_CLAIM`: Token claim listing the HCM environments a caller may use (optional)
+//! - `MCP_READYZ_CHECK_HCM`: Make `/readyz` check HCM connectivity and credentials (optional, defaults to false)
+//! - `MCP_READYZ_REQUIRE`: `default` or `all`, the HCM environments that must pass that check (optional, defaults to "default")
+//! - `MCP_READYZ_CACHE_SECS`: How long a readiness result is reused (optional, defaults to 30)
+//! - `MCP_REDACT_HEADERS` / `MCP_REDACT_FIELDS`: Extra headers and JSON fields redacted from logs (optional)
+//! - `MCP_AUDIT_FILE`: JSONL audit log of tool calls (optional, disabled when unset)
+//! - `MCP_AUDIT_MAX_BYTES` / `MCP_AUDIT_MAX_FILES`: Audit log rotation (optional, defaults to 100 MiB and 10 files)
+//! - `MCP_ORGANIZATION_NAME` / `MCP_WORKER_NUMBER_LABEL` / `MCP_WORKER_NUMBER_EXAMPLE` / `MCP_PERSON_ID_EXAMPLE`:
+//!   Organisation-specific wording of tool descriptions (optional, see `mcp::naming`)
+//! - `MCP_INSTRUCTIONS`: Server instructions sent to clients (optional)
+//! - `MCP_TOOL_NAMES`: Tool renames as `builtin=name`, comma-separated (optional)
+//! - `OTEL_EXPORTER_OTLP_ENDPOINT`, `OTEL_LOGS_EXPORTER` and other `OTEL_*` variables: OTLP export (optional, see `telemetry`)
+//!
+//! Every variable above prefixed with `MCP_` also has a matching CLI flag (see `--help`).
 //!
 //! # Server Endpoints
-//! - `POST /mcp`: MCP protocol endpoint (JSON-RPC over SSE)
+//! - `POST /mcp`: MCP protocol endpoint (JSON-RPC over SSE), requires
+//!   `Authorization: Bearer <jwt>` when a JWKS is configured
+//! - `POST /mcp/{environment}`: The same, with sessions bound to one HCM environment
+//! - `POST /cache/invalidate`: Drops cached HCM responses, optionally for one `?resource=`
+//!   (same authentication as `/mcp`)
+//! - `GET /metrics`: Prometheus metrics for tool calls and HCM requests (unauthenticated)
+//! - `GET /healthz` / `GET /readyz`: Liveness and readiness probes (unauthenticated)
+//!
+//! # Healthcheck
+//! `oracle-hcm-mcp healthcheck` calls `/readyz` (or `/healthz` with `--liveness`)
+//! on the local listener and exits non-zero on failure, for images without curl.
+//!
+//! # Printing the Configuration
+//! `oracle-hcm-mcp print-config` prints each setting that is set and where its
+//! value came from, with secrets redacted, and exits non-zero if it is invalid.
+//!
+//! # Audit Verification
+//! `oracle-hcm-mcp verify-audit <files>...` checks the audit log's hash chain
+//! and exits non-zero at the first modified, missing or reordered record.
+//!
+//! # Stdio Transport
+//! With `--transport stdio` the same tool router is served over stdin/stdout.
+//! Logs are written to stderr so they never corrupt the JSON-RPC stream.
 //!
 //! # Graceful Shutdown
 //! The server handles SIGINT (Ctrl+C) gracefully by:
@@ -28,22 +83,23 @@
 //! 3. Cleaning up resources before exit
 
 use anyhow::Result;
-use axum::{Router, serve};
 use dotenv::dotenv;
-use rmcp::transport::{
-    StreamableHttpServerConfig, StreamableHttpService,
-    streamable_http_server::session::local::LocalSessionManager,
-};
-use std::io::Error as IoError;
-use tokio::{net::TcpListener, signal::ctrl_c};
+use rmcp::{ServiceExt, transport::stdio};
+use std::io::{stderr, stdout};
+use tokio::signal::ctrl_c;
 use tracing::info;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 
+mod cli;
+mod config;
 mod mcp;
-use mcp::OracleHCMMCPFactory;
-
-/// Server bind address - listens on all interfaces, port 8080
-const BIND_ADDRESS: &str = "0.0.0.0:8080";
+mod metrics;
+mod server;
+mod telemetry;
+use cli::{Cli, Command, Transport};
+use mcp::{audit, http::watch_secrets, redact::RedactingMakeWriter, OracleHCMMCPFactory};
+use server::{health, serve_http};
+use telemetry::Telemetry;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -51,51 +107,83 @@ async fn main() -> Result<()> {
     // In production, env vars should be set by the container runtime
     dotenv().ok();
 
-    // Initialize structured logging
-    // Supports RUST_LOG env var for filtering (e.g., RUST_LOG=debug,oracle_hcm_mcp=trace)
-    // Default level is "info" for production use
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Parse CLI flags after loading .env so env-backed flags pick up its values,
+    // with the `--config` file underneath both
+    let (cli, config) = Cli::load()?;
+
+    match &cli.command {
+        // Container healthchecks run the binary itself, since the image has no curl
+        Some(Command::Healthcheck { liveness }) => {
+            let path = if *liveness { "/healthz" } else { "/readyz" };
+            return health::probe(&cli.local_url(path)).await;
+        }
+        Some(Command::VerifyAudit { files, from_seq }) => {
+            let verified = audit::verify(files, *from_seq)?;
+            println!("{} audit records verified", verified.records);
+            if let Some((seq, hash)) = verified.head {
+                println!("last record: seq {seq}, hash {hash}");
+            }
+            return Ok(());
+        }
+        Some(Command::PrintConfig) => {
+            print!("{config}");
+            return config.validate();
+        }
+        None => {}
+    }
+
+    // Stdout carries the JSON-RPC stream in stdio mode, so logs must go to stderr.
+    // Every line is scrubbed of credentials and PII before it is written.
+    let log_writer = match cli.transport {
+        Transport::Http => BoxMakeWriter::new(RedactingMakeWriter::new(stdout)),
+        Transport::Stdio => BoxMakeWriter::new(RedactingMakeWriter::new(stderr)),
+    };
+
+    // Install logging, plus OTLP trace/log export when OTEL_* variables configure it
+    let telemetry = Telemetry::init(log_writer)?;
 
     info!("Oracle HCM MCP Server starting...");
-    info!("Bind address: {}", BIND_ADDRESS);
-
-    // Create the MCP service with Oracle HCM tools
-    // Uses local session management (in-memory, suitable for single-instance deployment)
-    let service = StreamableHttpService::new(
-        || {
-            // Factory function called for each new MCP session
-            // Validates configuration early and fails fast if env vars are missing
-            OracleHCMMCPFactory::new()
-                .map_err(IoError::other)
-        },
-        LocalSessionManager::default().into(),
-        StreamableHttpServerConfig::default(),
-    );
-
-    // Build HTTP router with the MCP endpoint
-    // All MCP communication happens through POST /mcp
-    let router = Router::new().nest_service("/mcp", service);
-    
-    // Bind to the configured address
-    let tcp_listener = TcpListener::bind(BIND_ADDRESS).await?;
-    info!("Server listening on {}", BIND_ADDRESS);
-    info!("MCP endpoint available at http://{}/mcp", BIND_ADDRESS);
-
-    // Start the server with graceful shutdown on Ctrl+C
-    // This ensures all in-flight requests complete before shutdown
-    serve(tcp_listener, router)
-        .with_graceful_shutdown(async {
-            let _ = ctrl_c().await;
-            info!("Received shutdown signal, draining connections...");
-        })
-        .await?;
+
+    // Fail fast with every configuration problem, rather than on the first tool call
+    if let Err(e) = config.validate() {
+        telemetry.shutdown();
+        return Err(e);
+    }
+
+    // Pick up rotated HCM passwords, client secrets and keys without a restart
+    watch_secrets();
+
+    let served = match cli.transport {
+        Transport::Http => serve_http(cli.listener(), cli.auth(), cli.health()).await,
+        Transport::Stdio => serve_stdio().await,
+    };
+
+    // Flush buffered spans and logs even when serving failed
+    telemetry.shutdown();
+    served?;
 
     info!("Server shut down gracefully");
     Ok(())
 }
+
+/// Serves the MCP tool router over stdin/stdout until the client disconnects
+/// or a shutdown signal is received.
+async fn serve_stdio() -> Result<()> {
+    info!("Serving MCP over stdio");
+
+    // A stdio server hosts exactly one session, so configuration is validated here
+    let service = OracleHCMMCPFactory::new()?.serve(stdio()).await?;
+    let cancellation_token = service.cancellation_token();
+
+    tokio::select! {
+        quit_reason = service.waiting() => {
+            info!("Stdio session ended: {:?}", quit_reason?);
+        }
+        _ = ctrl_c() => {
+            info!("Received shutdown signal, closing stdio session...");
+            cancellation_token.cancel();
+        }
+    }
+
+    Ok(())
+}
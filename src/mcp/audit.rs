@@ -0,0 +1,559 @@
+//! Tamper-evident audit log of tool invocations.
+//!
+//! Every tool call appends one JSON line recording who looked up whose data:
+//! - **Who**: the caller's token subject and HCM identity claim, and the MCP session
+//! - **What**: the tool, the HCM environment, its parameters and the target `PersonId`s / worker numbers
+//! - **Result**: the outcome (`ok`, `tool_error` or the error kind), error message and latency
+//!
+//! The audit log is written to its own file, never to the `tracing` output, and
+//! its records are not redacted: showing whose data was read is its purpose.
+//!
+//! # Hash chain
+//! Each record carries a `seq` number, the previous record's hash (`prevHash`)
+//! and its own `hash`, always the last field: the SHA-256 of the exact line
+//! written, up to that field. Editing, inserting or deleting a record breaks
+//! the chain, which [`verify`] (the `verify-audit` subcommand) detects. The
+//! chain starts at `seq` 1 and continues across rotated files and server restarts.
+//!
+//! A plain SHA-256 chain only catches accidental edits: anyone who can write
+//! the file can also recompute every hash after it. With `MCP_AUDIT_KEY` set,
+//! hashes are HMAC-SHA256 under that key instead, so forging records needs the
+//! key too; keep it away from the hosts that store the log.
+//!
+//! Records cut off the end of the log leave a valid chain. `verify-audit`
+//! prints the last `seq` and `hash` to compare against a copy kept elsewhere,
+//! such as the chain head logged at each rotation.
+//!
+//! # Rotation
+//! When the file would grow past `MCP_AUDIT_MAX_BYTES`, it is renamed to
+//! `<file>.1` (older files shift to `.2`, `.3`, ...) and a new file is started.
+//! Files beyond `MCP_AUDIT_MAX_FILES` are deleted, so ship them elsewhere first
+//! if they must be kept.
+//!
+//! # Configuration
+//! - `MCP_AUDIT_FILE` (optional): Path of the JSONL audit log; auditing is off when unset
+//! - `MCP_AUDIT_MAX_BYTES` (optional): Size at which the file is rotated, defaults to 104857600 (100 MiB)
+//! - `MCP_AUDIT_MAX_FILES` (optional): Rotated files kept, defaults to 10
+//! - `MCP_AUDIT_KEY` / `MCP_AUDIT_KEY_FILE` (optional): HMAC key for the hash chain, inline or from a file
+
+use anyhow::{Context, Result, bail};
+use axum::http::request;
+use chrono::{SecondsFormat, Utc};
+use rmcp::{ErrorData, RoleServer, model::CallToolResult, service::RequestContext};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Write as _,
+    fs::{self, File, OpenOptions},
+    future::Future,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{LazyLock, Mutex, PoisonError},
+    time::Instant,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::tuning,
+    mcp::{
+        authz::hcm_identity,
+        caller::Caller,
+        error::outcome,
+        http::{HcmEnvironment, load_env_var_or},
+    },
+};
+
+/// `prevHash` of the first record ever written.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How far back from the end of an existing file to look for its last record.
+const TAIL_WINDOW: u64 = 64 * 1024;
+
+/// The configured audit log, or `None` when auditing is off.
+static AUDIT_LOG: LazyLock<Option<AuditLog>> = LazyLock::new(|| {
+    let path = load_env_var_or("MCP_AUDIT_FILE", "");
+    if path.is_empty() {
+        warn!("Audit logging is disabled; set MCP_AUDIT_FILE to record tool invocations");
+        return None;
+    }
+    let key = match audit_key() {
+        Ok(key) => key,
+        Err(e) => {
+            error!("Audit logging is disabled: {e:#}");
+            return None;
+        }
+    };
+    info!("Auditing tool invocations to {path}");
+    Some(AuditLog {
+        path: PathBuf::from(path),
+        max_bytes: tuning().audit.max_bytes,
+        max_files: tuning().audit.max_files,
+        key,
+        sink: Mutex::new(None),
+    })
+});
+
+/// The HMAC key from `MCP_AUDIT_KEY_FILE` or `MCP_AUDIT_KEY`, `None` when neither is set.
+fn audit_key() -> Result<Option<Vec<u8>>> {
+    let file = load_env_var_or("MCP_AUDIT_KEY_FILE", "");
+    let key = if file.is_empty() {
+        load_env_var_or("MCP_AUDIT_KEY", "")
+    } else {
+        fs::read_to_string(&file).with_context(|| format!("cannot read MCP_AUDIT_KEY_FILE {file}"))?
+    };
+    let key = key.trim();
+    Ok((!key.is_empty()).then(|| key.as_bytes().to_vec()))
+}
+
+// ============================================================================
+// Recording tool calls
+// ============================================================================
+
+/// One tool invocation being audited, started before the call runs.
+pub struct AuditEntry {
+    tool: &'static str,
+    caller: Option<Caller>,
+    session_id: Option<String>,
+    params: Value,
+    timestamp: String,
+    started: Instant,
+}
+
+impl AuditEntry {
+    /// Captures the caller, session and parameters of a tool call.
+    pub fn new<P: Serialize>(tool: &'static str, context: &RequestContext<RoleServer>, params: &P) -> Self {
+        let session_id = context
+            .extensions
+            .get::<request::Parts>()
+            .and_then(|parts| parts.headers.get("mcp-session-id"))
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        // Parameters are only needed when the record will be written
+        let params = if AUDIT_LOG.is_some() {
+            serde_json::to_value(params).unwrap_or(Value::Null)
+        } else {
+            Value::Null
+        };
+        Self {
+            tool,
+            caller: Caller::from_context(context),
+            session_id,
+            params,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            started: Instant::now(),
+        }
+    }
+
+    /// Runs the tool call and appends its audit record, naming the current HCM environment.
+    ///
+    /// A record that can't be written is logged as an error; the call's result is returned regardless.
+    pub async fn record<F>(self, call: F) -> Result<CallToolResult, ErrorData>
+    where
+        F: Future<Output = Result<CallToolResult, ErrorData>>,
+    {
+        let result = call.await;
+        if let Some(log) = AUDIT_LOG.as_ref()
+            && let Err(e) = log.append(self.into_record(&result))
+        {
+            error!("Failed to write audit record to {}: {e}", log.path.display());
+        }
+        result
+    }
+
+    fn into_record(self, result: &Result<CallToolResult, ErrorData>) -> Value {
+        let (person_ids, worker_numbers) = targets(&self.params);
+        json!({
+            "timestamp": self.timestamp,
+            "caller": self.caller.as_ref().map(|caller| caller.subject.as_str()),
+            "callerHcmId": self.caller.as_ref().and_then(hcm_identity),
+            "sessionId": self.session_id,
+            "tool": self.tool,
+            "environment": HcmEnvironment::current().ok().map(|environment| environment.name.as_str()),
+            "targetPersonIds": person_ids,
+            "targetWorkerNumbers": worker_numbers,
+            "params": self.params,
+            "outcome": outcome(result),
+            "error": result.as_ref().err().map(|e| e.message.as_ref()),
+            "latencyMs": u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX),
+        })
+    }
+}
+
+/// The `PersonId`s and worker numbers a tool call targets, taken from
+/// parameters named like `hcm_person_id` and `worker_number`.
+fn targets(params: &Value) -> (Vec<String>, Vec<String>) {
+    let mut person_ids = Vec::new();
+    let mut worker_numbers = Vec::new();
+    let Some(params) = params.as_object() else {
+        return (person_ids, worker_numbers);
+    };
+    for (name, value) in params {
+        let Some(value) = value.as_str() else { continue };
+        let name = name.replace('_', "").to_ascii_lowercase();
+        if name.ends_with("personid") {
+            person_ids.push(value.to_string());
+        } else if name.ends_with("employeeid") || name.ends_with("workernumber") {
+            worker_numbers.push(value.to_string());
+        }
+    }
+    (person_ids, worker_numbers)
+}
+
+// ============================================================================
+// JSONL sink with hash chain and rotation
+// ============================================================================
+
+struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    /// HMAC key for the chain, `None` for plain SHA-256
+    key: Option<Vec<u8>>,
+    /// Opened on the first record, so a bad path surfaces in the logs rather than at startup
+    sink: Mutex<Option<Sink>>,
+}
+
+/// The open file and the end of the hash chain.
+struct Sink {
+    file: File,
+    size: u64,
+    seq: u64,
+    last_hash: String,
+}
+
+impl AuditLog {
+    /// Chains `record` onto the log and appends it as one line.
+    fn append(&self, mut record: Value) -> Result<()> {
+        let mut sink = self.sink.lock().unwrap_or_else(PoisonError::into_inner);
+        if sink.is_none() {
+            *sink = Some(self.open()?);
+        }
+        let Some(state) = sink.as_mut() else {
+            bail!("audit log is not open");
+        };
+
+        record["seq"] = json!(state.seq + 1);
+        record["prevHash"] = json!(state.last_hash);
+        let (mut line, hash) = seal(&record, self.key.as_deref());
+        line.push('\n');
+
+        let len = line.len() as u64;
+        if state.size > 0 && state.size + len > self.max_bytes {
+            state.file = self.rotate(state.seq, &state.last_hash)?;
+            state.size = 0;
+        }
+        state.file.write_all(line.as_bytes())?;
+        state.size += len;
+        state.seq += 1;
+        state.last_hash = hash;
+        drop(sink);
+        Ok(())
+    }
+
+    /// Opens the current file for appending and resumes the chain from the
+    /// last record written, here or in the most recent rotated file.
+    fn open(&self) -> Result<Sink> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("cannot open {}", self.path.display()))?;
+        let size = file.metadata()?.len();
+
+        let last = if size > 0 {
+            last_record(&self.path)?
+        } else {
+            last_record(&rotated_path(&self.path, 1)).unwrap_or(None)
+        };
+        let (seq, last_hash) = last
+            .and_then(|record| Some((record["seq"].as_u64()?, record["hash"].as_str()?.to_string())))
+            .unwrap_or_else(|| (0, GENESIS_HASH.to_string()));
+        Ok(Sink { file, size, seq, last_hash })
+    }
+
+    /// Shifts `<file>.N` to `<file>.N+1`, dropping the oldest, and starts a new file.
+    /// Logs the chain head (`seq` and `hash` of the last record) as a copy kept outside the log.
+    fn rotate(&self, seq: u64, hash: &str) -> Result<File> {
+        let oldest = rotated_path(&self.path, self.max_files);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for n in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        info!(seq, hash, "Rotated audit log {}", self.path.display());
+        Ok(OpenOptions::new().create(true).append(true).open(&self.path)?)
+    }
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+/// Reads the last record of a JSONL file, if it has one.
+fn last_record(path: &Path) -> Result<Option<Value>> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_WINDOW)))?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail)?;
+    let Some(line) = tail.lines().rev().find(|line| !line.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let record = serde_json::from_str(line)
+        .with_context(|| format!("last line of {} is not an audit record", path.display()))?;
+    Ok(Some(record))
+}
+
+/// Serializes `record` and appends its `hash` as the last field, computed
+/// over the exact bytes before it. Returns the line and the hash.
+fn seal(record: &Value, key: Option<&[u8]>) -> (String, String) {
+    let unhashed = record.to_string();
+    let hash = digest(unhashed.as_bytes(), key);
+    let fields = unhashed.strip_suffix('}').unwrap_or(&unhashed);
+    (format!("{fields},\"hash\":\"{hash}\"}}"), hash)
+}
+
+/// Splits a written line into the bytes its hash covers and the hash, undoing [`seal`].
+fn unseal(line: &str) -> Option<(String, &str)> {
+    let (fields, hash) = line.strip_suffix("\"}")?.rsplit_once(",\"hash\":\"")?;
+    Some((format!("{fields}}}"), hash))
+}
+
+/// Hex SHA-256 of `bytes`, or HMAC-SHA256 when a key is given.
+fn digest(bytes: &[u8], key: Option<&[u8]>) -> String {
+    let digest: [u8; 32] = key.map_or_else(|| Sha256::digest(bytes).into(), |key| hmac_sha256(key, bytes));
+    digest.iter().fold(String::with_capacity(64), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let key = if key.len() > BLOCK_SIZE { Sha256::digest(key).to_vec() } else { key.to_vec() };
+    let pad = |byte: u8| -> Vec<u8> {
+        (0..BLOCK_SIZE).map(|i| key.get(i).copied().unwrap_or(0) ^ byte).collect()
+    };
+    let inner = Sha256::new().chain_update(pad(0x36)).chain_update(message).finalize();
+    Sha256::new().chain_update(pad(0x5c)).chain_update(inner).finalize().into()
+}
+
+// ============================================================================
+// Verification
+// ============================================================================
+
+/// The end of a verified chain.
+pub struct Verified {
+    pub records: u64,
+    /// `seq` and `hash` of the last record, `None` if there were no records
+    pub head: Option<(u64, String)>,
+}
+
+/// Checks the hash chain across audit files given oldest first (e.g.
+/// `audit.jsonl.2 audit.jsonl.1 audit.jsonl`), under the configured `MCP_AUDIT_KEY`.
+///
+/// The first record must be record 1, chained to the genesis hash, unless
+/// `from_seq` names the record the files start at because older files were
+/// rotated away. Every later record must hash correctly and link to the one before it.
+///
+/// # Errors
+/// Returns an error naming the first file and line that fails verification.
+pub fn verify(paths: &[PathBuf], from_seq: Option<u64>) -> Result<Verified> {
+    verify_with(paths, from_seq, audit_key()?.as_deref())
+}
+
+fn verify_with(paths: &[PathBuf], from_seq: Option<u64>, key: Option<&[u8]>) -> Result<Verified> {
+    let mut previous: Option<(u64, String)> = None;
+    let mut records = 0;
+    for path in paths {
+        let text = fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+        for (index, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let at = format!("{}:{}", path.display(), index + 1);
+            let Some((unhashed, hash)) = unseal(line) else {
+                bail!("{at}: not an audit record ending in its hash");
+            };
+            let record: Value = serde_json::from_str(&unhashed).with_context(|| format!("{at}: not valid JSON"))?;
+            let (Some(seq), Some(prev_hash)) = (record["seq"].as_u64(), record["prevHash"].as_str()) else {
+                bail!("{at}: missing seq or prevHash");
+            };
+            if digest(unhashed.as_bytes(), key) != hash {
+                bail!("{at}: record {seq} was modified, or MCP_AUDIT_KEY differs (hash mismatch)");
+            }
+            match (&previous, from_seq) {
+                (Some((previous_seq, previous_hash)), _) => {
+                    if seq != previous_seq + 1 || prev_hash != previous_hash {
+                        bail!("{at}: chain broken before record {seq} (a record was removed, inserted or reordered)");
+                    }
+                }
+                (None, None) if seq != 1 || prev_hash != GENESIS_HASH => bail!(
+                    "{at}: the log starts at record {seq}, not 1, so earlier records are missing \
+                     (pass --from-seq {seq} if older files were rotated away)"
+                ),
+                (None, Some(from)) if seq != from => bail!("{at}: the log starts at record {seq}, not {from}"),
+                (None, _) => {}
+            }
+            previous = Some((seq, hash.to_string()));
+            records += 1;
+        }
+    }
+    Ok(Verified { records, head: previous })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for one test's audit files.
+    fn scratch(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("oracle-hcm-mcp-audit-{}-{test}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap_or_else(|e| unreachable!("cannot create {}: {e}", dir.display()));
+        dir
+    }
+
+    fn audit_log(path: &Path, max_bytes: u64, key: Option<&[u8]>) -> AuditLog {
+        AuditLog {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files: 2,
+            key: key.map(<[u8]>::to_vec),
+            sink: Mutex::new(None),
+        }
+    }
+
+    fn append(log: &AuditLog, caller: &str) {
+        log.append(json!({ "caller": caller, "params": { "hoursPerDay": 7.6, "fte": 0.1 } }))
+            .unwrap_or_else(|e| unreachable!("append failed: {e}"));
+    }
+
+    fn records(path: &Path) -> Vec<Value> {
+        fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| unreachable!("bad record {line}: {e}")))
+            .collect()
+    }
+
+    fn error(result: Result<Verified>) -> String {
+        match result {
+            Ok(verified) => unreachable!("verified {} records", verified.records),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn records_chain_from_genesis() {
+        let path = scratch("chain").join("audit.jsonl");
+        let log = audit_log(&path, u64::MAX, None);
+        for caller in ["alice", "bob", "carol"] {
+            append(&log, caller);
+        }
+
+        let records = records(&path);
+        assert_eq!(records.iter().map(|r| r["seq"].as_u64()).collect::<Vec<_>>(), [Some(1), Some(2), Some(3)]);
+        assert_eq!(records[0]["prevHash"], GENESIS_HASH);
+        assert_eq!(records[1]["prevHash"], records[0]["hash"]);
+        assert_eq!(records[2]["prevHash"], records[1]["hash"]);
+
+        // Floats are hashed as written, not as re-serialized
+        let verified = verify_with(&[path], None, None).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(verified.records, 3);
+        assert_eq!(verified.head, Some((3, records[2]["hash"].as_str().unwrap_or_default().to_string())));
+    }
+
+    #[test]
+    fn chain_continues_across_rotations() {
+        let path = scratch("rotation").join("audit.jsonl");
+        // Every record rotates the file; with two rotated files kept, record 1 is dropped
+        let log = audit_log(&path, 1, None);
+        for caller in ["alice", "bob", "carol", "dave"] {
+            append(&log, caller);
+        }
+        let files = [rotated_path(&path, 2), rotated_path(&path, 1), path.clone()];
+        assert!(!rotated_path(&path, 3).exists());
+        assert_eq!(records(&files[0])[0]["seq"], 2);
+
+        assert!(error(verify_with(&files, None, None)).contains("--from-seq 2"));
+        let verified = verify_with(&files, Some(2), None).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(verified.records, 3);
+        assert!(error(verify_with(&files, Some(1), None)).contains("starts at record 2, not 1"));
+    }
+
+    #[test]
+    fn chain_resumes_after_restart() {
+        let path = scratch("resume").join("audit.jsonl");
+        append(&audit_log(&path, u64::MAX, None), "alice");
+        append(&audit_log(&path, u64::MAX, None), "bob");
+
+        // A restart right after rotation resumes from the rotated file
+        fs::rename(&path, rotated_path(&path, 1)).unwrap_or_else(|e| unreachable!("{e}"));
+        append(&audit_log(&path, u64::MAX, None), "carol");
+
+        assert_eq!(records(&path)[0]["seq"], 3);
+        let files = [rotated_path(&path, 1), path];
+        let verified = verify_with(&files, None, None).unwrap_or_else(|e| unreachable!("{e}"));
+        assert_eq!(verified.records, 3);
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let dir = scratch("tamper");
+        let path = dir.join("audit.jsonl");
+        let log = audit_log(&path, u64::MAX, None);
+        for caller in ["alice", "bob", "carol"] {
+            append(&log, caller);
+        }
+        let text = fs::read_to_string(&path).unwrap_or_default();
+        let lines: Vec<&str> = text.lines().collect();
+        let check = |name: &str, lines: &[&str]| {
+            let tampered = dir.join(name);
+            fs::write(&tampered, lines.join("\n")).unwrap_or_else(|e| unreachable!("{e}"));
+            error(verify_with(&[tampered], None, None))
+        };
+
+        let edited = lines[1].replace("bob", "eve");
+        assert!(check("edited", &[lines[0], &edited, lines[2]]).contains("record 2 was modified"));
+        assert!(check("removed", &[lines[0], lines[2]]).contains("chain broken before record 3"));
+        assert!(check("reordered", &[lines[0], lines[2], lines[1]]).contains("chain broken before record 3"));
+        assert!(check("truncated", &[lines[1], lines[2]]).contains("starts at record 2, not 1"));
+    }
+
+    #[test]
+    fn keyed_chain_needs_the_key() {
+        let path = scratch("keyed").join("audit.jsonl");
+        let log = audit_log(&path, u64::MAX, Some(b"audit-key"));
+        append(&log, "alice");
+        append(&log, "bob");
+
+        let files = [path];
+        assert!(verify_with(&files, None, Some(b"audit-key")).is_ok());
+        assert!(error(verify_with(&files, None, None)).contains("MCP_AUDIT_KEY differs"));
+        assert!(error(verify_with(&files, None, Some(b"other-key"))).contains("MCP_AUDIT_KEY differs"));
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        assert_eq!(
+            digest(b"what do ya want for nothing?", Some(b"Jefe")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Test case 6: a key longer than the block size is hashed first
+        assert_eq!(
+            digest(b"Test Using Larger Than Block-Size Key - Hash Key First", Some(&[0xaa; 131])),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Row-level authorization for tools that read a specific employee's data.
+//!
+//! An authenticated caller may query a `PersonId` only when:
+//! - **Self**: the target is the caller's own `PersonId`
+//! - **Manager**: the caller appears in the target's line-management chain
+//! - **HR admin**: the caller's token carries the configured HR-admin claim
+//!
+//! The caller's `PersonId` is derived from a token claim, holding either an
+//! HCM worker number (resolved through `/publicWorkers`) or a `PersonId`.
+//! Management chains are walked upwards from the target through each
+//! assignment's line managers, up to a bounded depth. Each worker's line
+//! managers are reused for 30 seconds, so repeated checks along the same
+//! chain don't each walk it in HCM again.
+//!
+//! Requests without a caller (stdio, or HTTP with authentication disabled)
+//! are not restricted. Every denial is logged and returned as `HcmError::Forbidden`.
+//!
+//! # Configuration
+//! - `MCP_AUTHZ_PERSON_CLAIM` (optional): Claim identifying the caller, defaults to "`worker_number`"
+//! - `MCP_AUTHZ_PERSON_CLAIM_TYPE` (optional): `worker_number` or `person_id`, defaults to "`worker_number`"
+//! - `MCP_AUTHZ_ADMIN_CLAIM` (optional): Claim checked for HR-admin access, defaults to "roles"
+//! - `MCP_AUTHZ_ADMIN_VALUE` (optional): Value granting HR-admin access, defaults to "hr-admin"
+//! - `MCP_AUTHZ_MAX_CHAIN_DEPTH` (optional): Management levels searched, defaults to 10
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::{
+    config::tuning,
+    mcp::{
+        caller::Caller,
+        error::HcmError,
+        http::{child_items, hcm_api_call, load_env_var_or, CallOptions, Filter, HcmEnvironment, HcmQuery, Method},
+        tools::person_id::lookup_person_id,
+    },
+};
+
+// ============================================================================
+// Configuration - Loaded once at startup
+// ============================================================================
+
+/// Token claim identifying the caller in HCM.
+static PERSON_CLAIM: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_AUTHZ_PERSON_CLAIM", "worker_number"));
+
+/// Token claim checked for HR-admin access (a string or an array of strings).
+static ADMIN_CLAIM: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_AUTHZ_ADMIN_CLAIM", "roles"));
+
+/// Claim value granting HR-admin access.
+static ADMIN_VALUE: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_AUTHZ_ADMIN_VALUE", "hr-admin"));
+
+/// How long a worker's line managers are reused; short, so a changed reporting line applies quickly
+const LINE_MANAGERS_TTL: Duration = Duration::from_secs(30);
+
+/// Upper bound on cached reporting lines; expired entries are pruned first
+const MAX_CACHED_LINE_MANAGERS: usize = 4096;
+
+/// Line managers by HCM environment and `PersonId`, with the instant they were looked up.
+type LineManagers = HashMap<(String, String), (Instant, Vec<String>)>;
+
+/// Recently looked-up line managers.
+static LINE_MANAGERS: LazyLock<Mutex<LineManagers>> = LazyLock::new(Mutex::default);
+
+// ============================================================================
+// Authorization checks
+// ============================================================================
+
+/// Checks that `caller` may read data belonging to `target_person_id`.
+///
+/// # Errors
+/// * `Forbidden` - If the caller is neither the target, in their management chain, nor an HR admin
+/// * Any error from resolving the caller or the management chain in HCM
+pub async fn authorize(caller: Option<&Caller>, target_person_id: &str) -> Result<(), HcmError> {
+    let Some(caller) = caller else {
+        return Ok(());
+    };
+
+    if is_hr_admin(caller) {
+        debug!(subject = %caller.subject, %target_person_id, "authorized as HR admin");
+        return Ok(());
+    }
+
+    let caller_person_id = caller_person_id(caller).await?;
+    if caller_person_id == target_person_id {
+        return Ok(());
+    }
+
+    if manages(&caller_person_id, target_person_id).await? {
+        debug!(subject = %caller.subject, %target_person_id, "authorized as manager");
+        return Ok(());
+    }
+
+    warn!(
+        subject = %caller.subject,
+        caller_person_id = %caller_person_id,
+        %target_person_id,
+        "authorization denied"
+    );
+    Err(HcmError::forbidden(format!(
+        "Caller is not permitted to access PersonId {target_person_id}"
+    )))
+}
+
+/// Returns `true` if the caller's admin claim contains the HR-admin value.
+pub fn is_hr_admin(caller: &Caller) -> bool {
+    let admin_value = ADMIN_VALUE.as_str();
+    match caller.claims.get(ADMIN_CLAIM.as_str()) {
+        Some(Value::String(value)) => value.split_whitespace().any(|v| v == admin_value),
+        Some(Value::Array(values)) => values.iter().any(|v| v.as_str() == Some(admin_value)),
+        _ => false,
+    }
+}
+
+/// The caller's HCM identity claim: a worker number or `PersonId`, depending on
+/// `MCP_AUTHZ_PERSON_CLAIM_TYPE`.
+pub fn hcm_identity(caller: &Caller) -> Option<&str> {
+    caller.claim_str(PERSON_CLAIM.as_str())
+}
+
+/// Resolves the caller's own HCM `PersonId` from their token.
+async fn caller_person_id(caller: &Caller) -> Result<String, HcmError> {
+    let claim = PERSON_CLAIM.as_str();
+    let Some(value) = hcm_identity(caller) else {
+        warn!(subject = %caller.subject, "authorization denied: token has no {claim} claim");
+        return Err(HcmError::forbidden(format!(
+            "Caller token has no {claim} claim to identify them in HCM"
+        )));
+    };
+
+    if tuning().person_claim_is_person_id {
+        Ok(value.to_string())
+    } else {
+        lookup_person_id(value).await
+    }
+}
+
+/// Returns `true` if `manager_person_id` is in `target_person_id`'s line-management chain.
+async fn manages(manager_person_id: &str, target_person_id: &str) -> Result<bool, HcmError> {
+    let mut visited = HashSet::from([target_person_id.to_string()]);
+    let mut level = vec![target_person_id.to_string()];
+
+    for _ in 0..tuning().max_chain_depth {
+        let mut next_level = Vec::new();
+        for person_id in &level {
+            for manager in line_managers(person_id).await? {
+                if manager == manager_person_id {
+                    return Ok(true);
+                }
+                if visited.insert(manager.clone()) {
+                    next_level.push(manager);
+                }
+            }
+        }
+        if next_level.is_empty() {
+            break;
+        }
+        level = next_level;
+    }
+
+    Ok(false)
+}
+
+/// Returns the `PersonId`s of the line managers across all of a worker's assignments.
+///
+/// Bypasses the response cache, whose TTLs are tuned for reference data, and
+/// reuses the answer for [`LINE_MANAGERS_TTL`] instead.
+async fn line_managers(person_id: &str) -> Result<Vec<String>, HcmError> {
+    let key = (HcmEnvironment::current()?.name.clone(), person_id.to_string());
+    if let Some(managers) = cached_line_managers(&key) {
+        return Ok(managers);
+    }
+
+    let query = HcmQuery::new("/publicWorkers")
+        .filter(Filter::new().eq("PersonId", person_id))
+        .expand(&["assignments.managers"])
+        .only_data()
+        .limit(1);
+    let options = CallOptions::for_query(&query).bypass_cache();
+    let json = hcm_api_call(&query.build(), Method::GET, None, options).await?;
+
+    let managers: Vec<String> = child_items(&json["items"][0]["assignments"])
+        .flat_map(|assignment| child_items(&assignment["managers"]))
+        .filter(|manager| {
+            manager["ManagerType"]
+                .as_str()
+                .is_none_or(|t| t == "LINE_MANAGER")
+        })
+        .filter_map(|manager| manager_person_id(&manager["ManagerPersonId"]))
+        .collect();
+
+    store_line_managers(key, managers.clone());
+    Ok(managers)
+}
+
+/// Returns a worker's line managers if they were looked up within the TTL.
+fn cached_line_managers(key: &(String, String)) -> Option<Vec<String>> {
+    let cached = LINE_MANAGERS.lock().ok()?;
+    cached
+        .get(key)
+        .filter(|(looked_up, _)| looked_up.elapsed() < LINE_MANAGERS_TTL)
+        .map(|(_, managers)| managers.clone())
+}
+
+/// Caches a worker's line managers, pruning expired entries when the cache is full.
+fn store_line_managers(key: (String, String), managers: Vec<String>) {
+    let Ok(mut cached) = LINE_MANAGERS.lock() else {
+        return;
+    };
+    if cached.len() >= MAX_CACHED_LINE_MANAGERS {
+        cached.retain(|_, (looked_up, _)| looked_up.elapsed() < LINE_MANAGERS_TTL);
+    }
+    if cached.len() < MAX_CACHED_LINE_MANAGERS {
+        cached.insert(key, (Instant::now(), managers));
+    }
+}
+
+/// Reads a `PersonId` that HCM may serialize as a number or a string.
+fn manager_person_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::http::HcmEnvironment;
+    use axum::{Json, Router, extract::{Query, State}, routing::get};
+    use serde_json::json;
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    fn caller(claims: Value) -> Caller {
+        let Value::Object(claims) = claims else {
+            unreachable!("claims must be an object")
+        };
+        Caller {
+            subject: "alice".to_string(),
+            claims: Arc::new(claims),
+            token: Arc::from("token"),
+        }
+    }
+
+    #[test]
+    fn admin_role_is_read_from_array_or_space_separated_claim() {
+        assert!(is_hr_admin(&caller(json!({ "roles": ["employee", "hr-admin"] }))));
+        assert!(is_hr_admin(&caller(json!({ "roles": "employee hr-admin" }))));
+    }
+
+    #[test]
+    fn partial_or_missing_admin_role_is_not_admin() {
+        assert!(!is_hr_admin(&caller(json!({ "roles": ["hr-administrator"] }))));
+        assert!(!is_hr_admin(&caller(json!({ "roles": "employee" }))));
+        assert!(!is_hr_admin(&caller(json!({ "scope": "hr-admin" }))));
+    }
+
+    /// A `/publicWorkers` stand-in answering worker-number lookups and each worker's managers.
+    #[derive(Default)]
+    struct Workers {
+        /// Worker number to `PersonId`
+        numbers: HashMap<&'static str, &'static str>,
+        /// `PersonId` to their managers' `PersonId`s and `ManagerType`s
+        managers: HashMap<String, Vec<(String, &'static str)>>,
+        requests: AtomicUsize,
+    }
+
+    impl Workers {
+        /// Workers each line-managed by the next in `chain`.
+        fn chain(chain: &[&str]) -> Self {
+            let mut workers = Self::default();
+            for pair in chain.windows(2) {
+                workers.manage(pair[1], pair[0], "LINE_MANAGER");
+            }
+            workers
+        }
+
+        fn manage(&mut self, manager: &str, report: &str, manager_type: &'static str) {
+            self.managers
+                .entry(report.to_string())
+                .or_default()
+                .push((manager.to_string(), manager_type));
+        }
+    }
+
+    async fn public_workers(
+        State(workers): State<Arc<Workers>>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> Json<Value> {
+        workers.requests.fetch_add(1, Ordering::Relaxed);
+        let q = params.get("q").map_or("", String::as_str);
+        let value = q.split('\'').nth(1).unwrap_or_default();
+        if q.contains("WorkerNumber") {
+            let items: Vec<Value> = workers.numbers.get(value).map(|id| json!({ "PersonId": id })).into_iter().collect();
+            return Json(json!({ "items": items, "hasMore": false }));
+        }
+        let managers: Vec<Value> = workers
+            .managers
+            .get(value)
+            .into_iter()
+            .flatten()
+            .map(|(id, manager_type)| json!({ "ManagerPersonId": id, "ManagerType": manager_type }))
+            .collect();
+        Json(json!({ "items": [{ "PersonId": value, "assignments": { "items": [{ "managers": managers }] } }] }))
+    }
+
+    /// Runs `check` in an environment whose HCM serves `workers`.
+    async fn with_workers<F: Future>(name: &str, workers: &Arc<Workers>, check: F) -> F::Output {
+        let app = Router::new().route("/publicWorkers", get(public_workers)).with_state(workers.clone());
+        let environment = HcmEnvironment::stand_in(name, app).await;
+        HcmEnvironment::scope(environment, check).await
+    }
+
+    #[tokio::test]
+    async fn direct_and_skip_level_managers_manage_the_worker() -> Result<(), HcmError> {
+        let workers = Arc::new(Workers::chain(&["1", "2", "3"]));
+
+        assert!(with_workers("authzdirect", &workers, manages("2", "1")).await?);
+        assert!(with_workers("authzskip", &workers, manages("3", "1")).await?);
+        assert!(!with_workers("authzreport", &workers, manages("1", "3")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cycle_in_the_reporting_lines_ends_the_search() -> Result<(), HcmError> {
+        let mut workers = Workers::chain(&["1", "2", "3"]);
+        workers.manage("1", "3", "LINE_MANAGER");
+        let workers = Arc::new(workers);
+
+        assert!(!with_workers("authzcycle", &workers, manages("9", "1")).await?);
+        // Each worker in the loop is looked up once
+        assert_eq!(workers.requests.load(Ordering::Relaxed), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn managers_beyond_the_depth_limit_are_not_found() -> Result<(), HcmError> {
+        let depth = tuning().max_chain_depth;
+        let ids: Vec<String> = (1..=depth + 2).map(|id| id.to_string()).collect();
+        let workers = Arc::new(Workers::chain(&ids.iter().map(String::as_str).collect::<Vec<_>>()));
+
+        assert!(with_workers("authzdeepest", &workers, manages(&ids[depth], &ids[0])).await?);
+        assert!(!with_workers("authztoodeep", &workers, manages(&ids[depth + 1], &ids[0])).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn only_line_managers_count() -> Result<(), HcmError> {
+        let mut workers = Workers::default();
+        workers.manage("5", "1", "PROJECT_MANAGER");
+        workers.manage("2", "1", "LINE_MANAGER");
+        workers.manage("6", "2", "HR_REPRESENTATIVE");
+        let workers = Arc::new(workers);
+
+        assert!(with_workers("authzline", &workers, manages("2", "1")).await?);
+        assert!(!with_workers("authzproject", &workers, manages("5", "1")).await?);
+        assert!(!with_workers("authzhr", &workers, manages("6", "1")).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn caller_outside_the_chain_is_forbidden() -> Result<(), HcmError> {
+        let mut workers = Workers::chain(&["1", "2"]);
+        workers.numbers.insert("E012345", "2");
+        workers.numbers.insert("E054321", "7");
+        let workers = Arc::new(workers);
+        let manager = caller(json!({ "worker_number": "E012345" }));
+        let stranger = caller(json!({ "worker_number": "E054321" }));
+
+        with_workers("authzallowed", &workers, authorize(Some(&manager), "1")).await?;
+        let denied = with_workers("authzdenied", &workers, authorize(Some(&stranger), "1")).await;
+        assert!(matches!(denied, Err(HcmError::Forbidden(_))), "{denied:?}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn line_managers_are_reused_between_checks() -> Result<(), HcmError> {
+        let workers = Arc::new(Workers::chain(&["1", "2", "3"]));
+
+        assert!(with_workers("authzreuse", &workers, manages("3", "1")).await?);
+        assert!(with_workers("authzreuse", &workers, manages("3", "1")).await?);
+        assert_eq!(workers.requests.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cached_line_managers_expire_after_the_ttl() {
+        let key = ("authzttl".to_string(), "1".to_string());
+        store_line_managers(key.clone(), vec!["2".to_string()]);
+
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert_eq!(cached_line_managers(&key), Some(vec!["2".to_string()]));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(cached_line_managers(&key), None);
+    }
+}
@@ -0,0 +1,69 @@
+//! Identity of the authenticated MCP caller.
+//!
+//! The bearer-token layer in `server::auth` validates the inbound JWT and
+//! stores a `Caller` in the HTTP request extensions. rmcp copies the request
+//! `Parts` into every `RequestContext`, so `initialize` and the tool handlers
+//! can recover the caller with [`Caller::from_context`].
+//!
+//! Tool handlers also run inside [`Caller::scope`], so code far from the
+//! handler (such as on-behalf-of delegation in `mcp::http`) can read the
+//! current caller with [`Caller::current`] without threading it through.
+//!
+//! Over stdio (or when authentication is disabled) there is no caller.
+
+use axum::http::request;
+use rmcp::{RoleServer, service::RequestContext};
+use serde_json::{Map, Value};
+use std::{fmt, future::Future, sync::Arc};
+
+tokio::task_local! {
+    /// The caller of the tool invocation running on this task.
+    static CURRENT_CALLER: Option<Caller>;
+}
+
+/// A caller whose bearer token passed signature, issuer, audience and expiry checks.
+#[derive(Clone)]
+pub struct Caller {
+    /// The token's `sub` claim
+    pub subject: String,
+    /// All claims from the validated token
+    pub claims: Arc<Map<String, Value>>,
+    /// The raw bearer token, kept for on-behalf-of delegation
+    pub token: Arc<str>,
+}
+
+impl fmt::Debug for Caller {
+    // Hand-written so the raw bearer token can never end up in a log line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Caller")
+            .field("subject", &self.subject)
+            .field("claims", &self.claims)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Caller {
+    /// Returns the authenticated caller attached to an MCP request, if any.
+    pub fn from_context(context: &RequestContext<RoleServer>) -> Option<Self> {
+        context
+            .extensions
+            .get::<request::Parts>()
+            .and_then(|parts| parts.extensions.get::<Self>())
+            .cloned()
+    }
+
+    /// Runs `future` with `caller` as the current caller.
+    pub async fn scope<F: Future>(caller: Option<Self>, future: F) -> F::Output {
+        CURRENT_CALLER.scope(caller, future).await
+    }
+
+    /// Returns the caller of the tool invocation running on this task, if any.
+    pub fn current() -> Option<Self> {
+        CURRENT_CALLER.try_with(Clone::clone).ok().flatten()
+    }
+
+    /// Returns a string claim by name.
+    pub fn claim_str(&self, name: &str) -> Option<&str> {
+        self.claims.get(name).and_then(Value::as_str)
+    }
+}
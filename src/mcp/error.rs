@@ -3,15 +3,93 @@
 //! This module contains the custom error types and conversions needed for the HCM API.
 //! It provides:
 //! - `HcmError`: The main error enum that covers all possible error cases
+//! - `ErrorKind`: What kind of error an `HcmError` is, including one shared by coalesced requests
+//! - `ErrorDetail`: Structured details of a failed HCM request (status, ADF error code, ...)
 //! - Conversions from various error types (`reqwest`, `serde_json`, etc.)
 //! - Conversion to RMCP's `ErrorData` for MCP protocol compliance
+//!
+//! # Error data
+//! Every `ErrorData` carries machine-readable `data` so agents can react
+//! without parsing messages:
+//! ```json
+//! { "kind": "rate_limited", "retryable": true, "status": 429, "retryAfterSecs": 30 }
+//! ```
 
+use std::{fmt, sync::Arc};
 use reqwest_middleware;
 use rmcp::ErrorData;
+use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error;
 use reqwest;
 use serde_json;
-use rmcp::model::ErrorCode;
+use rmcp::model::{CallToolResult, ErrorCode};
+
+/// JSON-RPC server error code returned when the caller may not access the requested data.
+/// MCP has no standard code for this, so it uses the implementation-defined range.
+pub const FORBIDDEN: ErrorCode = ErrorCode(-32003);
+
+/// JSON-RPC server error code returned when HCM rejects the server's credentials.
+pub const UNAUTHORIZED: ErrorCode = ErrorCode(-32004);
+
+/// JSON-RPC server error code returned when HCM throttles requests; retry after a delay.
+pub const RATE_LIMITED: ErrorCode = ErrorCode(-32005);
+
+/// JSON-RPC server error code returned when HCM is temporarily unavailable; retry later.
+pub const UNAVAILABLE: ErrorCode = ErrorCode(-32006);
+
+/// Details of a failed HCM request, parsed from Oracle's ADF error payload
+/// (`title`, `detail`, `o:errorCode`, `o:errorDetails`) where available.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorDetail {
+    /// Human-readable description of the failure
+    #[serde(skip)]
+    pub message: String,
+    /// HTTP status returned by HCM; absent for errors raised by this server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Short summary from the ADF `title` field
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Oracle error code from `o:errorCode`, e.g. "`PER-1530040`"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// Per-attribute or nested errors from `o:errorDetails`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub error_details: Vec<Value>,
+    /// Seconds to wait before retrying, from `Retry-After`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl ErrorDetail {
+    /// Creates details for an error raised by this server rather than returned by HCM.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Builds the `ErrorData.data` object: `kind`, `retryable` and the serialized details.
+    fn to_data(&self, kind: &str, retryable: bool) -> Value {
+        let mut data = json!({ "kind": kind, "retryable": retryable });
+        if let (Value::Object(data), Ok(Value::Object(detail))) = (&mut data, serde_json::to_value(self)) {
+            data.extend(detail);
+        }
+        data
+    }
+}
+
+impl fmt::Display for ErrorDetail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error_code {
+            Some(code) => write!(f, "{} ({code})", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum HcmError {
@@ -19,6 +97,30 @@ pub enum HcmError {
     #[error("Invalid parameters: {0}")]
     InvalidParams(String),
 
+    /// Error when the authenticated caller, or HCM itself, denies access to the requested data
+    #[error("Forbidden: {0}")]
+    Forbidden(Box<ErrorDetail>),
+
+    /// Error when the requested HCM resource or record does not exist
+    #[error("Not found: {0}")]
+    NotFound(Box<ErrorDetail>),
+
+    /// Error when HCM rejects the credentials used for the request
+    #[error("Unauthorized: {0}")]
+    Unauthorized(Box<ErrorDetail>),
+
+    /// Error when HCM rejects the request payload or query (business rule or validation failure)
+    #[error("Validation failed: {0}")]
+    Validation(Box<ErrorDetail>),
+
+    /// Error when HCM throttles the request, or it exceeds this server's own outbound limits
+    #[error("Rate limited: {0}")]
+    RateLimited(Box<ErrorDetail>),
+
+    /// Error when HCM is temporarily unavailable (502, 503, 504), or the circuit breaker is open
+    #[error("Service unavailable: {0}")]
+    Unavailable(Box<ErrorDetail>),
+
     /// Error when required environment variables or configuration is missing
     #[error("Missing configuration: {0}")]
     MissingConfig(String),
@@ -29,7 +131,7 @@ pub enum HcmError {
 
     /// Error from HTTP middleware stack (tracing, retries, etc.)
     #[error("HTTP middleware error: {0}")]
-    HttpMiddleware(#[from] reqwest_middleware::Error),
+    HttpMiddleware(reqwest_middleware::Error),
 
     /// Error during JSON serialization or deserialization
     #[error("Serialization error: {0}")]
@@ -38,17 +140,161 @@ pub enum HcmError {
     /// Catch-all for other internal errors
     #[error("Internal error: {0}")]
     Internal(#[from] anyhow::Error),
+
+    /// An error shared by every caller of a coalesced HCM request
+    #[error(transparent)]
+    Shared(Arc<Self>),
+}
+
+/// What kind of failure an [`HcmError`] is, the `kind` reported to agents.
+///
+/// Unlike matching on the variant, this sees through `HcmError::Shared`, so callers
+/// of a coalesced request react to its error the same way as the one that made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    InvalidParams,
+    Forbidden,
+    NotFound,
+    Unauthorized,
+    Validation,
+    RateLimited,
+    Unavailable,
+    MissingConfig,
+    Http,
+    Serialization,
+    Internal,
+}
+
+impl ErrorKind {
+    /// The label used in `ErrorData.data.kind`, metrics and the audit log.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidParams => "invalid_params",
+            Self::Forbidden => "forbidden",
+            Self::NotFound => "not_found",
+            Self::Unauthorized => "unauthorized",
+            Self::Validation => "validation",
+            Self::RateLimited => "rate_limited",
+            Self::Unavailable => "unavailable",
+            Self::MissingConfig => "missing_config",
+            Self::Http => "http",
+            Self::Serialization => "serialization",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl HcmError {
+    /// The kind of this error, or of the shared error it wraps.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InvalidParams(_) => ErrorKind::InvalidParams,
+            Self::Forbidden(_) => ErrorKind::Forbidden,
+            Self::NotFound(_) => ErrorKind::NotFound,
+            Self::Unauthorized(_) => ErrorKind::Unauthorized,
+            Self::Validation(_) => ErrorKind::Validation,
+            Self::RateLimited(_) => ErrorKind::RateLimited,
+            Self::Unavailable(_) => ErrorKind::Unavailable,
+            Self::MissingConfig(_) => ErrorKind::MissingConfig,
+            Self::Http(_) | Self::HttpMiddleware(_) => ErrorKind::Http,
+            Self::Serialization(_) => ErrorKind::Serialization,
+            Self::Internal(_) => ErrorKind::Internal,
+            Self::Shared(e) => e.kind(),
+        }
+    }
+
+    /// Creates a `Forbidden` error for a denial decided by this server.
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::Forbidden(Box::new(ErrorDetail::new(message)))
+    }
+
+    /// Creates an `Unauthorized` error for a request this server can't make on the caller's behalf.
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::Unauthorized(Box::new(ErrorDetail::new(message)))
+    }
+
+    /// Creates an `Unavailable` error raised locally (e.g. by the circuit breaker).
+    pub fn unavailable(message: impl Into<String>, retry_after_secs: Option<u64>) -> Self {
+        Self::Unavailable(Box::new(ErrorDetail {
+            retry_after_secs,
+            ..ErrorDetail::new(message)
+        }))
+    }
+
+    /// Creates a `RateLimited` error raised locally by the outbound rate limiter.
+    pub fn rate_limited(message: impl Into<String>, retry_after_secs: Option<u64>) -> Self {
+        Self::RateLimited(Box::new(ErrorDetail {
+            retry_after_secs,
+            ..ErrorDetail::new(message)
+        }))
+    }
+
+    /// Creates a `NotFound` error for a lookup that matched nothing.
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(Box::new(ErrorDetail::new(message)))
+    }
+}
+
+/// Builds `ErrorData` whose message and `data` come from an [`ErrorDetail`].
+fn detailed(code: ErrorCode, kind: &str, retryable: bool, detail: &ErrorDetail) -> ErrorData {
+    ErrorData::new(code, detail.to_string(), Some(detail.to_data(kind, retryable)))
+}
+
+/// Builds `ErrorData` with a `data` object holding only `kind` and `retryable`.
+fn simple(code: ErrorCode, kind: &str, retryable: bool, message: String) -> ErrorData {
+    ErrorData::new(code, message, Some(json!({ "kind": kind, "retryable": retryable })))
+}
+
+impl From<reqwest_middleware::Error> for HcmError {
+    /// Unwraps the errors raised by this server's own middleware, e.g. a rate limit.
+    fn from(err: reqwest_middleware::Error) -> Self {
+        match err {
+            reqwest_middleware::Error::Middleware(e) => e
+                .downcast::<Self>()
+                .unwrap_or_else(|e| Self::HttpMiddleware(reqwest_middleware::Error::Middleware(e))),
+            err @ reqwest_middleware::Error::Reqwest(_) => Self::HttpMiddleware(err),
+        }
+    }
 }
 
 impl From<HcmError> for ErrorData {
     fn from(err: HcmError) -> Self {
+        Self::from(&err)
+    }
+}
+
+impl From<&HcmError> for ErrorData {
+    fn from(err: &HcmError) -> Self {
+        let kind = err.kind().as_str();
         match err {
-            HcmError::InvalidParams(msg) => Self::new(ErrorCode::INVALID_PARAMS, msg, None),
-            HcmError::MissingConfig(msg) => Self::new(ErrorCode::INTERNAL_ERROR, msg, None),
-            HcmError::Http(e) => Self::new(ErrorCode::INTERNAL_ERROR, format!("HTTP error: {e}"), None),
-            HcmError::HttpMiddleware(e) => Self::new(ErrorCode::INTERNAL_ERROR, format!("HTTP middleware error: {e}"), None),
-            HcmError::Serialization(e) => Self::new(ErrorCode::INTERNAL_ERROR, e.to_string(), None),
-            HcmError::Internal(e) => Self::new(ErrorCode::INTERNAL_ERROR, e.to_string(), None),
+            HcmError::InvalidParams(msg) => simple(ErrorCode::INVALID_PARAMS, kind, false, msg.clone()),
+            HcmError::Forbidden(detail) => detailed(FORBIDDEN, kind, false, detail),
+            HcmError::NotFound(detail) => detailed(ErrorCode::RESOURCE_NOT_FOUND, kind, false, detail),
+            HcmError::Unauthorized(detail) => detailed(UNAUTHORIZED, kind, false, detail),
+            HcmError::Validation(detail) => detailed(ErrorCode::INVALID_PARAMS, kind, false, detail),
+            HcmError::RateLimited(detail) => detailed(RATE_LIMITED, kind, true, detail),
+            HcmError::Unavailable(detail) => detailed(UNAVAILABLE, kind, true, detail),
+            HcmError::MissingConfig(msg) => simple(ErrorCode::INTERNAL_ERROR, kind, false, msg.clone()),
+            HcmError::Http(e) => simple(ErrorCode::INTERNAL_ERROR, kind, e.is_timeout() || e.is_connect(), format!("HTTP error: {e}")),
+            HcmError::HttpMiddleware(e) => simple(ErrorCode::INTERNAL_ERROR, kind, false, format!("HTTP middleware error: {e}")),
+            HcmError::Serialization(e) => simple(ErrorCode::INTERNAL_ERROR, kind, false, e.to_string()),
+            HcmError::Internal(e) => simple(ErrorCode::INTERNAL_ERROR, kind, false, e.to_string()),
+            HcmError::Shared(e) => Self::from(e.as_ref()),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Short label for how a tool call ended: `ok`, `tool_error` (a result flagged
+/// `isError`), or the error's `kind`. Used by metrics and the audit log.
+pub fn outcome(result: &Result<CallToolResult, ErrorData>) -> &str {
+    match result {
+        Ok(output) if output.is_error == Some(true) => "tool_error",
+        Ok(_) => "ok",
+        Err(error) => error
+            .data
+            .as_ref()
+            .and_then(|data| data.get("kind"))
+            .and_then(Value::as_str)
+            .unwrap_or("error"),
+    }
+}
@@ -0,0 +1,172 @@
+//! Parsing of Oracle ADF REST error responses into `HcmError` variants.
+//!
+//! HCM reports failures with an ADF error payload (`application/vnd.oracle.adf.error+json`):
+//! ```json
+//! {
+//!   "title": "Bad Request",
+//!   "status": "400",
+//!   "detail": "The value of the attribute AbsenceTypeId isn't valid.",
+//!   "o:errorCode": "27024",
+//!   "o:errorDetails": [{ "detail": "...", "o:errorCode": "..." }]
+//! }
+//! ```
+//! Older framework versions return plain text instead, which becomes the message.
+//!
+//! # Status mapping
+//! - 400, 409, 412, 422: `Validation`
+//! - 401: `Unauthorized`
+//! - 403: `Forbidden`
+//! - 404, 410: `NotFound`
+//! - 429: `RateLimited` (with `Retry-After`)
+//! - 502, 503, 504: `Unavailable` (with `Retry-After`)
+//! - Anything else: `Internal`
+
+use std::time::Duration;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use reqwest::{header::{HeaderMap, RETRY_AFTER}, Response, StatusCode};
+use serde_json::Value;
+
+use crate::mcp::error::{ErrorDetail, HcmError};
+
+/// Longest plain-text error body kept in a message.
+const MAX_TEXT_BODY: usize = 500;
+
+/// Converts a non-2xx HCM response into the matching `HcmError`.
+pub async fn error_from_response(response: Response) -> HcmError {
+    let status = response.status();
+    let retry_after_secs = retry_after(response.headers()).map(|delay| delay.as_secs());
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|e| format!("Unable to read error response body: {e}"));
+
+    let mut detail = parse_body(status, &body);
+    detail.retry_after_secs = retry_after_secs;
+    into_error(status, detail)
+}
+
+/// Extracts `title`, `detail`, `o:errorCode` and `o:errorDetails` from an ADF error body.
+fn parse_body(status: StatusCode, body: &str) -> ErrorDetail {
+    let mut detail = ErrorDetail {
+        status: Some(status.as_u16()),
+        ..ErrorDetail::default()
+    };
+
+    let Ok(Value::Object(payload)) = serde_json::from_str::<Value>(body) else {
+        let text = body.trim();
+        detail.message = if text.is_empty() {
+            format!("HCM returned HTTP {status}")
+        } else {
+            format!("HCM returned HTTP {status}: {}", truncate(text, MAX_TEXT_BODY))
+        };
+        return detail;
+    };
+
+    let text = |value: Option<&Value>| value.and_then(Value::as_str).map(str::to_string);
+    detail.title = text(payload.get("title"));
+    detail.error_code = text(payload.get("o:errorCode"));
+    detail.error_details = payload
+        .get("o:errorDetails")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    // Prefer the top-level detail, then the first nested one, then the title
+    let message = text(payload.get("detail"))
+        .or_else(|| detail.error_details.iter().find_map(|d| text(d.get("detail"))))
+        .or_else(|| detail.title.clone());
+    if detail.error_code.is_none() {
+        detail.error_code = detail.error_details.iter().find_map(|d| text(d.get("o:errorCode")));
+    }
+    detail.message = message.unwrap_or_else(|| format!("HCM returned HTTP {status}"));
+    detail
+}
+
+/// Maps the HTTP status to the `HcmError` variant agents should react to.
+fn into_error(status: StatusCode, detail: ErrorDetail) -> HcmError {
+    let detail = Box::new(detail);
+    match status.as_u16() {
+        400 | 409 | 412 | 422 => HcmError::Validation(detail),
+        401 => HcmError::Unauthorized(detail),
+        403 => HcmError::Forbidden(detail),
+        404 | 410 => HcmError::NotFound(detail),
+        429 => HcmError::RateLimited(detail),
+        502..=504 => HcmError::Unavailable(detail),
+        _ => HcmError::Internal(anyhow!("HTTP {status}: {detail}")),
+    }
+}
+
+/// Reads `Retry-After` from response headers, see [`parse_retry_after`].
+///
+/// Shared by error parsing and the retry middleware, so both agree on the delay.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    parse_retry_after(headers.get(RETRY_AFTER)?.to_str().ok()?)
+}
+
+/// Parses `Retry-After` as delay-seconds or an HTTP date (RFC 9110 §10.2.3).
+/// A date in the past means no wait.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - Utc::now()).to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Shortens `text` to at most `max` characters.
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn retry_after_accepts_delay_seconds() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after(" 0 "), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_accepts_an_http_date() {
+        let at = (Utc::now() + TimeDelta::seconds(120)).format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let delay = parse_retry_after(&at).map(|delay| delay.as_secs());
+        assert!(delay.is_some_and(|secs| (115..=120).contains(&secs)), "{delay:?}");
+    }
+
+    #[test]
+    fn retry_after_in_the_past_means_no_wait() {
+        assert_eq!(parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_rejects_garbage() {
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+    }
+
+    #[test]
+    fn adf_payload_fields_are_parsed() {
+        let body = r#"{
+            "title": "Bad Request",
+            "status": "400",
+            "o:errorDetails": [{ "detail": "AbsenceTypeId isn't valid.", "o:errorCode": "27024" }]
+        }"#;
+        let detail = parse_body(StatusCode::BAD_REQUEST, body);
+        assert_eq!(detail.message, "AbsenceTypeId isn't valid.");
+        assert_eq!(detail.error_code.as_deref(), Some("27024"));
+        assert_eq!(detail.title.as_deref(), Some("Bad Request"));
+        assert!(matches!(into_error(StatusCode::BAD_REQUEST, detail), HcmError::Validation(_)));
+    }
+
+    #[test]
+    fn plain_text_body_becomes_the_message() {
+        let detail = parse_body(StatusCode::SERVICE_UNAVAILABLE, "Down for patching");
+        assert_eq!(detail.message, "HCM returned HTTP 503 Service Unavailable: Down for patching");
+        assert!(matches!(into_error(StatusCode::SERVICE_UNAVAILABLE, detail), HcmError::Unavailable(_)));
+    }
+}
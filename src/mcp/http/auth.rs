@@ -0,0 +1,708 @@
+//! Authentication providers for outbound Oracle HCM requests.
+//!
+//! Every HCM request carries an `Authorization` header produced by the
+//! configured [`HcmAuthProvider`]:
+//! - **Basic**: HTTP Basic with `HCM_USERNAME`/`HCM_PASSWORD` (legacy default)
+//! - **OAuth 2.0 client credentials**: access token from an IDCS/IAM token endpoint
+//! - **JWT bearer assertion**: a JWT signed with a configured private key,
+//!   exchanged for an access token (RFC 7523)
+//!
+//! Token-based providers cache the access token and refresh it shortly before
+//! it expires. Concurrent callers share a single refresh.
+//!
+//! Passwords, client secrets and signing keys can be read from files and are
+//! reloaded when the files change, see `secret`. After HCM rejects a request
+//! with `401`, [`HcmAuthProvider::reauthenticate`] re-reads them and drops the
+//! cached token so the request can be retried once.
+//!
+//! Each HCM environment has its own provider, configured by its own settings
+//! where set (e.g. `HCM_ENV_PROD_PASSWORD`), see `environment`.
+//!
+//! # Configuration
+//! - `HCM_AUTH_MODE` (optional): `basic`, `client_credentials` or `jwt_assertion`, defaults to "basic"
+//! - `HCM_USERNAME` / `HCM_PASSWORD` (`basic`): Integration account credentials, the password
+//!   optionally from `HCM_PASSWORD_FILE`
+//! - `HCM_OAUTH_TOKEN_URL` (OAuth modes): Token endpoint, e.g. `https://idcs-xxx.identity.oraclecloud.com/oauth2/v1/token`
+//! - `HCM_OAUTH_CLIENT_ID` / `HCM_OAUTH_CLIENT_SECRET` (OAuth modes): Confidential client credentials,
+//!   the secret optionally from `HCM_OAUTH_CLIENT_SECRET_FILE`
+//! - `HCM_OAUTH_SCOPE` (OAuth modes): Requested scope, e.g. `urn:opc:resource:fa:instanceid=xxxurn:opc:resource:consumer::all`
+//! - `HCM_JWT_PRIVATE_KEY_FILE` (`jwt_assertion`): PEM RSA private key used to sign assertions
+//! - `HCM_JWT_KEY_ID` (`jwt_assertion`, optional): `kid` header of the assertion
+//! - `HCM_JWT_SUBJECT` (`jwt_assertion`): Assertion subject, defaults to `HCM_USERNAME`; one of them is required
+//! - `HCM_JWT_AUDIENCE` (`jwt_assertion`, optional): Assertion audience, defaults to "<https://identity.oraclecloud.com/>"
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use anyhow::{anyhow, Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use reqwest::header::{CONTENT_TYPE, HeaderValue};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Mutex, time::Instant};
+use tracing::{debug, info};
+use url::form_urlencoded;
+
+use crate::mcp::{error::HcmError, redact};
+use super::{
+    environment::Settings,
+    secret::{Secret, SecretFile},
+};
+
+/// Tokens are refreshed this long before they expire, so in-flight requests never carry a stale token
+const TOKEN_REFRESH_SKEW: Duration = Duration::from_mins(1);
+
+/// Lifetime of a signed JWT assertion
+const ASSERTION_LIFETIME_SECS: i64 = 300;
+
+/// Boxed future returned by [`HcmAuthProvider`] methods, keeping the trait object-safe.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Produces the `Authorization` header value for outbound HCM requests.
+pub trait HcmAuthProvider: Send + Sync {
+    /// Short name used in logs (e.g. "basic").
+    fn name(&self) -> &'static str;
+
+    /// Returns a valid `Authorization` header value, fetching or refreshing a token if needed.
+    fn authorization(&self) -> BoxFuture<'_, Result<HeaderValue, HcmError>>;
+
+    /// Re-reads the provider's secrets and drops any cached token, after HCM
+    /// rejected its credentials. Returns `true` if retrying could succeed.
+    fn reauthenticate(&self) -> BoxFuture<'_, bool>;
+}
+
+// ============================================================================
+// Provider selection - Loaded once per environment at startup
+// ============================================================================
+
+/// The provider selected by `HCM_AUTH_MODE`.
+///
+/// Returns an error (surfaced as `MissingConfig`) if the mode is unknown or
+/// its required settings are missing.
+pub fn provider(settings: Settings<'_>) -> Result<Box<dyn HcmAuthProvider>> {
+    let mode = settings.var_or("HCM_AUTH_MODE", "basic");
+    let provider: Box<dyn HcmAuthProvider> = match mode.as_str() {
+        "basic" => Box::new(BasicAuth::from_env(settings)?),
+        "client_credentials" => Box::new(OAuthClientCredentials::from_env(settings)?),
+        "jwt_assertion" => Box::new(JwtAssertion::from_env(settings)?),
+        other => {
+            return Err(anyhow!(
+                "HCM_AUTH_MODE must be basic, client_credentials or jwt_assertion, got {other}"
+            ));
+        }
+    };
+    info!("HCM authentication mode: {}", provider.name());
+    Ok(provider)
+}
+
+/// Username of the integration account.
+fn username(settings: Settings<'_>) -> Result<String> {
+    settings.var("HCM_USERNAME")
+}
+
+/// Plain HTTP client for token endpoints, separate from the traced HCM client
+/// so token requests (which carry client secrets) never reach the HCM tracing span.
+static TOKEN_CLIENT: LazyLock<Result<reqwest::Client, String>> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("Failed to build token HTTP client: {e}"))
+});
+
+// ============================================================================
+// Basic authentication
+// ============================================================================
+
+/// HTTP Basic authentication with a username and a password that may be rotated.
+pub struct BasicAuth {
+    username: String,
+    password: Secret,
+}
+
+impl BasicAuth {
+    fn from_env(settings: Settings<'_>) -> Result<Self> {
+        let auth = Self {
+            username: username(settings)?,
+            password: Secret::required(settings, "HCM_PASSWORD")?,
+        };
+        // Fail at startup on credentials that can't be sent
+        auth.header()?;
+        Ok(auth)
+    }
+
+    /// Basic authentication as `username` with a fixed `password`.
+    #[cfg(test)]
+    pub fn new(username: &str, password: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            password: Secret::inline("HCM_PASSWORD", password),
+        }
+    }
+
+    /// The header for the current password.
+    fn header(&self) -> Result<HeaderValue> {
+        let encoded = BASE64.encode(format!("{}:{}", self.username, self.password.value()));
+        let mut header = HeaderValue::from_str(&format!("Basic {encoded}"))
+            .context("HCM credentials contain characters not allowed in a header")?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+}
+
+impl HcmAuthProvider for BasicAuth {
+    fn name(&self) -> &'static str {
+        "basic"
+    }
+
+    fn authorization(&self) -> BoxFuture<'_, Result<HeaderValue, HcmError>> {
+        Box::pin(async move { self.header().map_err(HcmError::Internal) })
+    }
+
+    fn reauthenticate(&self) -> BoxFuture<'_, bool> {
+        // The same password would only be rejected again
+        Box::pin(async move { self.password.reload() })
+    }
+}
+
+// ============================================================================
+// Token caching shared by OAuth providers
+// ============================================================================
+
+/// OAuth 2.0 token endpoint response.
+#[derive(Deserialize)]
+pub struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    token_type: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl TokenResponse {
+    /// Converts the response into an `Authorization` header and the token's lifetime.
+    pub fn into_header(self) -> Result<(HeaderValue, Duration), HcmError> {
+        let lifetime = Duration::from_secs(self.expires_in.unwrap_or(3600));
+        let token_type = self.token_type.as_deref().unwrap_or("Bearer");
+        // Some IDCS tenants return a lowercase token type; HCM expects "Bearer"
+        let scheme = if token_type.eq_ignore_ascii_case("bearer") { "Bearer" } else { token_type };
+
+        let mut header = HeaderValue::from_str(&format!("{scheme} {}", self.access_token))
+            .map_err(|e| HcmError::Internal(anyhow!("Token endpoint returned an invalid token: {e}")))?;
+        header.set_sensitive(true);
+        Ok((header, lifetime))
+    }
+}
+
+/// An access token and the instant after which it must be refreshed.
+pub struct CachedToken {
+    pub header: HeaderValue,
+    pub refresh_at: Instant,
+}
+
+impl CachedToken {
+    /// Caches `header` until `TOKEN_REFRESH_SKEW` before `lifetime` runs out.
+    pub fn new(header: HeaderValue, lifetime: Duration) -> Self {
+        Self {
+            header,
+            refresh_at: Instant::now() + lifetime.saturating_sub(TOKEN_REFRESH_SKEW),
+        }
+    }
+
+    /// Returns `true` while the token can still be used.
+    pub fn is_fresh(&self) -> bool {
+        Instant::now() < self.refresh_at
+    }
+}
+
+/// Caches one access token; the mutex also serializes refreshes so concurrent
+/// callers wait for a single token request instead of each fetching their own.
+#[derive(Default)]
+struct TokenCache {
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl TokenCache {
+    /// Returns the cached token, or calls `fetch` to obtain a new one when missing or near expiry.
+    async fn get_or_refresh<F, Fut>(&self, fetch: F) -> Result<HeaderValue, HcmError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<TokenResponse, HcmError>>,
+    {
+        let mut token = self.token.lock().await;
+        if let Some(cached) = token.as_ref()
+            && cached.is_fresh()
+        {
+            return Ok(cached.header.clone());
+        }
+
+        let (header, lifetime) = fetch().await?.into_header()?;
+        debug!("Obtained HCM access token valid for {}s", lifetime.as_secs());
+        *token = Some(CachedToken::new(header.clone(), lifetime));
+        drop(token);
+        Ok(header)
+    }
+
+    /// Drops the cached token, so the next call fetches a new one.
+    async fn clear(&self) {
+        *self.token.lock().await = None;
+    }
+}
+
+/// Settings shared by the OAuth providers.
+pub struct OAuthClient {
+    token_url: String,
+    client_id: String,
+    client_secret: Option<Secret>,
+    scope: Option<String>,
+}
+
+impl OAuthClient {
+    /// Loads the token endpoint and client credentials from `HCM_OAUTH_*`.
+    pub fn from_env(settings: Settings<'_>, require_secret: bool) -> Result<Self> {
+        Ok(Self {
+            token_url: settings.var("HCM_OAUTH_TOKEN_URL")?,
+            client_id: settings.var("HCM_OAUTH_CLIENT_ID")?,
+            client_secret: if require_secret {
+                Some(Secret::required(settings, "HCM_OAUTH_CLIENT_SECRET")?)
+            } else {
+                Secret::optional(settings, "HCM_OAUTH_CLIENT_SECRET")?
+            },
+            scope: settings.var("HCM_OAUTH_SCOPE").ok(),
+        })
+    }
+
+    /// A client for `token_url`, confidential when `client_secret` is set.
+    #[cfg(test)]
+    pub fn new(token_url: &str, client_id: &str, client_secret: Option<&str>) -> Self {
+        Self {
+            token_url: token_url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.map(|secret| Secret::inline("HCM_OAUTH_CLIENT_SECRET", secret)),
+            scope: None,
+        }
+    }
+
+    /// Posts a form-encoded grant to the token endpoint.
+    ///
+    /// Confidential clients authenticate with HTTP Basic (`client_secret_basic`);
+    /// public clients send their `client_id` in the form instead.
+    pub async fn request_token(&self, grant: &[(&str, &str)]) -> Result<TokenResponse, HcmError> {
+        let client = TOKEN_CLIENT
+            .as_ref()
+            .map_err(|e| HcmError::Internal(anyhow!("{e}")))?;
+
+        // Build the body up front: the serializer is not `Send` and must not live across an await
+        let body = {
+            let mut form = form_urlencoded::Serializer::new(String::new());
+            form.extend_pairs(grant);
+            if let Some(scope) = &self.scope {
+                form.append_pair("scope", scope);
+            }
+            if self.client_secret.is_none() {
+                form.append_pair("client_id", &self.client_id);
+            }
+            form.finish()
+        };
+
+        let mut request = client
+            .post(&self.token_url)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(body);
+        if let Some(secret) = &self.client_secret {
+            request = request.basic_auth(&self.client_id, Some(secret.value()));
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            // The body can echo the grant or name the client, so it stays out of the error
+            // returned to MCP clients and /readyz
+            let body = response.text().await.unwrap_or_default();
+            debug!("HCM token endpoint returned HTTP {status}: {}", redact::body(&body));
+            return Err(HcmError::Internal(anyhow!("HCM token endpoint returned HTTP {status}")));
+        }
+        Ok(response.json::<TokenResponse>().await?)
+    }
+
+    /// Re-reads the client secret, if there is one.
+    fn reload(&self) {
+        if let Some(secret) = &self.client_secret {
+            secret.reload();
+        }
+    }
+}
+
+// ============================================================================
+// OAuth2 client credentials
+// ============================================================================
+
+/// OAuth 2.0 client-credentials grant against the IDCS/IAM token endpoint.
+pub struct OAuthClientCredentials {
+    client: OAuthClient,
+    cache: TokenCache,
+}
+
+impl OAuthClientCredentials {
+    fn from_env(settings: Settings<'_>) -> Result<Self> {
+        Ok(Self {
+            client: OAuthClient::from_env(settings, true)?,
+            cache: TokenCache::default(),
+        })
+    }
+}
+
+impl HcmAuthProvider for OAuthClientCredentials {
+    fn name(&self) -> &'static str {
+        "client_credentials"
+    }
+
+    fn authorization(&self) -> BoxFuture<'_, Result<HeaderValue, HcmError>> {
+        Box::pin(self.cache.get_or_refresh(|| {
+            self.client.request_token(&[("grant_type", "client_credentials")])
+        }))
+    }
+
+    fn reauthenticate(&self) -> BoxFuture<'_, bool> {
+        // A revoked token is worth replacing even if the secret hasn't changed
+        Box::pin(async move {
+            self.client.reload();
+            self.cache.clear().await;
+            true
+        })
+    }
+}
+
+// ============================================================================
+// JWT bearer assertion
+// ============================================================================
+
+/// Claims of a signed JWT user assertion.
+#[derive(Serialize)]
+struct AssertionClaims<'a> {
+    iss: &'a str,
+    sub: &'a str,
+    aud: &'a str,
+    iat: i64,
+    exp: i64,
+    jti: String,
+}
+
+/// JWT bearer assertion grant (RFC 7523): a JWT signed with the configured
+/// private key is exchanged for an access token for `subject`.
+pub struct JwtAssertion {
+    client: OAuthClient,
+    /// PEM signing key, reloaded when its file changes
+    key: Secret,
+    key_id: Option<String>,
+    subject: String,
+    audience: String,
+    cache: TokenCache,
+    next_jti: AtomicU64,
+}
+
+impl JwtAssertion {
+    /// Loads the signing key and assertion settings from `HCM_JWT_*` and `HCM_OAUTH_*`.
+    pub fn from_env(settings: Settings<'_>) -> Result<Self> {
+        let key_file = settings.var("HCM_JWT_PRIVATE_KEY_FILE")?;
+        let key = Secret::new("HCM_JWT_PRIVATE_KEY_FILE", Box::new(SecretFile::new(&key_file)))?;
+        EncodingKey::from_rsa_pem(key.value().as_bytes())
+            .with_context(|| format!("{key_file} is not a PEM RSA private key"))?;
+
+        Ok(Self {
+            client: OAuthClient::from_env(settings, false)?,
+            key,
+            key_id: settings.var("HCM_JWT_KEY_ID").ok(),
+            subject: settings.var("HCM_JWT_SUBJECT").or_else(|_| username(settings))?,
+            audience: settings.var_or("HCM_JWT_AUDIENCE", "https://identity.oraclecloud.com/"),
+            cache: TokenCache::default(),
+            next_jti: AtomicU64::new(0),
+        })
+    }
+
+    /// An assertion grant signing with the PEM `key`, for the given `audience`.
+    #[cfg(test)]
+    pub fn new(client: OAuthClient, key: &str, audience: &str) -> Self {
+        Self {
+            client,
+            key: Secret::inline("HCM_JWT_PRIVATE_KEY_FILE", key),
+            key_id: None,
+            subject: String::new(),
+            audience: audience.to_string(),
+            cache: TokenCache::default(),
+            next_jti: AtomicU64::new(0),
+        }
+    }
+
+    /// Signs a fresh short-lived assertion for `subject`.
+    fn sign_assertion(&self, subject: &str) -> Result<String, HcmError> {
+        let now = Utc::now().timestamp();
+        let claims = AssertionClaims {
+            iss: &self.client.client_id,
+            sub: subject,
+            aud: &self.audience,
+            iat: now,
+            exp: now + ASSERTION_LIFETIME_SECS,
+            jti: format!("{now}-{}", self.next_jti.fetch_add(1, Ordering::Relaxed)),
+        };
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid.clone_from(&self.key_id);
+
+        let key = EncodingKey::from_rsa_pem(self.key.value().as_bytes())
+            .map_err(|e| HcmError::Internal(anyhow!("HCM_JWT_PRIVATE_KEY_FILE is not a PEM RSA private key: {e}")))?;
+        encode(&header, &claims, &key)
+            .map_err(|e| HcmError::Internal(anyhow!("Failed to sign JWT assertion: {e}")))
+    }
+
+    /// Signs an assertion for `subject` and exchanges it for an access token.
+    pub async fn exchange_assertion(&self, subject: &str) -> Result<TokenResponse, HcmError> {
+        let assertion = self.sign_assertion(subject)?;
+        self.client
+            .request_token(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .await
+    }
+}
+
+impl HcmAuthProvider for JwtAssertion {
+    fn name(&self) -> &'static str {
+        "jwt_assertion"
+    }
+
+    fn authorization(&self) -> BoxFuture<'_, Result<HeaderValue, HcmError>> {
+        Box::pin(self.cache.get_or_refresh(move || async move {
+            self.exchange_assertion(&self.subject).await
+        }))
+    }
+
+    fn reauthenticate(&self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            self.key.reload();
+            self.client.reload();
+            self.cache.clear().await;
+            true
+        })
+    }
+}
+
+
+/// A local OAuth token endpoint for tests of the providers and delegation.
+#[cfg(test)]
+pub mod stand_in {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, PoisonError},
+    };
+    use axum::{Json, Router, extract::State, http::{HeaderMap, StatusCode}, routing::post};
+    use serde_json::{Value, json};
+    use url::form_urlencoded;
+
+    /// A grant received by the token-endpoint stand-in.
+    pub struct Received {
+        pub form: HashMap<String, String>,
+        pub authorization: Option<String>,
+    }
+
+    pub type Log = Arc<Mutex<Vec<Received>>>;
+
+    async fn token_endpoint(State(log): State<Log>, headers: HeaderMap, body: String) -> Json<Value> {
+        let form = form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+        let authorization = headers
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let count = {
+            let mut log = log.lock().unwrap_or_else(PoisonError::into_inner);
+            log.push(Received { form, authorization });
+            log.len()
+        };
+        Json(json!({
+            "access_token": format!("token-{count}"),
+            "token_type": "bearer",
+            "expires_in": 3600,
+        }))
+    }
+
+    /// Rejects every grant, echoing it back the way some identity providers do.
+    async fn denied(body: String) -> (StatusCode, Json<Value>) {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid_grant", "error_description": format!("Rejected {body}") })),
+        )
+    }
+
+    /// Starts a local token endpoint, returning its URL and the grants it receives.
+    ///
+    /// The same server rejects every grant posted to `/denied`.
+    pub async fn stand_in() -> (String, Log) {
+        let log = Log::default();
+        let app = Router::new()
+            .route("/token", post(token_endpoint))
+            .route("/denied", post(denied))
+            .with_state(log.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap_or_else(|e| unreachable!("bind: {e}"));
+        let url = format!(
+            "http://{}/token",
+            listener.local_addr().unwrap_or_else(|e| unreachable!("addr: {e}"))
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, log)
+    }
+
+    /// Takes the grants received so far.
+    pub fn received(log: &Log) -> Vec<Received> {
+        std::mem::take(&mut *log.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::stand_in::{received, stand_in};
+    use jsonwebtoken::{DecodingKey, Validation, decode, decode_header};
+    use serde_json::{Map, Value};
+    use std::sync::atomic::AtomicUsize;
+
+    const SIGNING_KEY: &str = include_str!("testdata/jwt_assertion_key.pem");
+    const VERIFYING_KEY: &str = include_str!("testdata/jwt_assertion_key.pub.pem");
+
+    fn client_credentials(url: &str) -> OAuthClientCredentials {
+        let mut client = OAuthClient::new(url, "mcp-server", Some("s3cret"));
+        client.scope = Some("urn:opc:resource:consumer::all".to_string());
+        OAuthClientCredentials { client, cache: TokenCache::default() }
+    }
+
+    fn header_str(header: &HeaderValue) -> &str {
+        header.to_str().unwrap_or_else(|e| unreachable!("header: {e}"))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cached_token_is_refreshed_before_it_expires() -> Result<(), HcmError> {
+        let cache = TokenCache::default();
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async {
+            let count = fetches.fetch_add(1, Ordering::Relaxed) + 1;
+            Ok(TokenResponse {
+                access_token: format!("token-{count}"),
+                token_type: None,
+                expires_in: Some(120),
+            })
+        };
+
+        assert_eq!(header_str(&cache.get_or_refresh(fetch).await?), "Bearer token-1");
+        tokio::time::advance(Duration::from_secs(59)).await;
+        assert_eq!(header_str(&cache.get_or_refresh(fetch).await?), "Bearer token-1");
+        // One refresh skew before the token expires
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(header_str(&cache.get_or_refresh(fetch).await?), "Bearer token-2");
+        assert_eq!(fetches.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_credentials_authenticate_with_basic_auth() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let provider = client_credentials(&url);
+
+        assert_eq!(header_str(&provider.authorization().await?), "Bearer token-1");
+        assert_eq!(header_str(&provider.authorization().await?), "Bearer token-1");
+
+        let grants = received(&log);
+        assert_eq!(grants.len(), 1);
+        let form = &grants[0].form;
+        assert_eq!(form["grant_type"], "client_credentials");
+        assert_eq!(form["scope"], "urn:opc:resource:consumer::all");
+        assert!(!form.contains_key("client_id"));
+        let expected = format!("Basic {}", BASE64.encode("mcp-server:s3cret"));
+        assert_eq!(grants[0].authorization.as_deref(), Some(expected.as_str()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn public_client_sends_its_id_in_the_form() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let client = OAuthClient::new(&url, "mcp-public", None);
+
+        client.request_token(&[("grant_type", "client_credentials")]).await?;
+
+        let grants = received(&log);
+        assert_eq!(grants[0].form["client_id"], "mcp-public");
+        assert!(grants[0].authorization.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn jwt_assertion_carries_claims_kid_and_a_unique_jti() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let mut provider = JwtAssertion::new(OAuthClient::new(&url, "mcp-server", None), SIGNING_KEY, "urn:idcs");
+        provider.key_id = Some("signing-key-1".to_string());
+        provider.subject = "HCM.INTEGRATION".to_string();
+
+        provider.exchange_assertion(&provider.subject).await?;
+        provider.exchange_assertion(&provider.subject).await?;
+
+        let key = DecodingKey::from_rsa_pem(VERIFYING_KEY.as_bytes())
+            .unwrap_or_else(|e| unreachable!("test key: {e}"));
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&["urn:idcs"]);
+        validation.set_issuer(&["mcp-server"]);
+        let assertions: Vec<_> = received(&log)
+            .iter()
+            .map(|grant| {
+                assert_eq!(grant.form["grant_type"], "urn:ietf:params:oauth:grant-type:jwt-bearer");
+                let assertion = &grant.form["assertion"];
+                let header = decode_header(assertion).unwrap_or_else(|e| unreachable!("header: {e}"));
+                assert_eq!(header.kid.as_deref(), Some("signing-key-1"));
+                decode::<Map<String, Value>>(assertion, &key, &validation)
+                    .unwrap_or_else(|e| unreachable!("assertion rejected: {e}"))
+                    .claims
+            })
+            .collect();
+
+        assert_eq!(assertions.len(), 2);
+        for claims in &assertions {
+            assert_eq!(claims["sub"], "HCM.INTEGRATION");
+            let lifetime = claims["exp"].as_i64().zip(claims["iat"].as_i64()).map(|(exp, iat)| exp - iat);
+            assert_eq!(lifetime, Some(ASSERTION_LIFETIME_SECS));
+        }
+        assert_ne!(assertions[0]["jti"], assertions[1]["jti"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reauthenticate_drops_the_cached_token() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let provider = client_credentials(&url);
+
+        assert_eq!(header_str(&provider.authorization().await?), "Bearer token-1");
+        assert!(provider.reauthenticate().await);
+        assert_eq!(header_str(&provider.authorization().await?), "Bearer token-2");
+        assert_eq!(received(&log).len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn token_endpoint_errors_carry_only_the_status() {
+        let (url, _log) = stand_in().await;
+        let client = OAuthClient::new(&url.replace("/token", "/denied"), "mcp-server", Some("s3cret"));
+
+        let result = client.request_token(&[("grant_type", "client_credentials")]).await;
+        match result {
+            Err(HcmError::Internal(e)) => {
+                assert_eq!(e.to_string(), "HCM token endpoint returned HTTP 400 Bad Request");
+            }
+            Err(other) => unreachable!("expected an internal error, got {other:?}"),
+            Ok(_) => unreachable!("the token endpoint accepted the grant"),
+        }
+    }
+}
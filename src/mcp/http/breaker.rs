@@ -0,0 +1,351 @@
+//! Circuit breaker around the Oracle HCM backend.
+//!
+//! When HCM is degraded, every call would otherwise wait for its full timeout
+//! while agents keep adding more. The breaker tracks the outcome of recent
+//! calls and, once the failure ratio crosses a threshold, **opens**: calls fail
+//! immediately with `HcmError::Unavailable` and a `retryAfterSecs` hint.
+//! After the open period it goes **half-open** and lets a few probe calls
+//! through; a successful probe closes it again, a failed one re-opens it.
+//!
+//! Server errors (5xx), timeouts and connection failures count as failures.
+//! Client errors (4xx) mean HCM is answering, so they count as successes.
+//! [`BreakerMiddleware`] is the innermost of the client's own middleware: each
+//! attempt, retries included, is admitted after it has its rate-limit token and
+//! pool slot, so a half-open probe is never held while a call queues, and each
+//! attempt's outcome is recorded. Each HCM environment has its own breaker,
+//! with the settings below.
+//!
+//! # Configuration
+//! - `HCM_BREAKER_WINDOW` (optional): Recent calls considered, defaults to 20
+//! - `HCM_BREAKER_MIN_CALLS` (optional): Calls needed in the window before it can open, defaults to 10
+//! - `HCM_BREAKER_FAILURE_PERCENT` (optional): Failure percentage that opens it, defaults to 50
+//! - `HCM_BREAKER_OPEN_SECS` (optional): How long it stays open before probing, defaults to 30
+//! - `HCM_BREAKER_HALF_OPEN_PROBES` (optional): Concurrent probe calls when half-open, defaults to 1
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Error, Middleware, Next, Result as MiddlewareResult};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{
+    config::Breaker as BreakerConfig,
+    mcp::error::HcmError,
+    metrics::{self, BreakerState},
+};
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Closed,
+    Open { until: Instant },
+    HalfOpen { probes: u32 },
+}
+
+/// State changes worth logging, reported after the lock is released.
+enum Transition {
+    Opened { failures: usize, calls: usize },
+    Reopened,
+    Closed,
+}
+
+struct Inner {
+    state: State,
+    /// Outcomes of recent calls while closed; `true` is a failure
+    outcomes: VecDeque<bool>,
+}
+
+/// Failure-ratio circuit breaker with a half-open probing state.
+pub struct CircuitBreaker {
+    /// The HCM environment it guards, for logs and metrics
+    environment: String,
+    config: BreakerConfig,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    /// A closed breaker for the HCM environment `environment`.
+    pub fn new(environment: &str, config: BreakerConfig) -> Self {
+        Self {
+            environment: environment.to_string(),
+            config,
+            inner: Mutex::new(Inner {
+                state: State::Closed,
+                outcomes: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Admits a call, or fails fast while the breaker is open.
+    ///
+    /// # Errors
+    /// * `Unavailable` - If the breaker is open, or half-open with all probe slots taken
+    pub fn acquire(&self) -> Result<BreakerPermit<'_>, HcmError> {
+        let config = &self.config;
+        let mut inner = self.lock();
+        let probe = match inner.state {
+            State::Closed => false,
+            State::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    let wait = until.duration_since(now).as_secs().max(1);
+                    return Err(unavailable(wait));
+                }
+                info!(environment = %self.environment, "HCM circuit breaker half-open, probing recovery");
+                metrics::record_breaker_state(&self.environment, BreakerState::HalfOpen);
+                inner.state = State::HalfOpen { probes: 1 };
+                true
+            }
+            State::HalfOpen { probes } if probes < config.half_open_probes => {
+                inner.state = State::HalfOpen { probes: probes + 1 };
+                true
+            }
+            State::HalfOpen { .. } => return Err(unavailable(1)),
+        };
+        drop(inner);
+        Ok(BreakerPermit {
+            breaker: self,
+            probe,
+            recorded: false,
+        })
+    }
+
+    fn record(&self, probe: bool, failed: bool) {
+        let config = &self.config;
+        let mut inner = self.lock();
+        let transition = match inner.state {
+            State::HalfOpen { .. } if probe && failed => {
+                Self::trip(&mut inner, config);
+                Some(Transition::Reopened)
+            }
+            State::HalfOpen { .. } if probe => {
+                inner.state = State::Closed;
+                inner.outcomes.clear();
+                Some(Transition::Closed)
+            }
+            State::Closed => {
+                inner.outcomes.push_back(failed);
+                if inner.outcomes.len() > config.window {
+                    inner.outcomes.pop_front();
+                }
+                let calls = inner.outcomes.len();
+                let failures = inner.outcomes.iter().filter(|&&failed| failed).count();
+                if calls >= config.min_calls && failures * 100 >= calls * config.failure_percent {
+                    Self::trip(&mut inner, config);
+                    Some(Transition::Opened { failures, calls })
+                } else {
+                    None
+                }
+            }
+            // Late results from calls admitted before the state changed
+            _ => None,
+        };
+        drop(inner);
+
+        let open_secs = config.open_for.as_secs();
+        match transition {
+            Some(Transition::Opened { failures, calls }) => {
+                warn!(
+                    environment = %self.environment,
+                    "HCM circuit breaker opened: {failures} of the last {calls} calls failed; failing fast for {open_secs}s"
+                );
+                metrics::record_breaker_state(&self.environment, BreakerState::Open);
+            }
+            Some(Transition::Reopened) => {
+                warn!(environment = %self.environment, "HCM circuit breaker probe failed, re-opening for {open_secs}s");
+                metrics::record_breaker_state(&self.environment, BreakerState::Open);
+            }
+            Some(Transition::Closed) => {
+                info!(environment = %self.environment, "HCM circuit breaker closed, HCM has recovered");
+                metrics::record_breaker_state(&self.environment, BreakerState::Closed);
+            }
+            None => {}
+        }
+    }
+
+    /// Frees a probe slot whose call ended without an outcome (e.g. it was cancelled).
+    fn release_probe(&self) {
+        let mut inner = self.lock();
+        if let State::HalfOpen { probes } = inner.state {
+            inner.state = State::HalfOpen {
+                probes: probes.saturating_sub(1),
+            };
+        }
+    }
+
+    fn trip(inner: &mut Inner, config: &BreakerConfig) {
+        inner.state = State::Open {
+            until: Instant::now() + config.open_for,
+        };
+        inner.outcomes.clear();
+    }
+}
+
+/// Admission to make one HCM call; report its outcome with [`BreakerPermit::record`].
+pub struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    recorded: bool,
+}
+
+impl BreakerPermit<'_> {
+    /// Records whether the call failed in a way that suggests HCM is degraded.
+    pub fn record(mut self, failed: bool) {
+        self.recorded = true;
+        self.breaker.record(self.probe, failed);
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.recorded {
+            self.breaker.release_probe();
+        }
+    }
+}
+
+/// Admits each attempt through an environment's breaker and records its outcome.
+pub struct BreakerMiddleware {
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl BreakerMiddleware {
+    pub const fn new(breaker: Arc<CircuitBreaker>) -> Self {
+        Self { breaker }
+    }
+}
+
+#[async_trait]
+impl Middleware for BreakerMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MiddlewareResult<Response> {
+        let permit = self.breaker.acquire().map_err(|e| Error::Middleware(e.into()))?;
+        let outcome = next.run(req, extensions).await;
+        permit.record(is_backend_failure(&outcome));
+        outcome
+    }
+}
+
+/// Returns `true` if an outcome suggests HCM itself is degraded: a server
+/// error, timeout or connection failure. Client errors mean HCM is answering.
+fn is_backend_failure(outcome: &MiddlewareResult<Response>) -> bool {
+    match outcome {
+        Ok(response) => response.status().is_server_error(),
+        Err(Error::Reqwest(e)) => e.is_connect() || e.is_timeout() || e.is_request(),
+        Err(Error::Middleware(_)) => false,
+    }
+}
+
+fn unavailable(retry_after_secs: u64) -> HcmError {
+    metrics::record_rejection("breaker_open");
+    HcmError::unavailable(
+        format!("HCM unavailable, retry after {retry_after_secs} seconds"),
+        Some(retry_after_secs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const CONFIG: BreakerConfig = BreakerConfig {
+        window: 4,
+        min_calls: 4,
+        failure_percent: 50,
+        open_for: Duration::from_secs(30),
+        half_open_probes: 1,
+    };
+
+    fn record(breaker: &CircuitBreaker, outcomes: &[bool]) {
+        for &failed in outcomes {
+            let Ok(permit) = breaker.acquire() else {
+                unreachable!("closed breakers admit calls")
+            };
+            permit.record(failed);
+        }
+    }
+
+    fn is_open(breaker: &CircuitBreaker) -> bool {
+        matches!(breaker.acquire(), Err(HcmError::Unavailable(_)))
+    }
+
+    /// A breaker that has just opened.
+    fn tripped() -> CircuitBreaker {
+        let breaker = CircuitBreaker::new("test", CONFIG);
+        record(&breaker, &[true; 4]);
+        breaker
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_at_the_failure_ratio_once_min_calls_are_seen() {
+        let breaker = CircuitBreaker::new("test", CONFIG);
+        record(&breaker, &[true, true, true]);
+        assert!(!is_open(&breaker), "fewer than min_calls");
+
+        let breaker = CircuitBreaker::new("test", CONFIG);
+        record(&breaker, &[false, false, false, true]);
+        assert!(!is_open(&breaker), "25% failures");
+        record(&breaker, &[true]);
+        assert!(is_open(&breaker), "2 of the last 4 calls failed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn half_opens_after_the_open_period() {
+        let breaker = tripped();
+        tokio::time::advance(Duration::from_secs(29)).await;
+        assert!(is_open(&breaker));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(breaker.acquire().is_ok_and(|permit| permit.probe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn half_open_admits_only_the_probe_limit() {
+        let breaker = tripped();
+        tokio::time::advance(CONFIG.open_for).await;
+        let probe = breaker.acquire();
+        assert!(probe.is_ok());
+        assert!(is_open(&breaker), "the only probe slot is taken");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn successful_probe_closes() {
+        let breaker = tripped();
+        tokio::time::advance(CONFIG.open_for).await;
+        record(&breaker, &[false]);
+        assert!(breaker.acquire().is_ok_and(|permit| !permit.probe));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_probe_reopens() {
+        let breaker = tripped();
+        tokio::time::advance(CONFIG.open_for).await;
+        let Ok(probe) = breaker.acquire() else {
+            unreachable!("the open period is over")
+        };
+        probe.record(true);
+        assert!(is_open(&breaker));
+        tokio::time::advance(CONFIG.open_for).await;
+        assert!(!is_open(&breaker));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dropped_probe_frees_its_slot() {
+        let breaker = tripped();
+        tokio::time::advance(CONFIG.open_for).await;
+        drop(breaker.acquire());
+        assert!(breaker.acquire().is_ok_and(|permit| permit.probe));
+    }
+}
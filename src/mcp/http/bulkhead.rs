@@ -0,0 +1,104 @@
+//! Bulkheads: separate concurrency pools for different kinds of HCM calls.
+//!
+//! Projected-balance POSTs can take up to a minute, while worker and balance
+//! lookups usually return in well under a second. Each kind of call draws from
+//! its own pool, so a burst of slow projections can't starve quick lookups.
+//! A call that can't get a slot within the wait limit fails with
+//! `HcmError::Unavailable` instead of queueing indefinitely.
+//! Each HCM environment has its own [`Pools`], sized by the settings below.
+//!
+//! # Configuration
+//! - `HCM_BULKHEAD_LOOKUPS` (optional): Concurrent lookup calls, defaults to 32
+//! - `HCM_BULKHEAD_PROJECTIONS` (optional): Concurrent projection calls, defaults to 4
+//! - `HCM_BULKHEAD_WAIT_MS` (optional): How long a call waits for a slot, defaults to 10000
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::warn;
+
+use crate::{config::tuning, mcp::error::HcmError, metrics};
+
+/// The concurrency pool an HCM call runs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bulkhead {
+    /// Quick reads: worker lookups, balances, absence types
+    Lookup,
+    /// Slow calculations such as `loadProjectedBalance`
+    Projection,
+}
+
+impl Bulkhead {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Lookup => "lookup",
+            Self::Projection => "projection",
+        }
+    }
+}
+
+/// One environment's concurrency pools.
+pub struct Pools {
+    lookups: Semaphore,
+    projections: Semaphore,
+}
+
+impl Default for Pools {
+    fn default() -> Self {
+        Self {
+            lookups: Semaphore::new(tuning().bulkheads.lookups),
+            projections: Semaphore::new(tuning().bulkheads.projections),
+        }
+    }
+}
+
+impl Pools {
+    const fn semaphore(&self, bulkhead: Bulkhead) -> &Semaphore {
+        match bulkhead {
+            Bulkhead::Lookup => &self.lookups,
+            Bulkhead::Projection => &self.projections,
+        }
+    }
+
+    /// Waits for a slot in `bulkhead`'s pool; the call holds it until the permit is dropped.
+    ///
+    /// # Errors
+    /// * `Unavailable` - If no slot frees up within `HCM_BULKHEAD_WAIT_MS`
+    pub async fn acquire(&self, bulkhead: Bulkhead) -> Result<SemaphorePermit<'_>, HcmError> {
+        let wait = tuning().bulkheads.wait;
+        if let Ok(Ok(permit)) = tokio::time::timeout(wait, self.semaphore(bulkhead).acquire()).await {
+            return Ok(permit);
+        }
+        let name = bulkhead.name();
+        warn!("HCM {name} pool saturated, no slot within {}ms", wait.as_millis());
+        metrics::record_rejection("bulkhead_full");
+        Err(HcmError::unavailable(
+            format!("HCM {name} capacity is saturated, retry after 1 second"),
+            Some(1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn full_pool_times_out_without_blocking_the_other() {
+        let pools = Pools::default();
+        let mut held = Vec::new();
+        for _ in 0..tuning().bulkheads.projections {
+            let Ok(permit) = pools.acquire(Bulkhead::Projection).await else {
+                unreachable!("the pool has free slots")
+            };
+            held.push(permit);
+        }
+
+        let started = tokio::time::Instant::now();
+        assert!(matches!(pools.acquire(Bulkhead::Projection).await, Err(HcmError::Unavailable(_))));
+        assert!(started.elapsed() >= tuning().bulkheads.wait);
+        assert!(pools.acquire(Bulkhead::Lookup).await.is_ok());
+
+        drop(held.pop());
+        assert!(pools.acquire(Bulkhead::Projection).await.is_ok());
+        drop(held);
+    }
+}
@@ -0,0 +1,404 @@
+//! In-memory TTL cache of HCM reference data.
+//!
+//! Most conversations start by mapping a worker number to a `PersonId` and
+//! listing the worker's absence types. Those answers change rarely, so `GET`
+//! responses for the resources below are kept for a per-resource TTL instead
+//! of costing a Fusion round-trip every time:
+//! - `publicWorkers` (person mapping): 15 minutes
+//! - `absenceTypesLOV` (absence types): 1 hour
+//! - `absencePlans` (plan metadata): 1 hour
+//!
+//! Other resources, including balances, are never served from the cache
+//! without asking HCM unless configured.
+//! With on-behalf-of delegation each caller gets their own entries, since HCM
+//! filters responses by the caller's data security roles. Each HCM environment
+//! has its own entries, but they share `HCM_CACHE_MAX_ENTRIES`.
+//!
+//! # Conditional requests
+//! Oracle's ADF REST resources return an `ETag` with each response. Responses
+//! of the resources in `HCM_CACHE_ETAGS` that carry one are kept after their
+//! TTL: the next `GET` sends `If-None-Match`, and a `304 Not Modified` reuses
+//! the stored JSON instead of downloading and parsing the body again. By
+//! default these are the cached resources, so nothing else is kept once
+//! stale. Listing `planBalances` (TTL zero) keeps its large payloads, checked
+//! with HCM on every call but rarely downloaded again; each caller's balances
+//! then stay in memory until evicted or invalidated.
+//!
+//! The cache holds at most `HCM_CACHE_MAX_ENTRIES` responses; when full, expired
+//! entries without an `ETag` are dropped first, then the least recently used.
+//! Entries can be dropped early with [`invalidate`], exposed as `POST /cache/invalidate`.
+//!
+//! # Configuration
+//! - `HCM_CACHE_TTLS` (optional): Per-resource TTLs in seconds as `resource=secs`,
+//!   comma-separated, overriding the defaults above, e.g. "publicWorkers=300,planBalances=60".
+//!   A TTL of 0 stops caching that resource unless it is in `HCM_CACHE_ETAGS`
+//! - `HCM_CACHE_ETAGS` (optional): Comma-separated resources whose expired responses are
+//!   revalidated with `If-None-Match`, defaults to the resources with a TTL; empty turns it off
+//! - `HCM_CACHE_MAX_ENTRIES` (optional): Cached responses kept, defaults to 1000; 0 disables the cache
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::{config::tuning, mcp::caller::Caller, metrics};
+use super::{environment::HcmEnvironment, limiter::resource_of};
+
+/// Resources cached by default, with their TTLs in seconds.
+const DEFAULT_TTLS: &[(&str, u64)] = &[
+    ("publicWorkers", 900),
+    ("absenceTypesLOV", 3600),
+    ("absencePlans", 3600),
+];
+
+/// The process-wide cache, configured once at startup.
+static CACHE: LazyLock<Cache> = LazyLock::new(|| {
+    let config = &tuning().cache;
+    let mut ttls: HashMap<String, Duration> = DEFAULT_TTLS
+        .iter()
+        .map(|(resource, secs)| ((*resource).to_string(), Duration::from_secs(*secs)))
+        .collect();
+    ttls.extend(config.ttls.clone());
+    ttls.retain(|_, ttl| !ttl.is_zero());
+    let etags = config.etags.clone().unwrap_or_else(|| ttls.keys().cloned().collect());
+    Cache {
+        entries: Mutex::new(HashMap::new()),
+        ttls,
+        etags,
+        max_entries: config.max_entries,
+    }
+});
+
+/// Identifies a `GET` response: which pod, who asked (under delegation) and what for.
+/// Also groups identical in-flight requests, see `coalesce`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    /// HCM environment the request goes to
+    environment: Option<&'static str>,
+    /// Caller subject when delegation is on, since HCM answers per user
+    subject: Option<String>,
+    /// Request path including the query string
+    path: String,
+}
+
+impl Key {
+    /// The key for a `GET` of `path` by the current caller, in the current environment.
+    pub fn new(path: &str) -> Self {
+        let environment = HcmEnvironment::current().ok();
+        Self::for_caller(
+            environment.map(|environment| environment.name.as_str()),
+            environment.is_some_and(HcmEnvironment::delegates),
+            Caller::current(),
+            path,
+        )
+    }
+
+    /// The key for a `GET` of `path` by `caller`, who only counts when HCM answers per user.
+    fn for_caller(environment: Option<&'static str>, delegated: bool, caller: Option<Caller>, path: &str) -> Self {
+        Self {
+            environment,
+            subject: caller.filter(|_| delegated).map(|caller| caller.subject),
+            path: path.to_string(),
+        }
+    }
+
+    /// The key for a `GET` of `path`, or `None` if its responses can't be cached or revalidated.
+    pub fn for_path(path: &str) -> Option<Self> {
+        CACHE.accepts(resource_of(path)).then(|| Self::new(path))
+    }
+
+    /// The HCM resource the request addresses, e.g. "publicWorkers".
+    pub fn resource(&self) -> &str {
+        resource_of(&self.path)
+    }
+}
+
+struct Entry {
+    value: Arc<Value>,
+    etag: Option<String>,
+    expires_at: Instant,
+    last_used: Instant,
+}
+
+/// What the cache holds for a request.
+pub enum Lookup {
+    /// A response still within its TTL, usable without contacting HCM
+    Fresh(Value),
+    /// An expired response HCM can confirm is unchanged with `If-None-Match: etag`
+    Stale { etag: String, value: Arc<Value> },
+    Miss,
+}
+
+/// Looks up a cached response, recording the hit, stale entry or miss.
+pub fn get(key: &Key) -> Lookup {
+    CACHE.get(key)
+}
+
+/// Restarts the TTL of a stale entry that HCM confirmed with `304 Not Modified`.
+pub fn renew(key: &Key) {
+    CACHE.renew(key);
+}
+
+/// Caches a successful response for its resource's TTL, and for revalidation
+/// afterwards if HCM sent an `ETag`.
+pub fn insert(key: Key, value: &Value, etag: Option<String>) {
+    CACHE.insert(key, value, etag);
+}
+
+/// Drops cached responses for one resource (e.g. "publicWorkers"), or all of them.
+/// Returns the number of entries removed.
+pub fn invalidate(resource: Option<&str>) -> usize {
+    CACHE.invalidate(resource)
+}
+
+/// Cached responses and the settings bounding them.
+struct Cache {
+    entries: Mutex<HashMap<Key, Entry>>,
+    ttls: HashMap<String, Duration>,
+    /// Resources whose responses with an `ETag` are kept and revalidated after they expire
+    etags: HashSet<String>,
+    max_entries: usize,
+}
+
+impl Cache {
+    /// Whether responses of `resource` can be cached or revalidated.
+    fn accepts(&self, resource: &str) -> bool {
+        self.max_entries > 0 && (self.ttls.contains_key(resource) || self.etags.contains(resource))
+    }
+
+    fn ttl(&self, key: &Key) -> Duration {
+        self.ttls.get(key.resource()).copied().unwrap_or_default()
+    }
+
+    fn get(&self, key: &Key) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let lookup = match entries.get_mut(key) {
+            Some(entry) if entry.expires_at > now => {
+                entry.last_used = now;
+                Lookup::Fresh(entry.value.as_ref().clone())
+            }
+            Some(entry) => {
+                entry.last_used = now;
+                entry.etag.clone().map_or(Lookup::Miss, |etag| Lookup::Stale {
+                    etag,
+                    value: Arc::clone(&entry.value),
+                })
+            }
+            None => Lookup::Miss,
+        };
+        drop(entries);
+
+        let resource = key.resource();
+        let result = match lookup {
+            Lookup::Fresh(_) => "hit",
+            Lookup::Stale { .. } => "stale",
+            Lookup::Miss => "miss",
+        };
+        debug!("HCM cache {result} for {resource}");
+        metrics::record_cache_lookup(resource, result);
+        lookup
+    }
+
+    fn renew(&self, key: &Key) {
+        let ttl = self.ttl(key);
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(entry) = entries.get_mut(key) {
+            entry.expires_at = now + ttl;
+        }
+        drop(entries);
+        debug!("HCM cache entry for {} revalidated", key.resource());
+    }
+
+    fn insert(&self, key: Key, value: &Value, etag: Option<String>) {
+        let ttl = self.ttl(&key);
+        let etag = etag.filter(|_| self.etags.contains(key.resource()));
+        if ttl.is_zero() && etag.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.expires_at > now || entry.etag.is_some());
+            if entries.len() >= self.max_entries
+                && let Some(least_recent) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.remove(&least_recent);
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                value: Arc::new(value.clone()),
+                etag,
+                expires_at: now + ttl,
+                last_used: now,
+            },
+        );
+        let size = entries.len();
+        drop(entries);
+        metrics::set_cache_entries(size);
+    }
+
+    fn invalidate(&self, resource: Option<&str>) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let before = entries.len();
+        match resource.map(|resource| resource.trim_matches('/')) {
+            Some(resource) => entries.retain(|key, _| key.resource() != resource),
+            None => entries.clear(),
+        }
+        let size = entries.len();
+        drop(entries);
+
+        let removed = before - size;
+        info!("Invalidated {removed} cached HCM responses for {}", resource.unwrap_or("all resources"));
+        metrics::set_cache_entries(size);
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Map, json};
+
+    fn cache(max_entries: usize, etags: &[&str]) -> Cache {
+        Cache {
+            entries: Mutex::new(HashMap::new()),
+            ttls: HashMap::from([("publicWorkers".to_string(), Duration::from_millis(50))]),
+            etags: etags.iter().map(ToString::to_string).collect(),
+            max_entries,
+        }
+    }
+
+    fn key(path: &str) -> Key {
+        Key::for_caller(None, false, None, path)
+    }
+
+    fn caller(subject: &str) -> Caller {
+        Caller { subject: subject.to_string(), claims: Arc::new(Map::new()), token: Arc::from("token") }
+    }
+
+    fn is_fresh(lookup: &Lookup) -> bool {
+        matches!(lookup, Lookup::Fresh(_))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn entries_expire_after_their_ttl() {
+        let cache = cache(10, &[]);
+        let workers = key("/publicWorkers?q=PersonId=1");
+        cache.insert(workers.clone(), &json!({ "items": [] }), None);
+        assert!(is_fresh(&cache.get(&workers)));
+
+        tokio::time::advance(Duration::from_millis(80)).await;
+        assert!(matches!(cache.get(&workers), Lookup::Miss));
+
+        // Resources without a TTL are not kept unless they can be revalidated
+        let balances = key("/planBalances?q=personId=1");
+        cache.insert(balances.clone(), &json!({}), None);
+        assert!(matches!(cache.get(&balances), Lookup::Miss));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_entries_with_an_etag_are_revalidated() {
+        let cache = cache(10, &["publicWorkers", "planBalances"]);
+        let balances = key("/planBalances?q=personId=1");
+        cache.insert(balances.clone(), &json!({ "items": [] }), Some("\"v1\"".to_string()));
+        assert!(matches!(cache.get(&balances), Lookup::Stale { etag, .. } if etag == "\"v1\""));
+
+        let workers = key("/publicWorkers?q=PersonId=1");
+        cache.insert(workers.clone(), &json!({}), Some("\"v2\"".to_string()));
+        tokio::time::advance(Duration::from_millis(80)).await;
+        assert!(matches!(cache.get(&workers), Lookup::Stale { .. }));
+        cache.renew(&workers);
+        assert!(is_fresh(&cache.get(&workers)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn only_listed_resources_keep_stale_entries() {
+        let cache = cache(10, &["publicWorkers"]);
+        assert!(cache.accepts("publicWorkers"));
+        assert!(!cache.accepts("planBalances"));
+
+        let balances = key("/planBalances?q=personId=1");
+        cache.insert(balances.clone(), &json!({ "items": [] }), Some("\"v1\"".to_string()));
+        assert!(matches!(cache.get(&balances), Lookup::Miss));
+        assert!(cache.entries.lock().unwrap_or_else(PoisonError::into_inner).is_empty());
+
+        // A cached resource left out still expires, but isn't revalidated
+        let cache = super::Cache { etags: HashSet::new(), ..cache };
+        let workers = key("/publicWorkers?q=PersonId=1");
+        cache.insert(workers.clone(), &json!({}), Some("\"v2\"".to_string()));
+        tokio::time::advance(Duration::from_millis(80)).await;
+        assert!(matches!(cache.get(&workers), Lookup::Miss));
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted_when_full() {
+        let cache = cache(2, &[]);
+        let (first, second, third) = (key("/publicWorkers?1"), key("/publicWorkers?2"), key("/publicWorkers?3"));
+        cache.insert(first.clone(), &json!(1), None);
+        cache.insert(second.clone(), &json!(2), None);
+        assert!(is_fresh(&cache.get(&first)));
+
+        cache.insert(third.clone(), &json!(3), None);
+        assert!(is_fresh(&cache.get(&first)));
+        assert!(matches!(cache.get(&second), Lookup::Miss));
+        assert!(is_fresh(&cache.get(&third)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_entries_are_evicted_before_recent_ones() {
+        let cache = cache(2, &[]);
+        let (stale, recent) = (key("/publicWorkers?1"), key("/publicWorkers?2"));
+        cache.insert(stale.clone(), &json!(1), None);
+        tokio::time::advance(Duration::from_millis(80)).await;
+        cache.insert(recent.clone(), &json!(2), None);
+        // The expired entry was used last, but goes first
+        assert!(matches!(cache.get(&stale), Lookup::Miss));
+
+        cache.insert(key("/publicWorkers?3"), &json!(3), None);
+        assert!(is_fresh(&cache.get(&recent)));
+        assert_eq!(cache.entries.lock().unwrap_or_else(PoisonError::into_inner).len(), 2);
+    }
+
+    #[test]
+    fn delegated_entries_are_kept_per_caller() {
+        let cache = cache(10, &[]);
+        let path = "/publicWorkers?q=PersonId=1";
+        let alice = Key::for_caller(Some("prod"), true, Some(caller("alice")), path);
+        let bob = Key::for_caller(Some("prod"), true, Some(caller("bob")), path);
+        assert_ne!(alice, bob);
+
+        cache.insert(alice.clone(), &json!({ "for": "alice" }), None);
+        assert!(matches!(cache.get(&alice), Lookup::Fresh(value) if value["for"] == "alice"));
+        assert!(matches!(cache.get(&bob), Lookup::Miss));
+
+        // Without delegation HCM answers the same for everyone, so callers share entries
+        let shared = Key::for_caller(Some("prod"), false, Some(caller("alice")), path);
+        assert_eq!(shared, Key::for_caller(Some("prod"), false, Some(caller("bob")), path));
+        // Each environment has its own entries
+        assert_ne!(shared, Key::for_caller(Some("test"), false, None, path));
+    }
+
+    #[test]
+    fn invalidation_drops_one_resource_or_everything() {
+        let cache = cache(10, &[]);
+        let workers = key("/publicWorkers?1");
+        cache.insert(workers.clone(), &json!(1), None);
+        cache.insert(key("/publicWorkers?2"), &json!(2), None);
+
+        assert_eq!(cache.invalidate(Some("absenceTypesLOV")), 0);
+        assert!(is_fresh(&cache.get(&workers)));
+        assert_eq!(cache.invalidate(Some("/publicWorkers")), 2);
+        assert!(matches!(cache.get(&workers), Lookup::Miss));
+    }
+}
@@ -2,104 +2,113 @@
 //!
 //! This module provides the core HTTP infrastructure for communicating with Oracle HCM's REST API:
 //! - **Configuration Management**: Environment-based setup with sensible defaults
-//! - **Connection Pooling**: Shared HTTP client for optimal performance
-//! - **Request Tracing**: OpenTelemetry integration for observability
+//! - **Connection Pooling**: One shared HTTP client per HCM environment
+//! - **Environments**: Several named HCM pods from one process, see `environment`
+//! - **Request Tracing**: OpenTelemetry integration for observability, with W3C
+//!   `traceparent` propagation to HCM, see `propagation`
 //! - **Error Handling**: Comprehensive error types and recovery
-//! - **Authentication**: Basic auth with credential management
+//! - **Retries**: Jittered exponential backoff for transient failures, see `retry`
+//! - **Authentication**: Pluggable providers (Basic, OAuth 2.0, JWT assertion), see `auth`,
+//!   with secrets from files that are reloaded when rotated, see `secret`
+//! - **Caching**: TTL cache of reference data such as person mappings, see `cache`
+//! - **Coalescing**: Identical concurrent `GET`s share one request, see `coalesce`
 //!
 //! # Configuration
-//! All configuration is loaded from environment variables at startup:
+//! All configuration is loaded from environment variables, or the `--config` file, at startup:
 //! - `HCM_BASE_URL` (required): Base URL for your Oracle HCM instance
 //! - `HCM_API_VERSION` (optional): API version, defaults to "11.13.18.05"
-//! - `HCM_USERNAME` (optional): Username, defaults to "`WBC_HR_AGENT`"
-//! - `HCM_PASSWORD` (required): Password for authentication
+//! - `HCM_USERNAME` (required for Basic auth): Username
+//! - `HCM_PASSWORD` (required for Basic auth): Password for authentication, or `HCM_PASSWORD_FILE`
+//! - `HCM_SECRET_RELOAD_SECS` (optional): How often secret files are checked for changes, see `secret`
+//! - `HCM_AUTH_MODE` and related OAuth settings: see the `auth` module
 //! - `REST_FRAMEWORK_VERSION` (optional): Framework version, defaults to "9"
+//! - `HCM_RETRY_*` (optional): Retry attempts, delays and budget, see the `retry` module
+//! - `HCM_BREAKER_*` / `HCM_BULKHEAD_*` (optional): Circuit breaker and concurrency pools, see `breaker` and `bulkhead`
+//! - `HCM_RATE_LIMIT*` / `HCM_MAX_IN_FLIGHT` (optional): Per-resource outbound limits, see `limiter`
+//! - `HCM_CACHE_*` (optional): Per-resource cache TTLs and size, see `cache`
+//! - `HCM_COALESCE_GETS` (optional): Share identical in-flight `GET`s, see `coalesce`
+//! - `HCM_ENVIRONMENTS` / `HCM_ENV_<NAME>_*` (optional): Named HCM environments, see `environment`
 //!
 //! # Performance
-//! The module uses a singleton HTTP client (via `LazyLock`) that is initialized once
-//! and reused across all requests. This provides:
+//! Each HCM environment builds its HTTP client once at startup and reuses it
+//! across all requests. This provides:
 //! - Connection pooling and reuse
 //! - Reduced memory allocations
 //! - Lower latency for subsequent requests
 
-use std::{env, sync::LazyLock, time::Duration};
+use std::{sync::Arc, time::{Duration, Instant}};
 use anyhow::{anyhow, Result};
 use http::Extensions;
-use reqwest::{Body, Method, Request, Response};
+use reqwest::{
+    Body, Method, Request, Response, StatusCode,
+    header::{AUTHORIZATION, ETAG, IF_NONE_MATCH},
+};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware, Result as MiddlewareResult};
 use reqwest_tracing::{
     ReqwestOtelSpanBackend, TracingMiddleware, default_on_request_end, reqwest_otel_span,
 };
-use tracing::{Span, error, info, trace};
+use tracing::{Span, error, info, trace, warn};
 
-use crate::mcp::error::HcmError;
+use crate::{config, mcp::{error::HcmError, redact}, metrics};
+use super::{
+    adf_error::error_from_response,
+    breaker::{BreakerMiddleware, CircuitBreaker},
+    bulkhead::Bulkhead,
+    cache::{self, Lookup},
+    coalesce::coalesce,
+    delegation::delegated_authorization,
+    environment::HcmEnvironment,
+    limiter::{self, LimitedResource, LimiterMiddleware, Limiters},
+    propagation::TraceContextMiddleware,
+    query::HcmQuery,
+    retry::{RetryMiddleware, RetryNonIdempotent},
+};
 
-/// Helper function to load and sanitize environment variables.
+/// Helper function to load and sanitize a setting from the environment or the config file.
 /// Removes surrounding quotes (both single and double) that may be added by shell or .env files.
-fn load_env_var(key: &str) -> Result<String> {
-    env::var(key)
+pub fn load_env_var(key: &str) -> Result<String> {
+    config::lookup(key)
         .map(|s| s.trim_matches(|c| c == '"' || c == '\'').to_string())
-        .map_err(|e| anyhow!("{key} must be set: {e}"))
+        .ok_or_else(|| anyhow!("{key} must be set in the environment or the config file"))
 }
 
-/// Helper function to load optional environment variables with a default value.
+/// Helper function to load an optional setting with a default value.
 /// Removes surrounding quotes that may be added by shell or .env files.
-fn load_env_var_or(key: &str, default: &str) -> String {
-    env::var(key)
-        .unwrap_or_else(|_| default.to_string())
+pub fn load_env_var_or(key: &str, default: &str) -> String {
+    config::lookup(key)
+        .unwrap_or_else(|| default.to_string())
         .trim_matches('"')
         .to_string()
 }
 
 // ============================================================================
-// Configuration - Loaded once at startup
-// ============================================================================
-
-/// Base URL for the Oracle HCM instance (e.g., "<https://your-instance.oraclecloud.com>")
-/// This is required and must be set via environment variable.
-pub static HCM_BASE_URL: LazyLock<Result<String>> = 
-    LazyLock::new(|| load_env_var("HCM_BASE_URL"));
-
-/// Oracle HCM API version used in request paths.
-/// Defaults to "11.13.18.05" if not specified.
-pub static HCM_API_VERSION: LazyLock<String> = 
-    LazyLock::new(|| load_env_var_or("HCM_API_VERSION", "11.13.18.05"));
-
-/// REST Framework Version header value.
-/// Required by most HCM API endpoints. Defaults to "9" if not specified.
-pub static REST_FRAMEWORK_VERSION: LazyLock<String> = 
-    LazyLock::new(|| load_env_var_or("REST_FRAMEWORK_VERSION", "9"));
-
-/// Username for Basic Authentication with Oracle HCM.
-/// Defaults to "`WBC_HR_AGENT`" if not specified.
-pub static HCM_USERNAME: LazyLock<String> = 
-    LazyLock::new(|| load_env_var_or("HCM_USERNAME", "WBC_HR_AGENT"));
-
-/// Password for Basic Authentication with Oracle HCM.
-/// This is required and must be set via environment variable.
-pub static HCM_PASSWORD: LazyLock<Result<String>> = 
-    LazyLock::new(|| load_env_var("HCM_PASSWORD"));
-
-// ============================================================================
-// HTTP Client - Singleton with connection pooling
+// HTTP Client - One per environment, with connection pooling
 // ============================================================================
 
-/// Shared HTTP client with middleware for tracing.
-/// Initialized lazily on first use and reused for all subsequent requests.
+/// Builds an HCM environment's HTTP client with middleware for retries, rate limits and tracing.
 /// Uses a 30-second timeout by default.
 ///
+/// Retries wrap tracing, so every attempt gets its own span, and each
+/// attempt carries that span's `traceparent` header.
+///
 /// Returns a `Result` to handle initialization failures gracefully
 /// (e.g., TLS configuration issues).
-static HTTP_CLIENT: LazyLock<Result<ClientWithMiddleware, String>> = LazyLock::new(|| {
+pub fn build_client(limiters: Arc<Limiters>, breaker: Arc<CircuitBreaker>) -> Result<ClientWithMiddleware, String> {
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(30))
         .build()
         .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
     
     Ok(ClientBuilder::new(client)
+        .with(RetryMiddleware::default())
+        // Below the retries, so each attempt is charged to the rate limits
+        .with(LimiterMiddleware::new(limiters))
+        // Admitted last, once the attempt is about to be sent
+        .with(BreakerMiddleware::new(breaker))
         .with(TracingMiddleware::<CustomTracing>::new())
+        .with(TraceContextMiddleware)
         .build())
-});
+}
 
 // ============================================================================
 // Request Tracing - OpenTelemetry integration
@@ -124,13 +133,17 @@ impl ReqwestOtelSpanBackend for CustomTracing {
     /// - Request headers (for debugging auth/content-type issues)
     /// - Request body (if available)
     ///
-    /// Note: Sensitive data like passwords should be filtered by the tracing layer.
+    /// Headers and body are redacted (see `mcp::redact`) before they are recorded.
     fn on_request_start(req: &Request, _extension: &mut Extensions) -> Span {
+        let request_body = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|bytes| redact::body(&String::from_utf8_lossy(bytes)));
         reqwest_otel_span!(
             name = "hcm-api-request",
             req,
-            request_body = req.body().and_then(|b| b.as_bytes()).map(String::from_utf8_lossy).as_deref(),
-            request_headers = ?req.headers(),
+            request_body = request_body.as_deref(),
+            request_headers = ?redact::headers(req.headers()),
         )
     }
 
@@ -155,6 +168,95 @@ impl ReqwestOtelSpanBackend for CustomTracing {
 // HTTP API Call - Main entry point for all HCM API requests
 // ============================================================================
 
+/// Per-call settings for [`hcm_api_call`].
+#[derive(Debug, Clone, Copy)]
+pub struct CallOptions {
+    /// Whether to add the `REST-Framework-Version` header
+    /// - `true` for most endpoints
+    /// - `false` for endpoints that use the `Effective-Of` header instead
+    pub framework_version: bool,
+    /// Custom timeout override (`None` uses the default 30s timeout).
+    /// Some operations (like projected balance calculations) need longer.
+    pub timeout: Option<Duration>,
+    /// Retry transient failures even though the method isn't idempotent.
+    /// Only set this for actions that are safe to repeat.
+    pub retry_non_idempotent: bool,
+    /// Concurrency pool the call runs in
+    pub bulkhead: Bulkhead,
+    /// Always ask HCM, even for a resource whose responses are cached
+    pub bypass_cache: bool,
+    /// Whose privileges the call runs with when delegation is on
+    pub run_as: RunAs,
+}
+
+/// Whose HCM privileges a call runs with under on-behalf-of delegation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunAs {
+    /// The MCP caller; calls without one are refused
+    Caller,
+    /// The MCP caller, or the service account when there is none
+    CallerOrServiceAccount,
+}
+
+impl CallOptions {
+    /// Options for a plain path such as a POST action, sent with the
+    /// `REST-Framework-Version` header, the default timeout and retry policy.
+    pub const fn new() -> Self {
+        Self {
+            framework_version: true,
+            timeout: None,
+            retry_non_idempotent: false,
+            bulkhead: Bulkhead::Lookup,
+            bypass_cache: false,
+            run_as: RunAs::Caller,
+        }
+    }
+
+    /// Options matching `query`'s framework version, with the default timeout and retry policy.
+    pub const fn for_query(query: &HcmQuery) -> Self {
+        Self {
+            framework_version: query.uses_framework_version(),
+            ..Self::new()
+        }
+    }
+
+    /// Overrides the default timeout.
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs the call in another concurrency pool.
+    #[must_use]
+    pub const fn bulkhead(mut self, bulkhead: Bulkhead) -> Self {
+        self.bulkhead = bulkhead;
+        self
+    }
+
+    /// Opts a non-idempotent request (e.g. a read-only POST action) into retries.
+    #[must_use]
+    pub const fn retry_non_idempotent(mut self) -> Self {
+        self.retry_non_idempotent = true;
+        self
+    }
+
+    /// Runs the call as the service account when there is no caller to delegate for,
+    /// for server checks such as `/readyz`; tool calls must never set this.
+    #[must_use]
+    pub const fn service_account(mut self) -> Self {
+        self.run_as = RunAs::CallerOrServiceAccount;
+        self
+    }
+
+    /// Skips the response cache, for calls that must see HCM's current state.
+    #[must_use]
+    pub const fn bypass_cache(mut self) -> Self {
+        self.bypass_cache = true;
+        self
+    }
+}
+
 /// Makes an authenticated HTTP request to the Oracle HCM REST API.
 /// 
 /// This is the primary function used by all tools to communicate with Oracle HCM.
@@ -162,22 +264,20 @@ impl ReqwestOtelSpanBackend for CustomTracing {
 ///
 /// # Arguments
 /// * `path` - API endpoint path relative to the base URL
-///   - Example: `"/publicWorkers?q=assignments.WorkerNumber='M061230'"`
-///   - Should include query parameters if needed
+///   - Example: `"/publicWorkers?q=assignments.WorkerNumber='E012345'"`
+///   - Should include query parameters if needed, usually built with `HcmQuery::build`
 /// * `method` - HTTP method to use (only `GET` and `POST` are supported)
 /// * `body` - Request body for POST requests (use `None` for GET)
-/// * `enable_framework_version` - Whether to add the `REST-Framework-Version` header
-///   - Set to `true` for most endpoints
-///   - Set to `false` for endpoints that use `Effective-Of` header instead
-/// * `set_timeout` - Custom timeout override (use `None` for default 30s timeout)
-///   - Some operations (like projected balance calculations) may need longer timeouts
+/// * `options` - Framework version header, timeout and retry settings (see [`CallOptions`])
 ///
 /// # Returns
 /// * `Ok(Value)` - Parsed JSON response from the API
 /// * `Err(HcmError)` - Detailed error information:
 ///   - `InvalidParams`: Bad request (e.g., unsupported HTTP method)
 ///   - `MissingConfig`: Environment variable not set
-///   - `Http`: Network error or API returned error status
+///   - `Http`: Network error or timeout
+///   - `Validation`, `Unauthorized`, `Forbidden`, `NotFound`, `RateLimited`, `Unavailable`:
+///     HCM returned an error status, parsed from its ADF error payload (see `adf_error`)
 ///   - `Serialization`: Failed to parse JSON response
 ///
 /// # Example
@@ -186,26 +286,29 @@ impl ReqwestOtelSpanBackend for CustomTracing {
 /// use std::time::Duration;
 /// 
 /// // Simple GET request with default timeout
+/// let query = HcmQuery::new("/publicWorkers").only_data().limit(10);
 /// let workers = hcm_api_call(
-///     "/publicWorkers?onlyData=true&limit=10",
+///     &query.build(),
 ///     Method::GET,
 ///     None,
-///     true,  // Include REST-Framework-Version header
-///     None   // Use default 30s timeout
+///     CallOptions::for_query(&query),  // Include REST-Framework-Version header
 /// ).await?;
 ///
-/// // POST request with custom timeout
+/// // POST request with custom timeout, safe to retry
+/// let query = HcmQuery::new("/absences/action/loadProjectedBalance");
 /// let body = Body::from(r#"{"entry": {"personId": "123"}}"#);
 /// let result = hcm_api_call(
-///     "/absences/action/loadProjectedBalance",
+///     &query.build(),
 ///     Method::POST,
 ///     Some(body),
-///     true,
-///     Some(Duration::from_secs(60))  // 60s timeout for slow operation
+///     CallOptions::for_query(&query)
+///         .timeout(Duration::from_secs(60))  // 60s timeout for slow operation
+///         .retry_non_idempotent(),
 /// ).await?;
 /// ```
 ///
 /// # URL Construction
+/// Calls go to the HCM environment of the current session (see `environment`).
 /// The full URL is built as:
 /// ```text
 /// {HCM_BASE_URL}/hcmRestApi/resources/{HCM_API_VERSION}{path}
@@ -220,45 +323,112 @@ impl ReqwestOtelSpanBackend for CustomTracing {
 /// https://instance.oraclecloud.com/hcmRestApi/resources/11.13.18.05/publicWorkers?onlyData=true
 /// ```
 ///
+/// # Retries
+/// Transient failures (429, 502, 503, 504, connection errors) are retried with
+/// backoff by the `retry` middleware: always for `GET`, and for `POST` only with
+/// [`CallOptions::retry_non_idempotent`].
+///
+/// # Circuit breaker, rate limits and bulkheads
+/// Calls run in the concurrency pool chosen by [`CallOptions::bulkhead`]. Each
+/// attempt then queues for the resource's rate and in-flight limits (see `limiter`,
+/// failing with `RateLimited` past the deadline), and fails fast with `Unavailable`
+/// while the circuit breaker is open (see `breaker`).
+///
 /// # Authentication
-/// Automatically adds HTTP Basic Authentication using:
-/// - Username from `HCM_USERNAME` environment variable
-/// - Password from `HCM_PASSWORD` environment variable
+/// Automatically adds an `Authorization` header from the provider selected by
+/// `HCM_AUTH_MODE` (Basic by default, or a cached OAuth 2.0 access token).
+/// If HCM answers `401`, the provider reloads its secrets (see `secret`) and
+/// the request is sent once more when that could help.
+/// With `HCM_DELEGATION_MODE` enabled, calls made for an authenticated MCP
+/// caller use a delegated token carrying that caller's HCM privileges instead,
+/// and calls without a caller fail with `Unauthorized` unless they set
+/// [`CallOptions::service_account`].
+///
+/// # Caching
+/// `GET`s of reference data (person mappings, absence types, plan metadata)
+/// are answered from the `cache` while fresh, without contacting HCM, unless
+/// [`CallOptions::bypass_cache`] is set. Expired responses with an `ETag` are
+/// revalidated with `If-None-Match`, and a `304 Not Modified` reuses the stored
+/// JSON. Successful responses refresh the cache.
+///
+/// # Coalescing
+/// A `GET` identical to one already in flight (same path, and same caller under
+/// delegation) waits for that request and shares its response or error, see `coalesce`.
+///
+/// # Metrics
+/// Each call's latency is recorded by resource and final status (see `metrics`).
 ///
 /// # Logging
 /// Logs at different levels:
-/// - `INFO`: Request/response summary with URL and status
-/// - `TRACE`: Full JSON response body for debugging
+/// - `INFO`: Request/response summary with URL and status (`q`/`finder` values redacted)
+/// - `TRACE`: Full JSON response body for debugging, with PII fields redacted
 /// - `ERROR`: Error details when requests fail
 pub async fn hcm_api_call(
     path: &str,
     method: Method,
     body: Option<Body>,
-    enable_framework_version: bool,
-    set_timeout: Option<Duration>,
+    options: CallOptions,
+) -> Result<serde_json::Value, HcmError> {
+    // Answer reference lookups from the cache while they are fresh, and
+    // revalidate expired responses by their ETag
+    let cache_key = (method == Method::GET && !options.bypass_cache)
+        .then(|| cache::Key::for_path(path))
+        .flatten();
+    let stale = match cache_key.as_ref().map_or(Lookup::Miss, cache::get) {
+        Lookup::Fresh(cached) => return Ok(cached),
+        Lookup::Stale { etag, value } => Some((etag, value)),
+        Lookup::Miss => None,
+    };
+
+    // Identical GETs already in flight share one upstream request
+    if method == Method::GET {
+        let key = cache_key.clone().unwrap_or_else(|| cache::Key::new(path));
+        return coalesce(key, || send(path, method, None, options, cache_key.as_ref(), stale)).await;
+    }
+    send(path, method, body, options, None, None).await
+}
+
+/// Sends one request to HCM and parses the response. With a `cache_key`, a
+/// `304` for the `stale` entry reuses it and a fresh response is cached.
+async fn send(
+    path: &str,
+    method: Method,
+    body: Option<Body>,
+    options: CallOptions,
+    cache_key: Option<&cache::Key>,
+    stale: Option<(String, Arc<serde_json::Value>)>,
 ) -> Result<serde_json::Value, HcmError> {
-    // Load configuration from static globals (loaded once at startup)
-    let base = HCM_BASE_URL
+    // Load the session's environment (configured once at startup)
+    let environment = HcmEnvironment::current()?;
+    let base = environment
+        .base_url
         .as_ref()
         .map_err(|e| HcmError::MissingConfig(e.to_string()))?;
-    let api_ver = HCM_API_VERSION.as_str();
+    let api_ver = environment.api_version.as_str();
 
     // Construct the full API URL
     let url = format!("{base}/hcmRestApi/resources/{api_ver}{path}");
     
-    info!("HCM API request: {} {}", method, url);
+    info!("HCM API request: {} {}", method, redact::url(&url));
     
-    // Use shared client for optimal performance (connection pooling)
-    let client = HTTP_CLIENT
+    // Use the environment's shared client for optimal performance (connection pooling)
+    let client = environment
+        .client
         .as_ref()
         .map_err(|e| HcmError::Internal(anyhow!("HTTP client initialization failed: {e}")))?
         .clone();
 
-    // Load authentication credentials
-    let username = HCM_USERNAME.as_str();
-    let password = HCM_PASSWORD
-        .as_ref()
-        .map_err(|e| HcmError::MissingConfig(e.to_string()))?;
+    // Run as the MCP caller when delegation is enabled, otherwise as the service account.
+    // Either provider fetches or refreshes a token when needed.
+    let (authorization, service_account) = if let Some(delegated) = delegated_authorization(environment, options.run_as == RunAs::CallerOrServiceAccount).await? {
+        (delegated, None)
+    } else {
+        let auth = environment
+            .auth
+            .as_ref()
+            .map_err(|e| HcmError::MissingConfig(e.to_string()))?;
+        (auth.authorization().await?, Some(auth))
+    };
 
     // Build the HTTP request based on method
     let mut request_builder = match method {
@@ -267,47 +437,186 @@ pub async fn hcm_api_call(
         _ => return Err(HcmError::InvalidParams("Only GET and POST methods are supported".to_string())),
     };
     
-    // Apply custom timeout if specified
-    if let Some(timeout) = set_timeout {
+    // Apply custom timeout if specified (per attempt, when retried)
+    if let Some(timeout) = options.timeout {
         request_builder = request_builder.timeout(timeout);
     }
 
-    // Add HTTP Basic Authentication
-    request_builder = request_builder.basic_auth(username, Some(password));
+    // Let the retry middleware repeat this request even if it isn't idempotent
+    if options.retry_non_idempotent {
+        request_builder = request_builder.with_extension(RetryNonIdempotent);
+    }
+
+    // Charge every attempt, including retries and the resend after a 401, to the resource's limits
+    request_builder = request_builder.with_extension(LimitedResource(limiter::resource_of(path).to_string()));
 
     // Add REST-Framework-Version header if requested (required by most endpoints)
-    if enable_framework_version {
-        let rf_version = REST_FRAMEWORK_VERSION.as_str();
+    if options.framework_version {
+        let rf_version = environment.rest_framework_version.as_str();
         request_builder = request_builder.header("REST-Framework-Version", rf_version);
     }
 
+    // Ask HCM to answer 304 if the stale response is still current
+    if let Some((etag, _)) = &stale {
+        request_builder = request_builder.header(IF_NONE_MATCH, etag.as_str());
+    }
+
     // Add Content-Type header for POST requests (Oracle ADF format)
     if method == Method::POST {
         request_builder =
             request_builder.header("Content-Type", "application/vnd.oracle.adf.action+json");
     }
 
+    // Keep a copy without credentials, to resend if HCM rejects the service account's
+    let resend = service_account.and_then(|auth| Some((auth, request_builder.try_clone()?)));
+
+    // Add authentication
+    request_builder = request_builder.header(AUTHORIZATION, authorization);
+
+    // Queue for a slot in the call's pool, held until the response body has been read.
+    // The client's middleware then takes each attempt's rate-limit token and, just
+    // before sending, the circuit breaker's admission, failing fast while HCM is down.
+    let _slot = environment.pools.acquire(options.bulkhead).await?;
+
     // Execute the request
-    let response = request_builder.send().await?;
+    let started = Instant::now();
+    let mut outcome = request_builder.send().await;
+    if let Some((auth, resend)) = resend
+        && matches!(&outcome, Ok(response) if response.status() == StatusCode::UNAUTHORIZED)
+        && auth.reauthenticate().await
+    {
+        warn!(auth_mode = auth.name(), "HCM rejected the service account, retrying once with reloaded secrets");
+        outcome = resend.header(AUTHORIZATION, auth.authorization().await?).send().await;
+    }
+    metrics::record_hcm_request(limiter::resource_of(path), &outcome, started.elapsed());
+    let response = outcome?;
     let status = response.status();
     
-    info!("HCM API response: {} {} - Status: {}", method, url, status);
+    info!("HCM API response: {} {} - Status: {}", method, redact::url(&url), status);
+
+    if let (Some(key), Some((_, cached))) = (cache_key, stale) {
+        let not_modified = status == StatusCode::NOT_MODIFIED;
+        metrics::record_cache_revalidation(limiter::resource_of(path), not_modified);
+        if not_modified {
+            cache::renew(key);
+            return Ok(Arc::unwrap_or_clone(cached));
+        }
+    }
     
-    // Handle error responses
+    // Handle error responses - parse Oracle's ADF error payload into a specific variant
     if !status.is_success() {
-        let error_text = response.text().await
-            .unwrap_or_else(|e| format!("Unable to read error response body: {e}"));
-        
-        error!("HCM API request failed with status {}: {}", status, error_text);
-        return Err(HcmError::Internal(anyhow!("HTTP {status}: {error_text}")));
+        let error = error_from_response(response).await;
+        error!("HCM API request failed with status {}: {}", status, error);
+        return Err(error);
     }
     
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+
     // Parse successful JSON response
     let json_response = response.json::<serde_json::Value>().await.map_err(|e| {
         error!("HCM API failed to parse successful response as JSON: {}", e);
         HcmError::Internal(anyhow!("JSON parsing failed: {e}"))
     })?;
     
-    trace!("HCM API response (JSON): {:?}", json_response);
+    trace!("HCM API response (JSON): {}", redact::json(&json_response));
+    if let Some(key) = cache_key {
+        cache::insert(key.clone(), &json_response, etag);
+    }
     Ok(json_response)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::auth::{BoxFuture, HcmAuthProvider};
+    use axum::{Json, Router, http::HeaderMap, routing::get};
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+    use std::sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    /// The only credentials the HCM stand-in accepts.
+    const ACCEPTED: &str = "Bearer rotated";
+
+    /// A service account whose secret is rotated to `reloaded` when reauthenticating.
+    struct Rotating {
+        token: Mutex<&'static str>,
+        /// `None` if re-reading the secrets finds nothing new
+        reloaded: Option<&'static str>,
+        reauthentications: Arc<AtomicUsize>,
+    }
+
+    impl HcmAuthProvider for Rotating {
+        fn name(&self) -> &'static str {
+            "rotating"
+        }
+
+        fn authorization(&self) -> BoxFuture<'_, Result<HeaderValue, HcmError>> {
+            let token = *self.token.lock().unwrap_or_else(PoisonError::into_inner);
+            Box::pin(async move { Ok(HeaderValue::from_static(token)) })
+        }
+
+        fn reauthenticate(&self) -> BoxFuture<'_, bool> {
+            self.reauthentications.fetch_add(1, Ordering::Relaxed);
+            let reloaded = self.reloaded.inspect(|token| {
+                *self.token.lock().unwrap_or_else(PoisonError::into_inner) = token;
+            });
+            Box::pin(async move { reloaded.is_some() })
+        }
+    }
+
+    /// Calls `/workers` as a service account rotated to `reloaded`, returning
+    /// the result, the requests HCM received and the reauthentications.
+    async fn call(name: &str, reloaded: Option<&'static str>) -> (Result<serde_json::Value, HcmError>, usize, usize) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let reauthentications = Arc::new(AtomicUsize::new(0));
+        let received = requests.clone();
+        let app = Router::new().route(
+            "/workers",
+            get(move |headers: HeaderMap| async move {
+                received.fetch_add(1, Ordering::Relaxed);
+                if headers.get(AUTHORIZATION).is_some_and(|value| value == ACCEPTED) {
+                    (StatusCode::OK, Json(json!({ "items": [] })))
+                } else {
+                    (StatusCode::UNAUTHORIZED, Json(json!({ "title": "Unauthorized" })))
+                }
+            }),
+        );
+        let auth = Rotating {
+            token: Mutex::new("Bearer stale"),
+            reloaded,
+            reauthentications: reauthentications.clone(),
+        };
+        let environment = HcmEnvironment::stand_in_with_auth(name, app, Box::new(auth)).await;
+
+        let result = HcmEnvironment::scope(environment, hcm_api_call("/workers", Method::GET, None, CallOptions::new())).await;
+        (result, requests.load(Ordering::Relaxed), reauthentications.load(Ordering::Relaxed))
+    }
+
+    #[tokio::test]
+    async fn rejected_service_account_is_resent_once_with_reloaded_secrets() {
+        let (result, requests, reauthentications) = call("resendrotated", Some(ACCEPTED)).await;
+        assert!(result.is_ok(), "{result:?}");
+        assert_eq!((requests, reauthentications), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn second_rejection_is_returned_without_another_resend() {
+        let (result, requests, reauthentications) = call("resendrejected", Some("Bearer also-stale")).await;
+        assert!(matches!(result, Err(HcmError::Unauthorized(_))), "{result:?}");
+        assert_eq!((requests, reauthentications), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn unchanged_secrets_are_not_resent() {
+        let (result, requests, reauthentications) = call("resendunchanged", None).await;
+        assert!(matches!(result, Err(HcmError::Unauthorized(_))), "{result:?}");
+        assert_eq!((requests, reauthentications), (1, 1));
+    }
+}
@@ -0,0 +1,157 @@
+//! Single-flight coalescing of identical concurrent `GET`s.
+//!
+//! Parallel tool calls in one agent turn, or several sessions at once, often
+//! ask HCM for the same worker or balances at the same moment. The first
+//! request for a key (see `cache::Key`: the path, plus the caller under
+//! delegation) goes to HCM; identical requests arriving while it is in flight
+//! wait for it and get a copy of its response, or of its error.
+//!
+//! If the request leading a group is cancelled (its tool call was dropped),
+//! the next waiter makes the request instead, so nobody waits on a request
+//! that will never finish. Followers don't count against rate limits or
+//! bulkheads, since they make no request of their own.
+//!
+//! # Configuration
+//! - `HCM_COALESCE_GETS` (optional): Share identical in-flight `GET`s, defaults to true
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+};
+use serde_json::Value;
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use crate::{config::tuning, mcp::error::HcmError, metrics};
+use super::cache::Key;
+
+/// A response shared by every request in a group; errors are shared by reference.
+type Shared = Result<Value, Arc<HcmError>>;
+
+/// Groups of identical requests currently in flight.
+static IN_FLIGHT: LazyLock<Mutex<HashMap<Key, Arc<OnceCell<Shared>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Runs `fetch` for `key`, or waits for an identical request already in flight
+/// and returns a copy of its result.
+///
+/// # Errors
+/// Returns the error of whichever request reached HCM, as `HcmError::Shared`
+/// while other waiters still hold it; match on [`HcmError::kind`], which sees through it.
+pub async fn coalesce<F, Fut>(key: Key, fetch: F) -> Result<Value, HcmError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Value, HcmError>>,
+{
+    if !tuning().coalesce_gets {
+        return fetch().await;
+    }
+
+    let group = join(&key);
+    let mut led = false;
+    let result = group
+        .get_or_init(|| {
+            led = true;
+            async { fetch().await.map_err(Arc::new) }
+        })
+        .await
+        .clone();
+
+    finish(&key, &group);
+    drop(group);
+
+    if !led {
+        debug!("Coalesced HCM request for {}", key.resource());
+        metrics::record_coalesced(key.resource());
+    }
+    // The last holder of an error gets it back unwrapped
+    result.map_err(|e| Arc::try_unwrap(e).unwrap_or_else(HcmError::Shared))
+}
+
+/// Returns the group of requests in flight for `key`, starting one if there is none.
+fn join(key: &Key) -> Arc<OnceCell<Shared>> {
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner);
+    let group = Arc::clone(in_flight.entry(key.clone()).or_default());
+    drop(in_flight);
+    group
+}
+
+/// Retires a finished group, so later requests for `key` start a new one.
+fn finish(key: &Key, group: &Arc<OnceCell<Shared>>) {
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner);
+    if in_flight.get(key).is_some_and(|current| Arc::ptr_eq(current, group)) {
+        in_flight.remove(key);
+    }
+    drop(in_flight);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::error::ErrorKind;
+    use rmcp::ErrorData;
+    use serde_json::json;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tokio::{sync::oneshot, task::yield_now, time::timeout};
+
+    #[tokio::test]
+    async fn errors_reach_every_waiter() {
+        let key = Key::new("/publicWorkers?coalesce-errors");
+        let fetches = AtomicUsize::new(0);
+        let (release, released) = oneshot::channel::<()>();
+
+        let leader = coalesce(key.clone(), || async {
+            let _ = released.await;
+            fetches.fetch_add(1, Ordering::SeqCst);
+            Err(HcmError::not_found("No such worker"))
+        });
+        let follower = || {
+            coalesce(key.clone(), || async {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                Ok(json!("not shared"))
+            })
+        };
+        // Every request joins the group before the leader's response arrives
+        let (first, second, third, ()) = tokio::join!(leader, follower(), follower(), async {
+            yield_now().await;
+            let _ = release.send(());
+        });
+
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        let errors: Vec<HcmError> = [first, second, third]
+            .into_iter()
+            .map(|result| result.err().unwrap_or_else(|| unreachable!("every waiter gets the error")))
+            .collect();
+        assert!(errors.iter().any(|e| matches!(e, HcmError::Shared(_))));
+        for e in &errors {
+            assert_eq!(e.kind(), ErrorKind::NotFound);
+            let data = ErrorData::from(e);
+            assert_eq!(data.message, "No such worker");
+            assert_eq!(data.data.as_ref().map(|data| &data["kind"]), Some(&json!("not_found")));
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_leader_hands_over_to_a_waiter() {
+        let key = Key::new("/publicWorkers?coalesce-cancelled");
+        let mut leader = Box::pin(coalesce(key.clone(), std::future::pending::<Result<Value, HcmError>>));
+        let mut follower = Box::pin(coalesce(key.clone(), || async { Ok(json!("from follower")) }));
+
+        // The leader's request is in flight, so the follower waits for it
+        assert!(timeout(Duration::from_millis(20), &mut leader).await.is_err());
+        assert!(timeout(Duration::from_millis(20), &mut follower).await.is_err());
+
+        // Once the leader's tool call is dropped, the follower makes the request itself
+        drop(leader);
+        let result = timeout(Duration::from_secs(1), follower).await;
+        assert!(matches!(result, Ok(Ok(value)) if value == "from follower"));
+
+        // The group is retired, so the next request starts afresh
+        let next = coalesce(key, || async { Ok(json!("fresh")) }).await;
+        assert!(matches!(next, Ok(value) if value == "fresh"));
+    }
+}
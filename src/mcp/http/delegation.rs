@@ -0,0 +1,382 @@
+//! On-behalf-of delegation: HCM calls run with the MCP caller's own privileges.
+//!
+//! Without delegation every request uses the shared integration account, so
+//! HCM's data security roles never see the real user. In delegation mode the
+//! caller's validated bearer token (see `mcp::caller`) is exchanged for an HCM
+//! token scoped to that user:
+//! - **Token exchange** (RFC 8693): the caller's token is the `subject_token`
+//! - **JWT user assertion** (RFC 7523): a JWT for the caller's HCM username,
+//!   signed with `HCM_JWT_PRIVATE_KEY_FILE`, is exchanged for a user token
+//!
+//! Delegated tokens are cached per caller token, keyed by its SHA-256 digest so
+//! raw credentials are never held as map keys, and never outlive it.
+//! Tool calls without a caller (stdio, or authentication disabled) fail with
+//! `HcmError::Unauthorized` rather than running with the service account's
+//! wider privileges. Only calls that opt in with `CallOptions::service_account`,
+//! such as the `/readyz` check, use the service account provider in `auth`.
+//!
+//! Both grants go to `HCM_OAUTH_TOKEN_URL`, so a local token-endpoint stand-in
+//! can be used for testing.
+//!
+//! # Configuration
+//! - `HCM_DELEGATION_MODE` (optional): `off`, `token_exchange` or `jwt_user_assertion`, defaults to "off"
+//! - `HCM_DELEGATION_SUBJECT_TOKEN_TYPE` (`token_exchange`, optional): defaults to "`urn:ietf:params:oauth:token-type:jwt`"
+//! - `HCM_DELEGATION_AUDIENCE` (`token_exchange`, optional): Requested audience of the HCM token
+//! - `HCM_DELEGATION_USER_CLAIM` (`jwt_user_assertion`, optional): Claim holding the caller's HCM username, defaults to "sub"
+//! - `HCM_OAUTH_*` / `HCM_JWT_*`: Token endpoint, client credentials and signing key, see `auth`
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::Duration,
+};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use reqwest::header::HeaderValue;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+use crate::mcp::{caller::Caller, error::HcmError};
+use super::{
+    auth::{CachedToken, JwtAssertion, OAuthClient},
+    environment::{HcmEnvironment, Settings},
+};
+
+/// Upper bound on cached delegated tokens; expired entries are pruned first
+const MAX_CACHED_TOKENS: usize = 1024;
+
+/// SHA-256 digest of a caller's bearer token, identifying it in the cache.
+type TokenKey = [u8; 32];
+
+/// How the caller's identity is presented to the token endpoint.
+enum Grant {
+    TokenExchange {
+        client: OAuthClient,
+        subject_token_type: String,
+        audience: Option<String>,
+    },
+    JwtUserAssertion {
+        assertion: JwtAssertion,
+        user_claim: String,
+    },
+}
+
+/// Exchanges MCP caller tokens for user-scoped HCM tokens.
+pub struct Delegation {
+    grant: Grant,
+    /// Delegated tokens keyed by the digest of the caller's bearer token
+    tokens: Mutex<HashMap<TokenKey, CachedToken>>,
+}
+
+/// The delegation provider, or `None` when `HCM_DELEGATION_MODE` is "off".
+///
+/// Returns an error (surfaced as `MissingConfig`) if the mode is unknown or
+/// its required settings are missing.
+pub fn provider(settings: Settings<'_>) -> Result<Option<Delegation>> {
+    let grant = match settings.var_or("HCM_DELEGATION_MODE", "off").as_str() {
+        "off" => return Ok(None),
+        "token_exchange" => Grant::TokenExchange {
+            client: OAuthClient::from_env(settings, false)?,
+            subject_token_type: settings.var_or(
+                "HCM_DELEGATION_SUBJECT_TOKEN_TYPE",
+                "urn:ietf:params:oauth:token-type:jwt",
+            ),
+            audience: settings.var("HCM_DELEGATION_AUDIENCE").ok(),
+        },
+        "jwt_user_assertion" => Grant::JwtUserAssertion {
+            assertion: JwtAssertion::from_env(settings)?,
+            user_claim: settings.var_or("HCM_DELEGATION_USER_CLAIM", "sub"),
+        },
+        other => {
+            return Err(anyhow!(
+                "HCM_DELEGATION_MODE must be off, token_exchange or jwt_user_assertion, got {other}"
+            ));
+        }
+    };
+    info!("HCM on-behalf-of delegation enabled");
+    Ok(Some(Delegation {
+        grant,
+        tokens: Mutex::new(HashMap::new()),
+    }))
+}
+
+impl Delegation {
+    /// Returns the header for a request made for `caller`, or `None` for a request
+    /// without one that may use the service account.
+    ///
+    /// # Errors
+    /// * `Unauthorized` - If there is no caller and `service_account` is `false`
+    /// * Any error from [`Delegation::authorization_for`]
+    pub async fn authorization(&self, caller: Option<&Caller>, service_account: bool) -> Result<Option<HeaderValue>, HcmError> {
+        match caller {
+            Some(caller) => self.authorization_for(caller).await.map(Some),
+            None if service_account => Ok(None),
+            None => Err(HcmError::unauthorized(
+                "HCM delegation is enabled, but the request has no authenticated caller to act for",
+            )),
+        }
+    }
+
+    /// Returns an `Authorization` header that runs the request as `caller` in HCM.
+    pub async fn authorization_for(&self, caller: &Caller) -> Result<HeaderValue, HcmError> {
+        let key = token_key(&caller.token);
+        if let Some(header) = self.cached(&key) {
+            return Ok(header);
+        }
+
+        let response = match &self.grant {
+            Grant::TokenExchange { client, subject_token_type, audience } => {
+                let mut grant = vec![
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:token-exchange"),
+                    ("subject_token", caller.token.as_ref()),
+                    ("subject_token_type", subject_token_type.as_str()),
+                    ("requested_token_type", "urn:ietf:params:oauth:token-type:access_token"),
+                ];
+                if let Some(audience) = audience {
+                    grant.push(("audience", audience));
+                }
+                client.request_token(&grant).await?
+            }
+            Grant::JwtUserAssertion { assertion, user_claim } => {
+                let username = caller.claim_str(user_claim).ok_or_else(|| {
+                    HcmError::forbidden(format!(
+                        "Caller token has no {user_claim} claim to delegate to HCM"
+                    ))
+                })?;
+                assertion.exchange_assertion(username).await?
+            }
+        };
+
+        let (header, lifetime) = response.into_header()?;
+        // A delegated token must never outlive the caller's own token
+        let lifetime = caller_token_lifetime(caller).map_or(lifetime, |remaining| lifetime.min(remaining));
+        debug!(subject = %caller.subject, "obtained delegated HCM token valid for {}s", lifetime.as_secs());
+
+        self.store(key, CachedToken::new(header.clone(), lifetime));
+        Ok(header)
+    }
+
+    /// Returns a fresh cached token for the caller's bearer token.
+    fn cached(&self, key: &TokenKey) -> Option<HeaderValue> {
+        let tokens = self.tokens.lock().ok()?;
+        tokens
+            .get(key)
+            .filter(|cached| cached.is_fresh())
+            .map(|cached| cached.header.clone())
+    }
+
+    /// Caches a delegated token, pruning expired entries when the cache is full.
+    fn store(&self, key: TokenKey, token: CachedToken) {
+        let Ok(mut tokens) = self.tokens.lock() else {
+            return;
+        };
+        if tokens.len() >= MAX_CACHED_TOKENS {
+            tokens.retain(|_, cached| cached.is_fresh());
+        }
+        if tokens.len() < MAX_CACHED_TOKENS {
+            tokens.insert(key, token);
+        }
+    }
+}
+
+/// Cache key for a caller's bearer token.
+fn token_key(token: &str) -> TokenKey {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+/// Time left until the caller's token expires, from its `exp` claim.
+fn caller_token_lifetime(caller: &Caller) -> Option<Duration> {
+    let exp = caller.claims.get("exp")?.as_i64()?;
+    let remaining = u64::try_from(exp - Utc::now().timestamp()).unwrap_or(0);
+    Some(Duration::from_secs(remaining))
+}
+
+/// Returns the delegated header for the current caller in `environment`, or
+/// `None` when delegation is off there, or for a request without a caller that
+/// opted into the `service_account`.
+///
+/// # Errors
+/// * `MissingConfig` - If delegation is misconfigured
+/// * `Unauthorized` - If delegation is on, there is no caller and `service_account` is `false`
+/// * Any error from the token endpoint
+pub async fn delegated_authorization(
+    environment: &HcmEnvironment,
+    service_account: bool,
+) -> Result<Option<HeaderValue>, HcmError> {
+    let delegation = environment
+        .delegation
+        .as_ref()
+        .map_err(|e| HcmError::MissingConfig(e.to_string()))?;
+    let Some(delegation) = delegation else {
+        return Ok(None);
+    };
+    delegation.authorization(Caller::current().as_ref(), service_account).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::http::auth::stand_in::{received, stand_in};
+    use jsonwebtoken::{DecodingKey, Validation, decode};
+    use serde_json::{Map, Value, json};
+    use std::sync::Arc;
+
+    const SIGNING_KEY: &str = include_str!("testdata/jwt_assertion_key.pem");
+    const VERIFYING_KEY: &str = include_str!("testdata/jwt_assertion_key.pub.pem");
+
+    fn caller(token: &str, expires_in: i64, claims: Value) -> Caller {
+        let Value::Object(mut claims) = claims else {
+            unreachable!("claims must be an object")
+        };
+        claims.insert("exp".to_string(), json!(Utc::now().timestamp() + expires_in));
+        Caller {
+            subject: "alice".to_string(),
+            claims: Arc::new(claims),
+            token: Arc::from(token),
+        }
+    }
+
+    fn delegation(grant: Grant) -> Delegation {
+        Delegation { grant, tokens: Mutex::new(HashMap::new()) }
+    }
+
+    #[tokio::test]
+    async fn token_exchange_sends_the_caller_token_as_subject() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::TokenExchange {
+            client: OAuthClient::new(&url, "mcp-server", Some("s3cret")),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            audience: Some("urn:hcm".to_string()),
+        });
+
+        let header = delegation.authorization_for(&caller("caller-jwt", 600, json!({}))).await?;
+        assert_eq!(header.to_str().ok(), Some("Bearer token-1"));
+
+        let grants = received(&log);
+        assert_eq!(grants.len(), 1);
+        let form = &grants[0].form;
+        assert_eq!(form["grant_type"], "urn:ietf:params:oauth:grant-type:token-exchange");
+        assert_eq!(form["subject_token"], "caller-jwt");
+        assert_eq!(form["subject_token_type"], "urn:ietf:params:oauth:token-type:jwt");
+        assert_eq!(form["audience"], "urn:hcm");
+        assert!(!form.contains_key("client_id"));
+        assert!(grants[0].authorization.as_deref().is_some_and(|auth| auth.starts_with("Basic ")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn jwt_user_assertion_is_signed_for_the_callers_username() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::JwtUserAssertion {
+            assertion: JwtAssertion::new(OAuthClient::new(&url, "mcp-server", None), SIGNING_KEY, "urn:idcs"),
+            user_claim: "upn".to_string(),
+        });
+
+        let header = delegation
+            .authorization_for(&caller("caller-jwt", 600, json!({ "upn": "ALICE.SMITH" })))
+            .await?;
+        assert_eq!(header.to_str().ok(), Some("Bearer token-1"));
+
+        let grants = received(&log);
+        assert_eq!(grants.len(), 1);
+        let form = &grants[0].form;
+        assert_eq!(form["grant_type"], "urn:ietf:params:oauth:grant-type:jwt-bearer");
+        assert_eq!(form["client_id"], "mcp-server");
+
+        let key = DecodingKey::from_rsa_pem(VERIFYING_KEY.as_bytes())
+            .unwrap_or_else(|e| unreachable!("test key: {e}"));
+        let mut validation = Validation::new(jsonwebtoken::Algorithm::RS256);
+        validation.set_audience(&["urn:idcs"]);
+        validation.set_issuer(&["mcp-server"]);
+        let assertion = decode::<Map<String, Value>>(&form["assertion"], &key, &validation)
+            .unwrap_or_else(|e| unreachable!("assertion rejected: {e}"));
+        assert_eq!(assertion.claims["sub"], "ALICE.SMITH");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn caller_without_the_user_claim_is_forbidden() {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::JwtUserAssertion {
+            assertion: JwtAssertion::new(OAuthClient::new(&url, "mcp-server", None), SIGNING_KEY, "urn:idcs"),
+            user_claim: "upn".to_string(),
+        });
+
+        let result = delegation.authorization_for(&caller("caller-jwt", 600, json!({}))).await;
+        assert!(matches!(result, Err(HcmError::Forbidden(_))));
+        assert!(received(&log).is_empty());
+    }
+
+    #[tokio::test]
+    async fn delegated_tokens_are_cached_per_caller_token() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::TokenExchange {
+            client: OAuthClient::new(&url, "mcp-server", Some("s3cret")),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            audience: None,
+        });
+        let alice = caller("alice-jwt", 600, json!({}));
+        let bob = caller("bob-jwt", 600, json!({}));
+
+        let first = delegation.authorization_for(&alice).await?;
+        let again = delegation.authorization_for(&alice).await?;
+        let other = delegation.authorization_for(&bob).await?;
+        assert_eq!(first, again);
+        assert_ne!(first, other);
+        assert_eq!(received(&log).len(), 2);
+
+        let tokens = delegation.tokens.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+        assert!(tokens.contains_key(&token_key("alice-jwt")));
+        assert_eq!(tokens.len(), 2);
+        drop(tokens);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delegated_token_does_not_outlive_the_callers() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::TokenExchange {
+            client: OAuthClient::new(&url, "mcp-server", Some("s3cret")),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            audience: None,
+        });
+        // Expires within the refresh skew, so it is never served from the cache
+        let expiring = caller("expiring-jwt", 30, json!({}));
+
+        delegation.authorization_for(&expiring).await?;
+        delegation.authorization_for(&expiring).await?;
+        assert_eq!(received(&log).len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn request_without_a_caller_is_unauthorized() {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::TokenExchange {
+            client: OAuthClient::new(&url, "mcp-server", Some("s3cret")),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            audience: None,
+        });
+
+        let result = delegation.authorization(None, false).await;
+        assert!(matches!(result, Err(HcmError::Unauthorized(_))), "{result:?}");
+        assert!(received(&log).is_empty());
+    }
+
+    #[tokio::test]
+    async fn service_account_is_used_only_when_opted_into() -> Result<(), HcmError> {
+        let (url, log) = stand_in().await;
+        let delegation = delegation(Grant::TokenExchange {
+            client: OAuthClient::new(&url, "mcp-server", Some("s3cret")),
+            subject_token_type: "urn:ietf:params:oauth:token-type:jwt".to_string(),
+            audience: None,
+        });
+
+        assert!(delegation.authorization(None, true).await?.is_none());
+        // A caller is always delegated, even when the service account is allowed
+        let header = delegation.authorization(Some(&caller("caller-jwt", 600, json!({}))), true).await?;
+        assert_eq!(header.and_then(|header| header.to_str().ok().map(str::to_string)).as_deref(), Some("Bearer token-1"));
+        assert_eq!(received(&log).len(), 1);
+        Ok(())
+    }
+}
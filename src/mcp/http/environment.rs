@@ -0,0 +1,482 @@
+//! Named Oracle HCM environments served by one process.
+//!
+//! Teams usually work against several Fusion pods (dev, test, prod), each with
+//! its own base URL, API version and credentials. `HCM_ENVIRONMENTS` names them,
+//! and each gets its own HTTP client, authentication provider, delegation,
+//! circuit breaker, concurrency pools, rate limiters and cache entries, so a
+//! struggling test pod can't trip prod's breaker or use up its limits.
+//!
+//! An environment's settings are read from its own variables first, e.g.
+//! `HCM_ENV_PROD_BASE_URL` or `HCM_ENV_PROD_PASSWORD` for `prod` (see
+//! `config::scoped_name`), then from the shared ones (`HCM_BASE_URL`, ...), so
+//! values common to every pod are set once. Without `HCM_ENVIRONMENTS` there is
+//! a single environment, `default`, configured by the shared variables alone.
+//!
+//! # Session binding
+//! Each MCP session is bound to one environment when it initializes, taken from the first of:
+//! 1. The endpoint path: `/mcp/{environment}`
+//! 2. The `X-HCM-Environment` header of the `initialize` request
+//! 3. The caller's `MCP_ENVIRONMENT_CLAIM` token claim (its first entry if it is a list)
+//! 4. `HCM_DEFAULT_ENVIRONMENT`
+//!
+//! When `MCP_ENVIRONMENT_CLAIM` is set, authenticated callers may only bind to
+//! the environments their claim lists. Tool calls then run inside
+//! [`HcmEnvironment::scope`], so `hcm_api_call` reaches the session's pod.
+//!
+//! # Configuration
+//! - `HCM_ENVIRONMENTS` (optional): Environment names, comma-separated, e.g. "dev,test,prod";
+//!   letters and digits only
+//! - `HCM_DEFAULT_ENVIRONMENT` (optional): Environment of sessions that don't choose one, defaults to the first listed
+//! - `HCM_ENV_<NAME>_*` (optional): Per-environment connection, authentication and delegation settings
+//! - `MCP_ENVIRONMENT_CLAIM` (optional): Token claim listing the environments a caller may use
+
+use std::{future::Future, sync::{Arc, LazyLock}};
+use anyhow::{Result, anyhow, bail};
+use axum::extract::OriginalUri;
+use http::request;
+use reqwest_middleware::ClientWithMiddleware;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    config::{environment_names, scoped_name, tuning},
+    mcp::{caller::Caller, error::HcmError},
+};
+use super::{
+    auth::{self, HcmAuthProvider},
+    breaker::CircuitBreaker,
+    bulkhead::Pools,
+    client::{self, load_env_var, load_env_var_or},
+    delegation::{self, Delegation},
+    limiter::Limiters,
+};
+
+/// Name of the only environment when `HCM_ENVIRONMENTS` is unset.
+pub const DEFAULT_ENVIRONMENT: &str = "default";
+
+/// Request header choosing the environment of a new session.
+const ENVIRONMENT_HEADER: &str = "x-hcm-environment";
+
+tokio::task_local! {
+    /// The environment of the session whose tool call is running on this task.
+    static CURRENT_ENVIRONMENT: &'static HcmEnvironment;
+}
+
+/// Every configured environment, built once at startup.
+static ENVIRONMENTS: LazyLock<Result<Environments>> = LazyLock::new(Environments::load);
+
+/// Claim listing the environments an authenticated caller may use.
+static ENVIRONMENT_CLAIM: LazyLock<Option<String>> =
+    LazyLock::new(|| load_env_var("MCP_ENVIRONMENT_CLAIM").ok().filter(|claim| !claim.is_empty()));
+
+/// Reads one environment's settings: its own variables first, then the shared ones.
+#[derive(Debug, Clone, Copy)]
+pub struct Settings<'a> {
+    /// `None` for the single unnamed environment, which only has the shared variables
+    environment: Option<&'a str>,
+}
+
+#[cfg(test)]
+impl<'a> Settings<'a> {
+    /// The settings of `environment`, or the shared ones alone for `None`.
+    pub const fn new(environment: Option<&'a str>) -> Self {
+        Self { environment }
+    }
+}
+
+impl Settings<'_> {
+    /// Like [`load_env_var`], preferring the environment's own variable.
+    ///
+    /// # Errors
+    /// Fails if neither variable is set.
+    pub fn var(self, key: &str) -> Result<String> {
+        let Some(environment) = self.environment else {
+            return load_env_var(key);
+        };
+        let scoped = scoped_name(environment, key);
+        load_env_var(&scoped)
+            .or_else(|_| load_env_var(key))
+            .map_err(|_| anyhow!("{scoped} or {key} must be set in the environment or the config file"))
+    }
+
+    /// The variables `key` is read from, most specific first.
+    pub fn names(self, key: &str) -> Vec<String> {
+        self.environment
+            .map(|environment| scoped_name(environment, key))
+            .into_iter()
+            .chain([key.to_string()])
+            .collect()
+    }
+
+    /// Like [`load_env_var_or`], preferring the environment's own variable.
+    pub fn var_or(self, key: &str, default: &str) -> String {
+        self.environment
+            .and_then(|environment| load_env_var(&scoped_name(environment, key)).ok())
+            .unwrap_or_else(|| load_env_var_or(key, default))
+    }
+}
+
+/// One Oracle HCM pod and everything used to call it.
+pub struct HcmEnvironment {
+    /// Name used in `/mcp/{environment}`, logs and the audit log
+    pub name: String,
+    /// Base URL, e.g. "<https://your-instance.oraclecloud.com>" (`HCM_BASE_URL`)
+    pub base_url: Result<String>,
+    /// API version used in request paths (`HCM_API_VERSION`, defaults to "11.13.18.05")
+    pub api_version: String,
+    /// `REST-Framework-Version` header value (`REST_FRAMEWORK_VERSION`, defaults to "9")
+    pub rest_framework_version: String,
+    /// Service account provider selected by `HCM_AUTH_MODE`
+    pub auth: Result<Box<dyn HcmAuthProvider>>,
+    /// On-behalf-of delegation, `None` when `HCM_DELEGATION_MODE` is "off"
+    pub delegation: Result<Option<Delegation>>,
+    /// HTTP client with its own connection pool, rate limiters and circuit breaker
+    pub client: Result<ClientWithMiddleware, String>,
+    pub pools: Pools,
+}
+
+impl HcmEnvironment {
+    fn new(name: &str, settings: Settings<'_>) -> Self {
+        Self {
+            name: name.to_string(),
+            base_url: settings.var("HCM_BASE_URL"),
+            api_version: settings.var_or("HCM_API_VERSION", "11.13.18.05"),
+            rest_framework_version: settings.var_or("REST_FRAMEWORK_VERSION", "9"),
+            auth: auth::provider(settings),
+            delegation: delegation::provider(settings),
+            client: client::build_client(
+                Arc::new(Limiters::default()),
+                Arc::new(CircuitBreaker::new(name, tuning().breaker)),
+            ),
+            pools: Pools::default(),
+        }
+    }
+
+    /// An environment whose HCM is the local stand-in `app`, serving the
+    /// resources under `/hcmRestApi/resources/latest` to basic authentication.
+    #[cfg(test)]
+    pub async fn stand_in(name: &str, app: axum::Router) -> &'static Self {
+        Self::stand_in_with_auth(name, app, Box::new(auth::BasicAuth::new("HCM.INTEGRATION", "s3cret"))).await
+    }
+
+    /// Like [`Self::stand_in`], authenticating with `auth`.
+    #[cfg(test)]
+    pub async fn stand_in_with_auth(name: &str, app: axum::Router, auth: Box<dyn HcmAuthProvider>) -> &'static Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap_or_else(|e| unreachable!("bind: {e}"));
+        let addr = listener.local_addr().unwrap_or_else(|e| unreachable!("addr: {e}"));
+        let app = axum::Router::new().nest("/hcmRestApi/resources/latest", app);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Box::leak(Box::new(Self {
+            name: name.to_string(),
+            base_url: Ok(format!("http://{addr}")),
+            api_version: "latest".to_string(),
+            rest_framework_version: "9".to_string(),
+            auth: Ok(auth),
+            delegation: Ok(None),
+            client: client::build_client(
+                Arc::new(Limiters::default()),
+                Arc::new(CircuitBreaker::new(name, tuning().breaker)),
+            ),
+            pools: Pools::default(),
+        }))
+    }
+
+    /// Checks the settings every call needs: base URL, authentication and delegation.
+    ///
+    /// # Errors
+    /// Returns the first problem, naming the environment when there are several.
+    pub fn validate(&self) -> Result<()> {
+        let problem = if let Err(e) = &self.base_url {
+            anyhow!("Failed to load HCM_BASE_URL: {e}")
+        } else if let Err(e) = &self.auth {
+            anyhow!("Failed to configure HCM authentication: {e}")
+        } else if let Err(e) = &self.delegation {
+            anyhow!("Failed to configure HCM delegation: {e}")
+        } else {
+            return Ok(());
+        };
+        if self.name == DEFAULT_ENVIRONMENT {
+            return Err(problem);
+        }
+        Err(anyhow!("HCM environment {}: {problem}", self.name))
+    }
+
+    /// Whether calls are made with the caller's delegated privileges.
+    pub const fn delegates(&self) -> bool {
+        matches!(self.delegation, Ok(Some(_)))
+    }
+
+    /// Every configured environment.
+    ///
+    /// # Errors
+    /// Fails if `HCM_ENVIRONMENTS` or `HCM_DEFAULT_ENVIRONMENT` is invalid.
+    pub fn all() -> Result<&'static [Self]> {
+        ENVIRONMENTS.as_ref().map(|environments| environments.all.as_slice()).map_err(|e| anyhow!("{e}"))
+    }
+
+    /// Whether this is `HCM_DEFAULT_ENVIRONMENT`, the environment of sessions that don't choose one.
+    pub fn is_default(&self) -> bool {
+        environments().is_ok_and(|environments| std::ptr::eq(environments.default(), self))
+    }
+
+    /// Runs `future` with `environment` as the current environment.
+    pub async fn scope<F: Future>(environment: &'static Self, future: F) -> F::Output {
+        CURRENT_ENVIRONMENT.scope(environment, future).await
+    }
+
+    /// The environment of the tool call running on this task, or the default
+    /// environment outside a tool call (e.g. for health checks).
+    ///
+    /// # Errors
+    /// * `MissingConfig` - If the environments are misconfigured
+    pub fn current() -> Result<&'static Self, HcmError> {
+        CURRENT_ENVIRONMENT
+            .try_with(|environment| *environment)
+            .or_else(|_| Ok(environments()?.default()))
+    }
+
+    /// Chooses the environment for a new session from its `initialize`
+    /// request, see the module docs.
+    ///
+    /// # Errors
+    /// * `InvalidParams` - If the session asks for an unknown environment
+    /// * `Forbidden` - If the caller's `MCP_ENVIRONMENT_CLAIM` doesn't allow it
+    pub fn select(parts: Option<&request::Parts>, caller: Option<&Caller>) -> Result<&'static Self, HcmError> {
+        environments()?.select(ENVIRONMENT_CLAIM.as_deref(), parts, caller)
+    }
+}
+
+fn environments() -> Result<&'static Environments, HcmError> {
+    ENVIRONMENTS.as_ref().map_err(|e| HcmError::MissingConfig(e.to_string()))
+}
+
+struct Environments {
+    all: Vec<HcmEnvironment>,
+    /// Index of `HCM_DEFAULT_ENVIRONMENT` in `all`
+    default: usize,
+}
+
+impl Environments {
+    fn load() -> Result<Self> {
+        let names = environment_names();
+        if names.is_empty() {
+            return Ok(Self {
+                all: vec![HcmEnvironment::new(DEFAULT_ENVIRONMENT, Settings { environment: None })],
+                default: 0,
+            });
+        }
+        for (index, name) in names.iter().enumerate() {
+            if !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+                bail!("HCM_ENVIRONMENTS names may only contain letters and digits, got {name}");
+            }
+            if names[..index].contains(name) {
+                bail!("HCM_ENVIRONMENTS lists {name} twice");
+            }
+        }
+        let default_name = load_env_var("HCM_DEFAULT_ENVIRONMENT").map_or_else(
+            |_| names.first().cloned().unwrap_or_default(),
+            |name| name.trim().to_ascii_lowercase(),
+        );
+        let Some(default) = names.iter().position(|name| *name == default_name) else {
+            bail!("HCM_DEFAULT_ENVIRONMENT must be one of {}, got {default_name}", names.join(", "));
+        };
+
+        let all = names
+            .iter()
+            .map(|name| HcmEnvironment::new(name, Settings { environment: Some(name) }))
+            .collect();
+        info!("HCM environments: {} (default {})", names.join(", "), names[default]);
+        Ok(Self { all, default })
+    }
+
+    /// Like [`HcmEnvironment::select`], with `claim` as `MCP_ENVIRONMENT_CLAIM`.
+    fn select(
+        &self,
+        claim: Option<&str>,
+        parts: Option<&request::Parts>,
+        caller: Option<&Caller>,
+    ) -> Result<&HcmEnvironment, HcmError> {
+        let allowed = claim.zip(caller).map(|(claim, caller)| claim_values(caller, claim));
+        let requested = parts
+            .and_then(|parts| from_path(parts).or_else(|| from_header(parts)))
+            .or_else(|| allowed.as_ref().and_then(|allowed| allowed.first().cloned()));
+
+        let environment = match requested {
+            Some(name) => self.named(&name).ok_or_else(|| {
+                HcmError::InvalidParams(format!(
+                    "Unknown HCM environment {name}, expected one of {}",
+                    self.names()
+                ))
+            })?,
+            None => self.default(),
+        };
+        if let Some(allowed) = &allowed
+            && !allowed.contains(&environment.name)
+        {
+            warn!(environment = %environment.name, "caller may not use this HCM environment");
+            return Err(HcmError::forbidden(format!(
+                "You may not use the HCM environment {}",
+                environment.name
+            )));
+        }
+        Ok(environment)
+    }
+
+    fn named(&self, name: &str) -> Option<&HcmEnvironment> {
+        self.all.iter().find(|environment| environment.name.eq_ignore_ascii_case(name))
+    }
+
+    fn default(&self) -> &HcmEnvironment {
+        &self.all[self.default]
+    }
+
+    fn names(&self) -> String {
+        self.all
+            .iter()
+            .map(|environment| environment.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The environment in a `/mcp/{environment}` endpoint path.
+fn from_path(parts: &request::Parts) -> Option<String> {
+    // `nest_service` strips the `/mcp` prefix; the original URI still has it
+    let path = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or_else(|| parts.uri.path(), |original| original.path());
+    let rest = path.strip_prefix("/mcp").unwrap_or(path).trim_matches('/');
+    (!rest.is_empty()).then(|| rest.to_ascii_lowercase())
+}
+
+fn from_header(parts: &request::Parts) -> Option<String> {
+    let value = parts.headers.get(ENVIRONMENT_HEADER)?.to_str().ok()?.trim();
+    (!value.is_empty()).then(|| value.to_ascii_lowercase())
+}
+
+/// The environments a caller's claim lists: a string (comma-separated) or an array of strings.
+fn claim_values(caller: &Caller, claim: &str) -> Vec<String> {
+    let values: Vec<&str> = match caller.claims.get(claim) {
+        Some(Value::String(value)) => value.split(',').collect(),
+        Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    values
+        .into_iter()
+        .map(|value| value.trim().to_ascii_lowercase())
+        .filter(|value| !value.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use serde_json::{Map, json};
+
+    /// `dev`, `test` and `prod`, with `test` as the default.
+    fn environments() -> Environments {
+        Environments {
+            all: ["dev", "test", "prod"]
+                .into_iter()
+                .map(|name| HcmEnvironment::new(name, Settings { environment: Some(name) }))
+                .collect(),
+            default: 1,
+        }
+    }
+
+    fn parts(uri: &str, original: Option<&str>, header: Option<&str>) -> request::Parts {
+        let mut request = Request::builder().uri(uri);
+        if let Some(original) = original {
+            let original = original.parse().unwrap_or_else(|e| unreachable!("uri: {e}"));
+            request = request.extension(OriginalUri(original));
+        }
+        if let Some(header) = header {
+            request = request.header(ENVIRONMENT_HEADER, header);
+        }
+        request.body(()).unwrap_or_else(|e| unreachable!("request: {e}")).into_parts().0
+    }
+
+    fn caller(claims: &Value) -> Caller {
+        let claims: Map<String, Value> = claims.as_object().cloned().unwrap_or_default();
+        Caller {
+            subject: "alice".to_string(),
+            claims: Arc::new(claims),
+            token: Arc::from("token"),
+        }
+    }
+
+    fn selected(
+        environments: &Environments,
+        claim: Option<&str>,
+        parts: Option<&request::Parts>,
+        caller: Option<&Caller>,
+    ) -> Result<String, HcmError> {
+        environments.select(claim, parts, caller).map(|environment| environment.name.clone())
+    }
+
+    #[test]
+    fn path_wins_over_header_and_header_over_claim() -> Result<(), HcmError> {
+        let environments = environments();
+        let alice = caller(&json!({ "hcm_envs": ["dev", "test", "prod"] }));
+        let claim = Some("hcm_envs");
+
+        let both = parts("/prod", Some("/mcp/prod"), Some("dev"));
+        assert_eq!(selected(&environments, claim, Some(&both), Some(&alice))?, "prod");
+        let header = parts("/", Some("/mcp"), Some("PROD"));
+        assert_eq!(selected(&environments, claim, Some(&header), Some(&alice))?, "prod");
+        let neither = parts("/", Some("/mcp/"), None);
+        assert_eq!(selected(&environments, claim, Some(&neither), Some(&alice))?, "dev");
+        assert_eq!(selected(&environments, None, Some(&neither), Some(&alice))?, "test");
+        assert_eq!(selected(&environments, None, None, None)?, "test");
+        Ok(())
+    }
+
+    #[test]
+    fn claim_restricts_the_environments_as_a_string_or_an_array() -> Result<(), HcmError> {
+        let environments = environments();
+        let claim = Some("hcm_envs");
+        let prod = parts("/prod", Some("/mcp/prod"), None);
+
+        for claims in [json!({ "hcm_envs": "dev, Prod" }), json!({ "hcm_envs": ["dev", "prod"] })] {
+            assert_eq!(selected(&environments, claim, Some(&prod), Some(&caller(&claims)))?, "prod");
+        }
+        for claims in [json!({ "hcm_envs": "dev,test" }), json!({ "hcm_envs": ["dev"] })] {
+            let result = selected(&environments, claim, Some(&prod), Some(&caller(&claims)));
+            assert!(matches!(result, Err(HcmError::Forbidden(_))), "{result:?}");
+        }
+        // Without the claim setting, or without a caller, every environment is open
+        let dev_only = caller(&json!({ "hcm_envs": "dev" }));
+        assert_eq!(selected(&environments, None, Some(&prod), Some(&dev_only))?, "prod");
+        assert_eq!(selected(&environments, claim, Some(&prod), None)?, "prod");
+        Ok(())
+    }
+
+    #[test]
+    fn empty_or_missing_claim_allows_no_environment() {
+        let environments = environments();
+        for claims in [json!({ "hcm_envs": "" }), json!({ "hcm_envs": [] }), json!({})] {
+            let result = selected(&environments, Some("hcm_envs"), None, Some(&caller(&claims)));
+            assert!(matches!(result, Err(HcmError::Forbidden(_))), "{claims}: {result:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_environment_is_invalid() {
+        let environments = environments();
+        let staging = parts("/staging", Some("/mcp/staging"), None);
+        let result = selected(&environments, None, Some(&staging), None);
+        assert!(matches!(result, Err(HcmError::InvalidParams(_))), "{result:?}");
+    }
+
+    #[test]
+    fn path_is_read_from_the_original_uri_when_nested() {
+        assert_eq!(from_path(&parts("/dev", Some("/mcp/dev"), None)).as_deref(), Some("dev"));
+        assert_eq!(from_path(&parts("/mcp/Prod/", None, None)).as_deref(), Some("prod"));
+        assert_eq!(from_path(&parts("/", Some("/mcp"), None)), None);
+        assert_eq!(from_path(&parts("/mcp", None, None)), None);
+    }
+}
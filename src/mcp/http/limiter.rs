@@ -0,0 +1,348 @@
+//! Outbound rate limiting and concurrency caps toward Oracle HCM.
+//!
+//! Oracle Fusion throttles REST clients per user, so one runaway agent loop
+//! could get the integration user throttled for everyone. Each HCM resource
+//! (e.g. `publicWorkers`, `absences/action/loadProjectedBalance`) gets its own:
+//! - **Token bucket**: sustained requests per second, with a burst allowance
+//! - **In-flight cap**: concurrent requests
+//!
+//! Calls over the limit queue until `HCM_RATE_LIMIT_WAIT_MS` has passed and
+//! then fail with `HcmError::RateLimited`. [`LimiterMiddleware`] sits below the
+//! retry middleware, so every attempt sent to HCM is charged, including retries
+//! and the resend after a `401`. Each HCM environment has its own [`Limiters`],
+//! so one pod's traffic never delays another's.
+//!
+//! Limits are kept per resource rather than per tool: Fusion throttles the
+//! integration user by the requests it receives, whichever tool made them, so
+//! separate buckets for tools sharing a resource (e.g. `publicWorkers`, used by
+//! the person lookup and by authorization checks) would together exceed the limit.
+//!
+//! # Configuration
+//! - `HCM_RATE_LIMIT_RPS` (optional): Requests per second per resource, defaults to 10; at least 0.001
+//! - `HCM_RATE_LIMIT_BURST` (optional): Requests allowed at once before throttling, defaults to 20
+//! - `HCM_MAX_IN_FLIGHT` (optional): Concurrent requests per resource, defaults to 8
+//! - `HCM_RATE_LIMIT_WAIT_MS` (optional): How long a call may queue, defaults to 5000
+//! - `HCM_RATE_LIMITS` (optional): Per-resource overrides as `resource=rps:burst:in_flight`,
+//!   comma-separated, e.g. "absences/action/loadProjectedBalance=1:2:2,publicWorkers=20:40:16"
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Error, Middleware, Next, Result as MiddlewareResult};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::Instant,
+};
+use tracing::{debug, warn};
+
+use crate::{
+    config::{RateLimit, tuning},
+    mcp::error::HcmError,
+    metrics,
+};
+
+/// One environment's limiters, created on first use of each resource.
+#[derive(Default)]
+pub struct Limiters {
+    by_resource: Mutex<HashMap<String, Arc<ResourceLimiter>>>,
+}
+
+/// Request extension naming the HCM resource whose limits the request is charged to.
+#[derive(Debug, Clone)]
+pub struct LimitedResource(pub String);
+
+/// Charges each attempt of a request carrying [`LimitedResource`] to that
+/// resource's limits, holding its in-flight slot until the response is dropped.
+pub struct LimiterMiddleware {
+    limiters: Arc<Limiters>,
+}
+
+impl LimiterMiddleware {
+    pub const fn new(limiters: Arc<Limiters>) -> Self {
+        Self { limiters }
+    }
+}
+
+#[async_trait]
+impl Middleware for LimiterMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> MiddlewareResult<Response> {
+        let Some(LimitedResource(resource)) = extensions.get::<LimitedResource>().cloned() else {
+            return next.run(req, extensions).await;
+        };
+        let permit = self
+            .limiters
+            .acquire(&resource)
+            .await
+            .map_err(|e| Error::Middleware(e.into()))?;
+        let mut response = next.run(req, extensions).await?;
+        // The slot is freed once the caller has read and dropped the response
+        response.extensions_mut().insert(Arc::new(permit));
+        Ok(response)
+    }
+}
+
+/// Token bucket whose tokens may go negative: each caller reserves a token
+/// up front and waits until it has been refilled, so waiters are served in order.
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Rate limiter and in-flight cap for one HCM resource.
+struct ResourceLimiter {
+    limits: RateLimit,
+    bucket: Mutex<TokenBucket>,
+    in_flight: Arc<Semaphore>,
+}
+
+impl ResourceLimiter {
+    fn new(limits: RateLimit) -> Self {
+        Self {
+            limits,
+            bucket: Mutex::new(TokenBucket {
+                tokens: limits.burst,
+                last_refill: Instant::now(),
+            }),
+            in_flight: Arc::new(Semaphore::new(limits.in_flight)),
+        }
+    }
+
+    /// Reserves a token and returns how long to wait before using it,
+    /// or `None` (without reserving) if that wait would pass `deadline`.
+    fn reserve(&self, deadline: Instant) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let refilled = now.duration_since(bucket.last_refill).as_secs_f64() * self.limits.rps;
+        bucket.tokens = (bucket.tokens + refilled).min(self.limits.burst);
+        bucket.last_refill = now;
+
+        let shortfall = 1.0 - bucket.tokens;
+        let wait = if shortfall > 0.0 {
+            Duration::try_from_secs_f64(shortfall / self.limits.rps).unwrap_or(Duration::MAX)
+        } else {
+            Duration::ZERO
+        };
+        if now.checked_add(wait).is_none_or(|ready| ready > deadline) {
+            return None;
+        }
+        bucket.tokens -= 1.0;
+        drop(bucket);
+        Some(wait)
+    }
+
+    /// Returns a reserved token that was never used, e.g. because no in-flight slot freed up.
+    fn refund(&self) {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        bucket.tokens = (bucket.tokens + 1.0).min(self.limits.burst);
+    }
+}
+
+impl Limiters {
+    /// Waits for `resource`'s rate limit and an in-flight slot, holding the slot
+    /// until the returned permit is dropped.
+    ///
+    /// # Arguments
+    /// * `path` - Request path relative to the API version, e.g. "/publicWorkers?q=..."
+    ///
+    /// # Errors
+    /// * `RateLimited` - If the call can't be admitted within `HCM_RATE_LIMIT_WAIT_MS`
+    pub async fn acquire(&self, path: &str) -> Result<OwnedSemaphorePermit, HcmError> {
+        let resource = resource_of(path);
+        let limiter = self.limiter_for(resource);
+        let deadline = Instant::now() + tuning().rate_limits.wait;
+
+        let Some(wait) = limiter.reserve(deadline) else {
+            return Err(rate_limited(resource, "request rate", limiter.limits.rps));
+        };
+        if !wait.is_zero() {
+            debug!("HCM rate limit for {resource}: queued for {}ms", wait.as_millis());
+            tokio::time::sleep(wait).await;
+        }
+
+        if let Ok(Ok(permit)) =
+            tokio::time::timeout_at(deadline, Arc::clone(&limiter.in_flight).acquire_owned()).await
+        {
+            return Ok(permit);
+        }
+        // The request is never sent, so its token shouldn't count against the rate
+        limiter.refund();
+        Err(rate_limited(resource, "concurrent requests", 1.0))
+    }
+
+    fn limiter_for(&self, resource: &str) -> Arc<ResourceLimiter> {
+        let mut limiters = self.by_resource.lock().unwrap_or_else(PoisonError::into_inner);
+        let limiter = limiters.entry(resource.to_string()).or_insert_with(|| {
+            let limits = &tuning().rate_limits;
+            let limits = limits.overrides.get(resource).copied().unwrap_or(limits.default);
+            Arc::new(ResourceLimiter::new(limits))
+        });
+        let limiter = Arc::clone(limiter);
+        drop(limiters);
+        limiter
+    }
+}
+
+/// The resource a path addresses: everything before the query string, without the leading slash.
+pub fn resource_of(path: &str) -> &str {
+    path.split('?').next().unwrap_or(path).trim_matches('/')
+}
+
+/// Builds the error for a call that couldn't be admitted, suggesting when to retry.
+fn rate_limited(resource: &str, limit: &str, per_second: f64) -> HcmError {
+    warn!("HCM {limit} limit for {resource} exceeded, rejecting call");
+    metrics::record_rejection("rate_limited");
+    // Roughly when capacity frees up again; never less than a second
+    let retry_after_secs = Duration::try_from_secs_f64(1.0 / per_second)
+        .map_or(u64::MAX, |interval| interval.as_secs())
+        .max(1);
+    HcmError::rate_limited(
+        format!("Too many HCM {resource} calls ({limit} limit), retry after {retry_after_secs} seconds"),
+        Some(retry_after_secs),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::http::retry::RetryMiddleware;
+    use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+
+    fn limiter(rps: f64, burst: f64) -> ResourceLimiter {
+        ResourceLimiter::new(RateLimit { rps, burst, in_flight: 1 })
+    }
+
+    /// Answers every request with `status` without contacting a server.
+    struct Reply(u16);
+
+    #[async_trait]
+    impl Middleware for Reply {
+        async fn handle(&self, _: Request, _: &mut Extensions, _: Next<'_>) -> MiddlewareResult<Response> {
+            let response = http::Response::builder()
+                .status(self.0)
+                .body("")
+                .map_err(|e| Error::Middleware(e.into()))?;
+            Ok(Response::from(response))
+        }
+    }
+
+    /// A client whose `publicWorkers` requests are limited to `burst` tokens and
+    /// `in_flight` slots, with next to no refill, all answered with `status`.
+    fn client(burst: f64, in_flight: usize, status: u16) -> ClientWithMiddleware {
+        let limiters = Limiters::default();
+        limiters.by_resource.lock().unwrap_or_else(PoisonError::into_inner).insert(
+            "publicWorkers".to_string(),
+            Arc::new(ResourceLimiter::new(RateLimit { rps: 0.001, burst, in_flight })),
+        );
+        ClientBuilder::new(reqwest::Client::new())
+            .with(RetryMiddleware::default())
+            .with(LimiterMiddleware::new(Arc::new(limiters)))
+            .with(Reply(status))
+            .build()
+    }
+
+    async fn get(client: &ClientWithMiddleware) -> Result<Response, HcmError> {
+        Ok(client
+            .get("http://hcm.invalid/publicWorkers")
+            .with_extension(LimitedResource("publicWorkers".to_string()))
+            .send()
+            .await?)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn every_retry_is_charged() {
+        let client = client(f64::from(tuning().retry.max_retries + 1), 8, 503);
+        let first = get(&client).await;
+        assert!(first.is_ok_and(|response| response.status() == 503));
+        // The first call's retries used up the whole burst
+        let second = get(&client).await;
+        assert!(matches!(second, Err(HcmError::RateLimited(_))), "{second:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn in_flight_slot_is_held_until_the_response_is_dropped() {
+        let client = client(10.0, 1, 200);
+        let Ok(first) = get(&client).await else {
+            unreachable!("the first call is admitted")
+        };
+        let second = get(&client).await;
+        assert!(matches!(second, Err(HcmError::RateLimited(_))), "{second:?}");
+        drop(first);
+        assert!(get(&client).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_without_a_resource_are_not_limited() {
+        let client = client(1.0, 1, 200);
+        for _ in 0..3 {
+            assert!(client.get("http://hcm.invalid/publicWorkers").send().await.is_ok());
+        }
+    }
+
+    #[test]
+    fn burst_is_admitted_without_waiting() {
+        let limiter = limiter(1.0, 3.0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        for _ in 0..3 {
+            assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+        }
+        let wait = limiter.reserve(deadline);
+        assert!(wait.is_some_and(|wait| wait > Duration::from_millis(900)), "{wait:?}");
+    }
+
+    #[test]
+    fn call_past_the_deadline_is_rejected_without_reserving() {
+        let limiter = limiter(0.01, 1.0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+        assert_eq!(limiter.reserve(deadline), None);
+        assert_eq!(limiter.reserve(deadline), None);
+    }
+
+    #[test]
+    fn tiny_rate_does_not_overflow() {
+        let limiter = limiter(1e-300, 1.0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+        assert_eq!(limiter.reserve(deadline), None);
+
+        let HcmError::RateLimited(detail) = rate_limited("absences", "request rate", 1e-300) else {
+            unreachable!("rate_limited builds a RateLimited error")
+        };
+        assert_eq!(detail.retry_after_secs, Some(u64::MAX));
+    }
+
+    #[test]
+    fn refunded_token_can_be_reserved_again() {
+        let limiter = limiter(0.01, 1.0);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+        limiter.refund();
+        assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn refund_never_exceeds_the_burst() {
+        let limiter = limiter(0.01, 1.0);
+        limiter.refund();
+        limiter.refund();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        assert_eq!(limiter.reserve(deadline), Some(Duration::ZERO));
+        assert_eq!(limiter.reserve(deadline), None);
+    }
+
+    #[test]
+    fn resource_drops_the_query_string_and_slashes() {
+        assert_eq!(resource_of("/publicWorkers?q=PersonId%20%3D%201"), "publicWorkers");
+        assert_eq!(resource_of("/absences/action/loadProjectedBalance"), "absences/action/loadProjectedBalance");
+    }
+}
@@ -3,13 +3,33 @@
 //! This module handles all HTTP communication with Oracle HCM's REST API,
 //! including authentication, request construction, and response handling.
 
+mod adf_error;
+mod auth;
+mod breaker;
+mod cache;
+mod coalesce;
+mod bulkhead;
 mod client;
+mod delegation;
+mod environment;
+mod limiter;
+mod paging;
+mod propagation;
+mod query;
+mod retry;
+mod secret;
 
 // Re-export client's public API
+pub use bulkhead::Bulkhead;
+pub use cache::invalidate as invalidate_cache;
+pub use environment::HcmEnvironment;
+pub use paging::{child_items, hcm_collection, PageOptions};
+pub use query::{Filter, HcmQuery};
+pub use secret::spawn_watcher as watch_secrets;
 pub use client::{
     hcm_api_call,
-    HCM_BASE_URL,
-    HCM_PASSWORD,
+    CallOptions,
+    load_env_var_or,
 };
 
 // Re-export common types used in our public API
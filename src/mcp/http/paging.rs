@@ -0,0 +1,299 @@
+//! Automatic pagination for Oracle HCM collection resources.
+//!
+//! Oracle ADF REST collections return one page at a time:
+//! ```json
+//! { "items": [...], "count": 25, "hasMore": true, "limit": 25, "offset": 0 }
+//! ```
+//! [`HcmPages`] walks a collection page by page, following `hasMore` and
+//! advancing `offset`, until the collection ends or the item cap is reached.
+//! [`hcm_collection`] collects everything into a single [`Collection`].
+//!
+//! # Configuration
+//! - `HCM_PAGE_SIZE` (optional): Items requested per page, defaults to 100 (HCM allows up to 500)
+//! - `HCM_MAX_ITEMS` (optional): Maximum items collected per call, defaults to 2000
+
+use reqwest::Method;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::{config::tuning, mcp::error::HcmError};
+use super::{
+    client::{hcm_api_call, CallOptions},
+    query::HcmQuery,
+};
+
+/// Page size and item cap for walking a collection.
+#[derive(Debug, Clone, Copy)]
+pub struct PageOptions {
+    /// Items requested per page (`limit`)
+    pub page_size: usize,
+    /// Stop after this many items, even if HCM reports more
+    pub max_items: usize,
+}
+
+impl Default for PageOptions {
+    /// Uses `HCM_PAGE_SIZE` and `HCM_MAX_ITEMS`.
+    fn default() -> Self {
+        Self {
+            page_size: tuning().page_size,
+            max_items: tuning().max_items,
+        }
+    }
+}
+
+impl PageOptions {
+    /// Fetches at most `max_items` items, in a single page when possible.
+    pub fn first(max_items: usize) -> Self {
+        Self {
+            page_size: max_items.clamp(1, 500),
+            max_items,
+        }
+    }
+}
+
+/// Items collected from a paged resource.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub items: Vec<Value>,
+    /// `true` if HCM had more items than `max_items` allowed
+    pub truncated: bool,
+}
+
+/// Async iterator over the pages of an HCM collection.
+///
+/// # Example
+/// ```no_run
+/// let query = HcmQuery::legacy("/planBalances").only_data();
+/// let mut pages = HcmPages::new(query, PageOptions::default());
+/// while let Some(page) = pages.next_page().await {
+///     for item in page? { /* ... */ }
+/// }
+/// ```
+pub struct HcmPages {
+    query: HcmQuery,
+    options: PageOptions,
+    offset: usize,
+    fetched: usize,
+    has_more: bool,
+}
+
+impl HcmPages {
+    /// Starts paging `query`; its `limit` and `offset` are set for each page.
+    pub const fn new(query: HcmQuery, options: PageOptions) -> Self {
+        Self {
+            query,
+            options,
+            offset: 0,
+            fetched: 0,
+            has_more: true,
+        }
+    }
+
+    /// Fetches the next page, or returns `None` once the collection or the item cap is exhausted.
+    pub async fn next_page(&mut self) -> Option<Result<Vec<Value>, HcmError>> {
+        let remaining = self.options.max_items.saturating_sub(self.fetched);
+        if !self.has_more || remaining == 0 {
+            return None;
+        }
+
+        let path = self
+            .query
+            .clone()
+            .limit(self.options.page_size.min(remaining))
+            .offset(self.offset)
+            .build();
+        let options = CallOptions::for_query(&self.query);
+
+        let json = match hcm_api_call(&path, Method::GET, None, options).await {
+            Ok(json) => json,
+            Err(e) => {
+                self.has_more = false;
+                return Some(Err(e));
+            }
+        };
+
+        let mut items = match json {
+            Value::Object(mut page) => match page.remove("items") {
+                Some(Value::Array(items)) => {
+                    self.has_more = page.get("hasMore").and_then(Value::as_bool).unwrap_or(false);
+                    items
+                }
+                _ => Vec::new(),
+            },
+            _ => Vec::new(),
+        };
+        // An empty page can't advance the offset; treat it as the end to avoid looping forever
+        if items.is_empty() {
+            self.has_more = false;
+        }
+        items.truncate(remaining);
+
+        self.offset += items.len();
+        self.fetched += items.len();
+        debug!(
+            "HCM collection page: {} items (total {}, hasMore {})",
+            items.len(),
+            self.fetched,
+            self.has_more
+        );
+        Some(Ok(items))
+    }
+
+    /// Returns `true` if the item cap stopped paging while HCM still had more items.
+    pub const fn is_truncated(&self) -> bool {
+        self.has_more && self.fetched >= self.options.max_items
+    }
+
+    /// Drains all remaining pages into a [`Collection`].
+    ///
+    /// # Errors
+    /// Returns the first error from any page request.
+    pub async fn collect_items(mut self) -> Result<Collection, HcmError> {
+        let mut items = Vec::new();
+        while let Some(page) = self.next_page().await {
+            items.extend(page?);
+        }
+
+        let truncated = self.is_truncated();
+        if truncated {
+            warn!(
+                "HCM collection {} truncated at {} items (HCM_MAX_ITEMS)",
+                self.query.resource(), self.options.max_items
+            );
+        }
+        Ok(Collection { items, truncated })
+    }
+}
+
+/// Fetches every item of an HCM collection, following `hasMore` up to the item cap.
+///
+/// # Arguments
+/// * `query` - Collection query, without `limit`/`offset`
+/// * `options` - Page size and item cap
+///
+/// # Errors
+/// Returns the first error from any page request.
+pub async fn hcm_collection(query: HcmQuery, options: PageOptions) -> Result<Collection, HcmError> {
+    HcmPages::new(query, options)
+        .collect_items()
+        .await
+}
+
+/// Iterates an expanded child collection, which HCM renders either as an array or as `{"items": [...]}`.
+pub fn child_items(value: &Value) -> impl Iterator<Item = &Value> {
+    value
+        .as_array()
+        .or_else(|| value["items"].as_array())
+        .into_iter()
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::http::HcmEnvironment;
+    use axum::{Json, Router, extract::{Query, State}, http::StatusCode, routing::get};
+    use serde_json::json;
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
+    /// A `/planBalances` stand-in with `total` items, recording each page's `offset` and `limit`.
+    #[derive(Default)]
+    struct Plans {
+        total: usize,
+        /// Rejects the request for this offset
+        fail_at: Option<usize>,
+        /// Reports `hasMore` even past the end
+        endless: bool,
+        pages: Mutex<Vec<(usize, usize)>>,
+    }
+
+    impl Plans {
+        fn pages(&self) -> Vec<(usize, usize)> {
+            self.pages.lock().unwrap_or_else(PoisonError::into_inner).clone()
+        }
+    }
+
+    async fn plan_balances(
+        State(plans): State<Arc<Plans>>,
+        Query(params): Query<HashMap<String, String>>,
+    ) -> (StatusCode, Json<Value>) {
+        let number = |name: &str| params.get(name).and_then(|value| value.parse().ok()).unwrap_or(0);
+        let (offset, limit): (usize, usize) = (number("offset"), number("limit"));
+        plans.pages.lock().unwrap_or_else(PoisonError::into_inner).push((offset, limit));
+        if plans.fail_at == Some(offset) {
+            return (StatusCode::BAD_REQUEST, Json(json!({ "title": "Bad Request" })));
+        }
+        let end = plans.total.min(offset + limit);
+        let items: Vec<Value> = (offset..end).map(|id| json!({ "id": id })).collect();
+        let has_more = plans.endless || end < plans.total;
+        (StatusCode::OK, Json(json!({ "items": items, "count": items.len(), "hasMore": has_more })))
+    }
+
+    async fn collect(name: &str, plans: &Arc<Plans>, options: PageOptions) -> Result<Collection, HcmError> {
+        let app = Router::new().route("/planBalances", get(plan_balances)).with_state(plans.clone());
+        let environment = HcmEnvironment::stand_in(name, app).await;
+        let query = HcmQuery::legacy("/planBalances").only_data();
+        HcmEnvironment::scope(environment, hcm_collection(query, options)).await
+    }
+
+    fn ids(collection: &Collection) -> Vec<u64> {
+        collection.items.iter().filter_map(|item| item["id"].as_u64()).collect()
+    }
+
+    #[tokio::test]
+    async fn follows_has_more_across_pages() -> Result<(), HcmError> {
+        let plans = Arc::new(Plans { total: 5, ..Plans::default() });
+
+        let collection = collect("pagingall", &plans, PageOptions { page_size: 2, max_items: 100 }).await?;
+        assert_eq!(ids(&collection), [0, 1, 2, 3, 4]);
+        assert!(!collection.truncated);
+        assert_eq!(plans.pages(), [(0, 2), (2, 2), (4, 2)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn item_cap_stops_paging_and_marks_the_collection_truncated() -> Result<(), HcmError> {
+        let plans = Arc::new(Plans { total: 5, ..Plans::default() });
+
+        let collection = collect("pagingcap", &plans, PageOptions { page_size: 2, max_items: 3 }).await?;
+        assert_eq!(ids(&collection), [0, 1, 2]);
+        assert!(collection.truncated);
+        // The last page only asks for what the cap leaves
+        assert_eq!(plans.pages(), [(0, 2), (2, 1)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collection_that_ends_exactly_at_the_cap_is_not_truncated() -> Result<(), HcmError> {
+        let plans = Arc::new(Plans { total: 4, ..Plans::default() });
+
+        let collection = collect("pagingexact", &plans, PageOptions { page_size: 2, max_items: 4 }).await?;
+        assert_eq!(collection.items.len(), 4);
+        assert!(!collection.truncated);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn empty_page_ends_paging_despite_has_more() -> Result<(), HcmError> {
+        let plans = Arc::new(Plans { total: 3, endless: true, ..Plans::default() });
+
+        let collection = collect("pagingempty", &plans, PageOptions { page_size: 2, max_items: 100 }).await?;
+        assert_eq!(ids(&collection), [0, 1, 2]);
+        assert!(!collection.truncated);
+        assert_eq!(plans.pages(), [(0, 2), (2, 2), (3, 2)]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn error_on_a_later_page_fails_the_collection() {
+        let plans = Arc::new(Plans { total: 5, fail_at: Some(2), ..Plans::default() });
+
+        let result = collect("pagingerror", &plans, PageOptions { page_size: 2, max_items: 100 }).await;
+        assert!(matches!(result, Err(HcmError::Validation(_))), "{result:?}");
+        // Paging stops at the failed page
+        assert_eq!(plans.pages(), [(0, 2), (2, 2)]);
+    }
+}
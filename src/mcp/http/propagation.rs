@@ -0,0 +1,34 @@
+//! W3C Trace Context propagation to Oracle HCM.
+//!
+//! [`TraceContextMiddleware`] runs inside each `hcm-api-request` span and adds
+//! its `traceparent` (and `tracestate`) headers to the outgoing request, so
+//! Fusion-side traces join the agent's trace. Without OTLP trace export
+//! (see `telemetry`) there is no trace context and no headers are added.
+
+use async_trait::async_trait;
+use http::Extensions;
+use opentelemetry::global;
+use opentelemetry_http::HeaderInjector;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Injects the current span's trace context into outgoing request headers.
+pub struct TraceContextMiddleware;
+
+#[async_trait]
+impl Middleware for TraceContextMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let context = Span::current().context();
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut HeaderInjector(req.headers_mut()));
+        });
+        next.run(req, extensions).await
+    }
+}
@@ -0,0 +1,449 @@
+//! Typed query builder for Oracle ADF REST resource URLs.
+//!
+//! Builds the query string for the parameters HCM resources understand:
+//! - `q`: Equality filters (see [`Filter`])
+//! - `finder`: Named finders with bind variables
+//! - `fields` / `expand`: Attribute selection and child expansion
+//! - `orderBy`, `limit`, `offset`, `onlyData`
+//!
+//! Attribute, finder, field and child names are `&'static str`, so they can
+//! only come from code. User-supplied values are escaped for the ADF grammar
+//! and every parameter is percent-encoded, so a value can never change the
+//! shape of the query.
+//!
+//! # Filter syntax
+//! HCM understands two `q` grammars, selected by the REST framework version:
+//! - **Expression** (framework version 2+, [`HcmQuery::new`]):
+//!   `assignments.WorkerNumber = 'E012345' and AssignmentStatusType = 'ACTIVE'`
+//! - **Simple** (framework version 1, [`HcmQuery::legacy`]):
+//!   `personId=300000578701661;planDisplayStatusFlag=true`
+//!
+//! # Example
+//! ```no_run
+//! let path = HcmQuery::new("/publicWorkers")
+//!     .filter(Filter::new().eq("assignments.WorkerNumber", "E012345"))
+//!     .fields(&["PersonId"])
+//!     .only_data()
+//!     .build();
+//! // /publicWorkers?q=assignments.WorkerNumber%20%3D%20%27E012345%27&fields=PersonId&onlyData=true
+//! ```
+
+use std::fmt::Write;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+
+/// Characters left unencoded in query parameter values (RFC 3986 unreserved).
+const QUERY_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Which `q` grammar a resource expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    /// Framework version 2+: `attr = 'value' and ...`
+    Expression,
+    /// Framework version 1: `attr=value;...`
+    Simple,
+}
+
+/// A value compared against an attribute in a filter or finder.
+#[derive(Debug, Clone)]
+pub enum QueryValue {
+    Text(String),
+    Bool(bool),
+}
+
+impl From<&str> for QueryValue {
+    fn from(value: &str) -> Self {
+        Self::Text(value.to_string())
+    }
+}
+
+impl From<String> for QueryValue {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<bool> for QueryValue {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl QueryValue {
+    /// Renders the value as a quoted ADF expression literal: `'O\'Brien'`.
+    fn to_expression_literal(&self) -> String {
+        match self {
+            Self::Text(text) => format!("'{}'", escape(text, '\'')),
+            Self::Bool(flag) => flag.to_string(),
+        }
+    }
+
+    /// Renders the value for simple `q` and finder syntax, where `;`, `,` and
+    /// `=` are separators. Plain values stay bare; anything else is double-quoted.
+    fn to_simple_literal(&self) -> String {
+        match self {
+            Self::Text(text) if is_bare(text) => text.clone(),
+            Self::Text(text) => format!("\"{}\"", escape(text, '"')),
+            Self::Bool(flag) => flag.to_string(),
+        }
+    }
+}
+
+/// Escapes backslashes and the quote character with a backslash, and drops
+/// control characters, which have no meaning in an ADF literal.
+fn escape(text: &str, quote: char) -> String {
+    text.chars()
+        .filter(|c| !c.is_control())
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            if c == '\\' || c == quote {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+}
+
+/// Returns `true` if `text` can appear unquoted in simple syntax.
+fn is_bare(text: &str) -> bool {
+    !text.is_empty()
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// A single `attribute = value` condition.
+#[derive(Debug, Clone)]
+struct Condition {
+    attribute: &'static str,
+    value: QueryValue,
+}
+
+/// A conjunction of conditions for the `q` parameter.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    conditions: Vec<Condition>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `attribute = value`, combined with the other conditions by AND.
+    #[must_use]
+    pub fn eq(mut self, attribute: &'static str, value: impl Into<QueryValue>) -> Self {
+        self.conditions.push(Condition {
+            attribute,
+            value: value.into(),
+        });
+        self
+    }
+
+    fn render(&self, syntax: Syntax) -> String {
+        let rendered = self.conditions.iter().map(|c| match syntax {
+            Syntax::Expression => format!("{} = {}", c.attribute, c.value.to_expression_literal()),
+            Syntax::Simple => format!("{}={}", c.attribute, c.value.to_simple_literal()),
+        });
+        let separator = match syntax {
+            Syntax::Expression => " and ",
+            Syntax::Simple => ";",
+        };
+        rendered.collect::<Vec<_>>().join(separator)
+    }
+}
+
+/// Builder for an HCM resource path and its query string.
+#[derive(Debug, Clone)]
+pub struct HcmQuery {
+    resource: &'static str,
+    syntax: Syntax,
+    filter: Option<Filter>,
+    finder: Option<(&'static str, Vec<(&'static str, QueryValue)>)>,
+    fields: Vec<&'static str>,
+    expand: Vec<&'static str>,
+    order_by: Vec<&'static str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    only_data: bool,
+}
+
+impl HcmQuery {
+    /// Starts a query on `resource` (e.g. `"/publicWorkers"`) for framework version 2+,
+    /// sent with the `REST-Framework-Version` header.
+    pub const fn new(resource: &'static str) -> Self {
+        Self::with_syntax(resource, Syntax::Expression)
+    }
+
+    /// Starts a query on a resource that only understands framework version 1
+    /// (simple `q` syntax, no `REST-Framework-Version` header).
+    pub const fn legacy(resource: &'static str) -> Self {
+        Self::with_syntax(resource, Syntax::Simple)
+    }
+
+    const fn with_syntax(resource: &'static str, syntax: Syntax) -> Self {
+        Self {
+            resource,
+            syntax,
+            filter: None,
+            finder: None,
+            fields: Vec::new(),
+            expand: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+            only_data: false,
+        }
+    }
+
+    /// Whether requests for this query should carry the `REST-Framework-Version` header.
+    pub const fn uses_framework_version(&self) -> bool {
+        matches!(self.syntax, Syntax::Expression)
+    }
+
+    /// The resource path without the query string (e.g. `"/publicWorkers"`).
+    pub const fn resource(&self) -> &'static str {
+        self.resource
+    }
+
+    /// Sets the `q` filter.
+    #[must_use]
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets `finder=name;var=value,...`.
+    #[must_use]
+    pub fn finder<V: Into<QueryValue>>(
+        mut self,
+        name: &'static str,
+        variables: impl IntoIterator<Item = (&'static str, V)>,
+    ) -> Self {
+        let variables = variables
+            .into_iter()
+            .map(|(variable, value)| (variable, value.into()))
+            .collect();
+        self.finder = Some((name, variables));
+        self
+    }
+
+    /// Restricts the returned attributes (`fields=a,b`).
+    #[must_use]
+    pub fn fields(mut self, fields: &[&'static str]) -> Self {
+        self.fields.extend_from_slice(fields);
+        self
+    }
+
+    /// Expands child resources inline (`expand=assignments.managers`).
+    #[must_use]
+    pub fn expand(mut self, children: &[&'static str]) -> Self {
+        self.expand.extend_from_slice(children);
+        self
+    }
+
+    /// Adds an ascending sort key (`orderBy=attr:asc`), which keeps
+    /// `offset` paging stable across pages.
+    #[must_use]
+    pub fn order_by(mut self, attribute: &'static str) -> Self {
+        self.order_by.push(attribute);
+        self
+    }
+
+    /// Sets the page size.
+    #[must_use]
+    pub const fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets the index of the first item returned.
+    #[must_use]
+    pub const fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Omits links and metadata from the response (`onlyData=true`).
+    #[must_use]
+    pub const fn only_data(mut self) -> Self {
+        self.only_data = true;
+        self
+    }
+
+    /// Renders the resource path with its percent-encoded query string.
+    pub fn build(&self) -> String {
+        let mut params: Vec<(&str, String)> = Vec::new();
+
+        if let Some(filter) = &self.filter {
+            params.push(("q", filter.render(self.syntax)));
+        }
+        if let Some((name, variables)) = &self.finder {
+            let mut finder = (*name).to_string();
+            if !variables.is_empty() {
+                let bindings = variables
+                    .iter()
+                    .map(|(variable, value)| format!("{variable}={}", value.to_simple_literal()))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(finder, ";{bindings}");
+            }
+            params.push(("finder", finder));
+        }
+        if !self.fields.is_empty() {
+            params.push(("fields", self.fields.join(",")));
+        }
+        if !self.expand.is_empty() {
+            params.push(("expand", self.expand.join(",")));
+        }
+        if !self.order_by.is_empty() {
+            let order_by = self
+                .order_by
+                .iter()
+                .map(|attribute| format!("{attribute}:asc"))
+                .collect::<Vec<_>>()
+                .join(",");
+            params.push(("orderBy", order_by));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            params.push(("offset", offset.to_string()));
+        }
+        if self.only_data {
+            params.push(("onlyData", "true".to_string()));
+        }
+
+        if params.is_empty() {
+            return self.resource.to_string();
+        }
+        let query = params
+            .iter()
+            .map(|(name, value)| format!("{name}={}", utf8_percent_encode(value, QUERY_VALUE)))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{}?{query}", self.resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use url::form_urlencoded;
+
+    /// Decodes the query string of a built path into its parameters.
+    fn params(path: &str) -> Vec<(String, String)> {
+        let (_, query) = path.split_once('?').unwrap_or((path, ""));
+        form_urlencoded::parse(query.as_bytes()).into_owned().collect()
+    }
+
+    fn param(path: &str, name: &str) -> Option<String> {
+        params(path).into_iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    #[test]
+    fn escape_backslashes_the_quote_and_backslash() {
+        assert_eq!(escape("O'Brien", '\''), "O\\'Brien");
+        assert_eq!(escape(r"a\b", '\''), r"a\\b");
+        assert_eq!(escape("say \"hi\"", '"'), "say \\\"hi\\\"");
+        // Only the active quote character is escaped
+        assert_eq!(escape("it's \"x\"", '"'), "it's \\\"x\\\"");
+    }
+
+    #[test]
+    fn escape_drops_control_characters() {
+        assert_eq!(escape("a\nb\tc\u{0}d", '\''), "abcd");
+    }
+
+    #[test]
+    fn bare_values_are_plain_ascii_words() {
+        assert!(is_bare("300000578701661"));
+        assert!(is_bare("E012345"));
+        assert!(is_bare("a-b_c.d"));
+        for value in ["", "a b", "a;b", "a,b", "a=b", "a'b", "a\"b", "Zoë", "名前"] {
+            assert!(!is_bare(value), "{value:?} must be quoted");
+        }
+    }
+
+    #[test]
+    fn expression_literals_are_single_quoted() {
+        assert_eq!(QueryValue::from("O'Brien").to_expression_literal(), "'O\\'Brien'");
+        assert_eq!(QueryValue::from("a; b, c=d").to_expression_literal(), "'a; b, c=d'");
+        assert_eq!(QueryValue::from(true).to_expression_literal(), "true");
+    }
+
+    #[test]
+    fn simple_literals_quote_separators() {
+        assert_eq!(QueryValue::from("300000578701661").to_simple_literal(), "300000578701661");
+        assert_eq!(QueryValue::from("a;b").to_simple_literal(), "\"a;b\"");
+        assert_eq!(QueryValue::from("a,b=c").to_simple_literal(), "\"a,b=c\"");
+        assert_eq!(QueryValue::from("say \"hi\"").to_simple_literal(), "\"say \\\"hi\\\"\"");
+        assert_eq!(QueryValue::from("Zoë Ng").to_simple_literal(), "\"Zoë Ng\"");
+        assert_eq!(QueryValue::from("").to_simple_literal(), "\"\"");
+    }
+
+    #[test]
+    fn injected_expression_stays_inside_the_literal() {
+        let path = HcmQuery::new("/publicWorkers")
+            .filter(Filter::new().eq("assignments.WorkerNumber", "x' or PersonId = '1"))
+            .build();
+        assert_eq!(
+            param(&path, "q").as_deref(),
+            Some("assignments.WorkerNumber = 'x\\' or PersonId = \\'1'")
+        );
+    }
+
+    #[test]
+    fn injected_separators_stay_inside_the_literal() {
+        let path = HcmQuery::legacy("/absences")
+            .filter(Filter::new().eq("personId", "1;personId=2").eq("flag", true))
+            .finder("findByWord", [("SearchTerm", "a,b=c;d")])
+            .build();
+        assert_eq!(param(&path, "q").as_deref(), Some("personId=\"1;personId=2\";flag=true"));
+        assert_eq!(param(&path, "finder").as_deref(), Some("findByWord;SearchTerm=\"a,b=c;d\""));
+    }
+
+    #[test]
+    fn build_percent_encodes_every_value() {
+        let path = HcmQuery::new("/publicWorkers")
+            .filter(Filter::new().eq("DisplayName", "Zoë & O'Brien+1 #2"))
+            .fields(&["PersonId", "DisplayName"])
+            .only_data()
+            .build();
+        let (resource, query) = path.split_once('?').unwrap_or((&path, ""));
+        assert_eq!(resource, "/publicWorkers");
+        assert!(
+            query.chars().all(|c| c.is_ascii_alphanumeric() || "-._~%=&".contains(c)),
+            "unencoded character in {query}"
+        );
+        assert_eq!(
+            params(&path),
+            [
+                ("q".to_string(), "DisplayName = 'Zoë & O\\'Brien+1 #2'".to_string()),
+                ("fields".to_string(), "PersonId,DisplayName".to_string()),
+                ("onlyData".to_string(), "true".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_matches_the_documented_example() {
+        let path = HcmQuery::new("/publicWorkers")
+            .filter(Filter::new().eq("assignments.WorkerNumber", "E012345"))
+            .fields(&["PersonId"])
+            .only_data()
+            .build();
+        assert_eq!(
+            path,
+            "/publicWorkers?q=assignments.WorkerNumber%20%3D%20%27E012345%27&fields=PersonId&onlyData=true"
+        );
+    }
+
+    #[test]
+    fn query_without_parameters_is_the_bare_resource() {
+        assert_eq!(HcmQuery::new("/absenceTypesLOV").build(), "/absenceTypesLOV");
+    }
+}
@@ -0,0 +1,305 @@
+//! Retry middleware for transient Oracle HCM failures.
+//!
+//! Fusion patching windows and load balancer restarts surface as 429/502/503/504
+//! responses and dropped connections. [`RetryMiddleware`] retries those with
+//! jittered exponential backoff ("full jitter"), honouring `Retry-After` when HCM
+//! sends it, within a per-call budget of attempts and total time. When HCM asks
+//! to wait longer than `HCM_RETRY_MAX_DELAY_MS` or the remaining budget, the
+//! response is returned at once, so the caller gets `RateLimited`/`Unavailable`
+//! with the `Retry-After` hint instead of a retry HCM didn't ask for.
+//!
+//! Only idempotent requests (`GET`, `HEAD`) are retried by default. A
+//! non-idempotent action opts in by carrying the [`RetryNonIdempotent`] request
+//! extension (see `CallOptions::retry_non_idempotent`).
+//!
+//! # Configuration
+//! - `HCM_RETRY_MAX_RETRIES` (optional): Retries after the first attempt, defaults to 3 (0 disables retries)
+//! - `HCM_RETRY_BASE_DELAY_MS` (optional): Backoff before the first retry, defaults to 200
+//! - `HCM_RETRY_MAX_DELAY_MS` (optional): Upper bound on a single delay; a longer `Retry-After` ends retrying, defaults to 10000
+//! - `HCM_RETRY_BUDGET_MS` (optional): Total time a call may spend retrying, defaults to 30000
+
+use std::time::Duration;
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Method, Request, Response, StatusCode};
+use reqwest_middleware::{Error, Middleware, Next, Result};
+use tokio::time::Instant;
+use tracing::warn;
+
+use crate::{
+    config::{Retry as RetryConfig, tuning},
+    metrics,
+};
+use super::adf_error;
+
+/// Request extension marking a non-idempotent request as safe to retry.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryNonIdempotent;
+
+/// Retries transient failures with jittered exponential backoff.
+pub struct RetryMiddleware {
+    config: RetryConfig,
+}
+
+impl Default for RetryMiddleware {
+    /// Retries as configured by `HCM_RETRY_*`.
+    fn default() -> Self {
+        Self { config: tuning().retry }
+    }
+}
+
+#[async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let config = &self.config;
+        let retryable = matches!(*req.method(), Method::GET | Method::HEAD)
+            || extensions.get::<RetryNonIdempotent>().is_some();
+        if !retryable || config.max_retries == 0 {
+            return next.run(req, extensions).await;
+        }
+
+        let started = Instant::now();
+        let mut retries = 0;
+        loop {
+            // Streaming bodies can't be replayed; send those exactly once
+            let Some(attempt) = req.try_clone() else {
+                return next.run(req, extensions).await;
+            };
+            let outcome = next.clone().run(attempt, extensions).await;
+
+            let Some(reason) = transient_failure(&outcome) else {
+                return outcome;
+            };
+            if retries >= config.max_retries {
+                return outcome;
+            }
+
+            let retry_after = outcome
+                .as_ref()
+                .ok()
+                .and_then(|response| adf_error::retry_after(response.headers()));
+            if let Some(after) = retry_after
+                && (after > config.max_delay || started.elapsed() + after > config.budget)
+            {
+                // Retrying sooner than asked would only hit a throttled pod again
+                warn!(
+                    "HCM request {} {} failed ({reason}); HCM asked to retry after {}s, beyond the retry limits",
+                    req.method(),
+                    req.url().path(),
+                    after.as_secs()
+                );
+                return outcome;
+            }
+            let delay = retry_after.unwrap_or_else(|| jittered_backoff(config, retries));
+            if started.elapsed() + delay > config.budget {
+                warn!(
+                    "HCM request {} {} failed ({reason}); retry budget of {}ms exhausted",
+                    req.method(),
+                    req.url().path(),
+                    config.budget.as_millis()
+                );
+                return outcome;
+            }
+
+            retries += 1;
+            metrics::record_retry(&outcome);
+            warn!(
+                "HCM request {} {} failed ({reason}); retry {retries}/{} in {}ms",
+                req.method(),
+                req.url().path(),
+                config.max_retries,
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Describes why an outcome is worth retrying, or `None` if it isn't.
+fn transient_failure(outcome: &Result<Response>) -> Option<String> {
+    match outcome {
+        Ok(response) => match response.status() {
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT => Some(format!("HTTP {}", response.status())),
+            _ => None,
+        },
+        Err(Error::Reqwest(e)) if e.is_connect() || e.is_timeout() || e.is_request() => {
+            Some(e.to_string())
+        }
+        Err(_) => None,
+    }
+}
+
+/// Full-jitter backoff: a random delay up to `base * 2^retry`, capped at `max_delay`.
+fn jittered_backoff(config: &RetryConfig, retry: u32) -> Duration {
+    let ceiling = config
+        .base_delay
+        .saturating_mul(2_u32.saturating_pow(retry))
+        .min(config.max_delay);
+    let millis = u64::try_from(ceiling.as_millis()).unwrap_or(u64::MAX);
+    Duration::from_millis(fastrand::u64(0..=millis))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::RETRY_AFTER;
+    use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex, PoisonError},
+    };
+
+    /// Answers each attempt with the next scripted status and `Retry-After`, then 200.
+    #[derive(Default)]
+    struct Script {
+        replies: Mutex<VecDeque<(u16, Option<&'static str>)>>,
+        attempts: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl Middleware for Script {
+        async fn handle(&self, _: Request, _: &mut Extensions, _: Next<'_>) -> Result<Response> {
+            *self.attempts.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+            let (status, retry_after) = self
+                .replies
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front()
+                .unwrap_or((200, None));
+            let mut response = http::Response::builder().status(status);
+            if let Some(retry_after) = retry_after {
+                response = response.header(RETRY_AFTER, retry_after);
+            }
+            let response = response.body("").map_err(|e| Error::Middleware(e.into()))?;
+            Ok(Response::from(response))
+        }
+    }
+
+    const CONFIG: RetryConfig = RetryConfig {
+        max_retries: 3,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(10),
+        budget: Duration::from_secs(30),
+    };
+
+    fn scripted(config: RetryConfig, replies: &[(u16, Option<&'static str>)]) -> (ClientWithMiddleware, Arc<Script>) {
+        let script = Arc::new(Script {
+            replies: Mutex::new(replies.iter().copied().collect()),
+            ..Script::default()
+        });
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(RetryMiddleware { config })
+            .with_arc(Arc::clone(&script) as Arc<dyn Middleware>)
+            .build();
+        (client, script)
+    }
+
+    fn attempts(script: &Script) -> u32 {
+        *script.attempts.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn get(client: &ClientWithMiddleware) -> StatusCode {
+        client
+            .get("http://hcm.invalid/publicWorkers")
+            .send()
+            .await
+            .map_or_else(|e| unreachable!("scripted responses never fail: {e}"), |response| response.status())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transient_statuses_are_retried() {
+        for status in [429, 502, 503, 504] {
+            let (client, script) = scripted(CONFIG, &[(status, None)]);
+            assert_eq!(get(&client).await, StatusCode::OK, "{status}");
+            assert_eq!(attempts(&script), 2, "{status}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn other_statuses_are_returned_at_once() {
+        for status in [400, 401, 404, 500] {
+            let (client, script) = scripted(CONFIG, &[(status, None)]);
+            assert_eq!(get(&client).await.as_u16(), status);
+            assert_eq!(attempts(&script), 1, "{status}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn post_is_retried_only_when_marked_safe() {
+        let (client, script) = scripted(CONFIG, &[(503, None)]);
+        let response = client.post("http://hcm.invalid/absences/action/loadProjectedBalance").send().await;
+        assert!(response.is_ok_and(|response| response.status() == StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(attempts(&script), 1);
+
+        let (client, script) = scripted(CONFIG, &[(503, None)]);
+        let response = client
+            .post("http://hcm.invalid/absences/action/loadProjectedBalance")
+            .with_extension(RetryNonIdempotent)
+            .send()
+            .await;
+        assert!(response.is_ok_and(|response| response.status() == StatusCode::OK));
+        assert_eq!(attempts(&script), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_stop_after_max_retries() {
+        let (client, script) = scripted(CONFIG, &[(503, None); 5]);
+        assert_eq!(get(&client).await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(attempts(&script), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_stop_when_the_budget_is_spent() {
+        let config = RetryConfig {
+            budget: Duration::from_millis(2500),
+            ..CONFIG
+        };
+        let (client, script) = scripted(config, &[(503, Some("1")); 5]);
+        assert_eq!(get(&client).await, StatusCode::SERVICE_UNAVAILABLE);
+        // Two one-second waits fit the budget, a third doesn't
+        assert_eq!(attempts(&script), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_is_waited_for() {
+        let (client, script) = scripted(CONFIG, &[(429, Some("2"))]);
+        let started = Instant::now();
+        assert_eq!(get(&client).await, StatusCode::OK);
+        assert_eq!(attempts(&script), 2);
+        assert!(started.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_beyond_the_max_delay_is_returned_to_the_caller() {
+        let (client, script) = scripted(CONFIG, &[(429, Some("60"))]);
+        let started = Instant::now();
+        let response = client.get("http://hcm.invalid/publicWorkers").send().await;
+        let Ok(response) = response else {
+            unreachable!("scripted responses never fail")
+        };
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(adf_error::retry_after(response.headers()), Some(Duration::from_mins(1)));
+        assert_eq!(attempts(&script), 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_after_beyond_the_budget_is_returned_to_the_caller() {
+        let config = RetryConfig {
+            max_delay: Duration::from_mins(1),
+            budget: Duration::from_secs(5),
+            ..CONFIG
+        };
+        let (client, script) = scripted(config, &[(503, Some("30"))]);
+        assert_eq!(get(&client).await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(attempts(&script), 1);
+    }
+}
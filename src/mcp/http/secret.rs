@@ -0,0 +1,339 @@
+//! Credentials for outbound HCM authentication, and their rotation.
+//!
+//! A secret setting such as `HCM_PASSWORD` can be set directly, or as the path
+//! of a file holding it in the matching `*_FILE` variable, e.g.
+//! `HCM_PASSWORD_FILE=/run/secrets/hcm_password` for a Docker or Kubernetes
+//! secret. Surrounding whitespace, such as a trailing newline, is trimmed from
+//! file contents. Per-environment variables work the same way
+//! (`HCM_ENV_PROD_PASSWORD_FILE`), see `environment`.
+//!
+//! Secrets read from files are watched: every `HCM_SECRET_RELOAD_SECS` their
+//! files are checked for changes, and new values are used from the next
+//! request (or the next token refresh for OAuth), so rotating the integration
+//! user's password or client secret needs no restart. If HCM answers `401`
+//! before the change is noticed, the provider re-reads its secrets at once
+//! and the request is retried once (see `client`).
+//!
+//! Other stores, such as a vault, plug in by implementing [`SecretSource`].
+//!
+//! # Configuration
+//! - `HCM_PASSWORD_FILE` / `HCM_OAUTH_CLIENT_SECRET_FILE` (optional): Files holding the
+//!   secrets, instead of the variables themselves
+//! - `HCM_SECRET_RELOAD_SECS` (optional): How often secret files are checked for changes,
+//!   defaults to 30; 0 disables watching
+
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+    time::SystemTime,
+};
+use anyhow::{Result, anyhow, bail};
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::config::tuning;
+use super::{client::load_env_var, environment::Settings};
+
+/// Secrets whose source can change, checked by [`spawn_watcher`].
+static WATCHED: LazyLock<Mutex<Vec<Secret>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Where a secret's value is read from.
+pub trait SecretSource: Send + Sync {
+    /// Describes the source for logs, never the value, e.g. "file `/run/secrets/hcm_password`".
+    fn describe(&self) -> String;
+
+    /// Reads the current value.
+    ///
+    /// # Errors
+    /// Fails if the source can't be read.
+    fn read(&self) -> Result<String>;
+
+    /// A marker that changes whenever the value may have, such as a file's
+    /// modification time; `None` for a value that never changes.
+    fn version(&self) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// A value set in the environment or the configuration file.
+struct Inline(String);
+
+impl SecretSource for Inline {
+    fn describe(&self) -> String {
+        "its variable".to_string()
+    }
+
+    fn read(&self) -> Result<String> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A file holding the secret, such as a mounted Docker or Kubernetes secret.
+pub struct SecretFile {
+    path: PathBuf,
+}
+
+impl SecretFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl SecretSource for SecretFile {
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn read(&self) -> Result<String> {
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| anyhow!("cannot read {}: {e}", self.path.display()))?;
+        let value = contents.trim();
+        if value.is_empty() {
+            bail!("{} is empty", self.path.display());
+        }
+        Ok(value.to_string())
+    }
+
+    fn version(&self) -> Option<SystemTime> {
+        // Follows symlinks, so Kubernetes' atomic `..data` swaps show up too
+        fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()
+    }
+}
+
+/// A secret and its current value. Clones share the value, so the watcher's
+/// reloads reach every provider using it.
+#[derive(Clone)]
+pub struct Secret(Arc<Inner>);
+
+struct Inner {
+    /// Variable the secret was configured with, for logs and errors
+    name: String,
+    source: Box<dyn SecretSource>,
+    current: Mutex<Current>,
+}
+
+struct Current {
+    value: String,
+    version: Option<SystemTime>,
+}
+
+impl Secret {
+    /// Reads a secret from `source`, watching it for changes if it can change.
+    ///
+    /// # Errors
+    /// Fails if the source can't be read.
+    pub fn new(name: &str, source: Box<dyn SecretSource>) -> Result<Self> {
+        let version = source.version();
+        let value = source.read().map_err(|e| anyhow!("{name}: {e}"))?;
+        let secret = Self(Arc::new(Inner {
+            name: name.to_string(),
+            source,
+            current: Mutex::new(Current { value, version }),
+        }));
+        if version.is_some() {
+            WATCHED.lock().unwrap_or_else(PoisonError::into_inner).push(secret.clone());
+        }
+        Ok(secret)
+    }
+
+    /// The secret `key` of an environment, from the file named by `{key}_FILE`
+    /// or the value of `key`, preferring the environment's own variables.
+    /// `None` if none of them is set.
+    ///
+    /// # Errors
+    /// Fails if a secret file can't be read.
+    pub fn optional(settings: Settings<'_>, key: &str) -> Result<Option<Self>> {
+        for name in settings.names(key) {
+            let file_name = format!("{name}_FILE");
+            if let Ok(path) = load_env_var(&file_name) {
+                return Self::new(&file_name, Box::new(SecretFile::new(path))).map(Some);
+            }
+            if let Ok(value) = load_env_var(&name) {
+                return Self::new(&name, Box::new(Inline(value))).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`Self::optional`], for a secret that must be set.
+    ///
+    /// # Errors
+    /// Fails if the secret isn't set or its file can't be read.
+    pub fn required(settings: Settings<'_>, key: &str) -> Result<Self> {
+        Self::optional(settings, key)?.ok_or_else(|| {
+            let mut names: Vec<String> = settings
+                .names(key)
+                .into_iter()
+                .flat_map(|name| {
+                    let file = format!("{name}_FILE");
+                    [name, file]
+                })
+                .collect();
+            let last = names.pop().unwrap_or_default();
+            anyhow!("{} or {last} must be set in the environment or the config file", names.join(", "))
+        })
+    }
+
+    /// A fixed secret that is never reloaded.
+    #[cfg(test)]
+    pub fn inline(name: &str, value: &str) -> Self {
+        Self(Arc::new(Inner {
+            name: name.to_string(),
+            source: Box::new(Inline(value.to_string())),
+            current: Mutex::new(Current { value: value.to_string(), version: None }),
+        }))
+    }
+
+    /// The current value.
+    pub fn value(&self) -> String {
+        self.0.current.lock().unwrap_or_else(PoisonError::into_inner).value.clone()
+    }
+
+    /// Re-reads the secret now, e.g. after HCM rejected it.
+    /// Returns `true` if the value changed.
+    pub fn reload(&self) -> bool {
+        self.refresh(self.0.source.version())
+    }
+
+    /// Re-reads the secret if its source changed since it was last read.
+    fn reload_if_changed(&self) -> bool {
+        let version = self.0.source.version();
+        let current = self.0.current.lock().unwrap_or_else(PoisonError::into_inner).version;
+        version != current && self.refresh(version)
+    }
+
+    fn refresh(&self, version: Option<SystemTime>) -> bool {
+        let value = match self.0.source.read() {
+            Ok(value) => value,
+            Err(e) => {
+                // A half-written file mid-rotation is retried on the next check
+                warn!("Failed to reload {}, keeping the previous value: {e}", self.0.name);
+                return false;
+            }
+        };
+        let mut current = self.0.current.lock().unwrap_or_else(PoisonError::into_inner);
+        let changed = current.value != value;
+        *current = Current { value, version };
+        drop(current);
+        if changed {
+            info!("Reloaded {} from {}", self.0.name, self.0.source.describe());
+        }
+        changed
+    }
+}
+
+/// Spawns a background task that re-reads secrets whenever their files change,
+/// checking every `HCM_SECRET_RELOAD_SECS`.
+///
+/// Call it once the HCM environments are loaded, so their secrets are known.
+pub fn spawn_watcher() {
+    let secrets = WATCHED.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    let reload_interval = tuning().secret_reload;
+    if secrets.is_empty() || reload_interval.is_zero() {
+        return;
+    }
+    info!(
+        "Watching {} HCM secret files for changes every {}s",
+        secrets.len(),
+        reload_interval.as_secs()
+    );
+    tokio::spawn(async move {
+        let mut ticker = interval(reload_interval);
+        loop {
+            ticker.tick().await;
+            for secret in &secrets {
+                secret.reload_if_changed();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A secret file in the temporary directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(test: &str, contents: &str) -> Self {
+            let file = Self(std::env::temp_dir().join(format!("oracle-hcm-mcp-secret-{}-{test}", std::process::id())));
+            file.write(contents, SystemTime::now());
+            file
+        }
+
+        /// Replaces the contents, stamping the file with `modified`.
+        fn write(&self, contents: &str, modified: SystemTime) {
+            fs::write(&self.0, contents).unwrap_or_else(|e| unreachable!("write: {e}"));
+            fs::File::options()
+                .write(true)
+                .open(&self.0)
+                .and_then(|file| file.set_modified(modified))
+                .unwrap_or_else(|e| unreachable!("set mtime: {e}"));
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn secret_file_is_trimmed() -> Result<()> {
+        let file = TempFile::new("trimmed", "  s3cret\n");
+        assert_eq!(SecretFile::new(&file.0).read()?, "s3cret");
+        Ok(())
+    }
+
+    #[test]
+    fn empty_or_missing_secret_file_is_an_error() {
+        let file = TempFile::new("empty", " \n");
+        assert!(SecretFile::new(&file.0).read().is_err());
+        assert!(Secret::new("HCM_PASSWORD_FILE", Box::new(SecretFile::new(&file.0))).is_err());
+        assert!(SecretFile::new(file.0.with_extension("missing")).read().is_err());
+    }
+
+    #[test]
+    fn changed_file_is_reloaded_even_with_an_older_mtime() -> Result<()> {
+        let file = TempFile::new("rotated", "first");
+        let secret = Secret::new("HCM_PASSWORD_FILE", Box::new(SecretFile::new(&file.0)))?;
+        assert!(!secret.reload_if_changed());
+
+        // Restored from a backup or copied with its original timestamp
+        file.write("second", SystemTime::now() - Duration::from_hours(1));
+        assert!(secret.reload_if_changed());
+        assert_eq!(secret.value(), "second");
+        Ok(())
+    }
+
+    #[test]
+    fn unreadable_file_keeps_the_previous_value() -> Result<()> {
+        let file = TempFile::new("half-written", "first");
+        let secret = Secret::new("HCM_PASSWORD_FILE", Box::new(SecretFile::new(&file.0)))?;
+
+        file.write("", SystemTime::now() + Duration::from_mins(1));
+        assert!(!secret.reload_if_changed());
+        assert_eq!(secret.value(), "first");
+
+        // Picked up once the write completes
+        file.write("second", SystemTime::now() + Duration::from_mins(2));
+        assert!(secret.reload_if_changed());
+        assert_eq!(secret.value(), "second");
+        Ok(())
+    }
+
+    #[test]
+    fn missing_required_secret_names_every_variable() {
+        let Err(e) = Secret::required(Settings::new(Some("prod")), "HCM_UNSET_TEST_SECRET") else {
+            unreachable!("HCM_UNSET_TEST_SECRET is set")
+        };
+        assert_eq!(
+            e.to_string(),
+            "HCM_ENV_PROD_UNSET_TEST_SECRET, HCM_ENV_PROD_UNSET_TEST_SECRET_FILE, HCM_UNSET_TEST_SECRET \
+             or HCM_UNSET_TEST_SECRET_FILE must be set in the environment or the config file"
+        );
+    }
+}
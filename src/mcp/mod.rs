@@ -1,53 +1,71 @@
-//! Oracle HCM (People HQ) Model Context Protocol implementation.
+//! Oracle HCM Model Context Protocol implementation.
 //!
 //! This crate provides MCP tools for interacting with Oracle HCM's REST API.
 //! The implementation is organized into:
 //!
+//! - `audit`: Tamper-evident audit log of tool invocations
+//! - `authz`: Row-level authorization of the caller against the target employee
+//! - `caller`: Identity of the authenticated MCP caller
 //! - `error`: Error types and conversions
 //! - `http`: HTTP client and API communication
+//! - `naming`: Organisation-specific tool names and descriptions
+//! - `redact`: Redaction of credentials and PII in logs and spans
 //! - `tools`: Individual MCP tools for specific HCM operations
 //!
 //! The main entry point is the `OracleHCMMCPFactory` which provides the MCP server
 //! implementation and manages all tools.
 
+pub mod audit;
+pub mod authz;
+pub mod caller;
 pub mod error;
 pub mod http;
+pub mod naming;
+pub mod redact;
 pub mod tools;
 
-use anyhow::{Result, anyhow};
+use std::{future::Future, pin::Pin, sync::{Arc, OnceLock}};
+use anyhow::Result;
 use rmcp::{
     ErrorData, RoleServer, ServerHandler,
     handler::server::{
         router::tool::ToolRouter,
         wrapper::Parameters,
     },
+    handler::server::tool::ToolCallContext,
     model::{
-        InitializeRequestParam, InitializeResult, CallToolResult,
-        ProtocolVersion, ServerCapabilities, ServerInfo, Implementation,
+        InitializeRequestParam, InitializeResult, CallToolRequestParam, CallToolResult,
+        ListToolsResult, PaginatedRequestParam, ProtocolVersion, ServerCapabilities,
+        ServerInfo, Implementation,
     },
     service::RequestContext,
-    tool, tool_handler, tool_router,
+    tool, tool_router,
 };
 use axum::http::request;
-use tracing::info;
+use serde::Serialize;
+use tracing::{Instrument, info};
+
+use crate::{mcp::{audit::AuditEntry, caller::Caller}, metrics::observe_tool_call, telemetry::tool_span};
 
 // Use re-exported items from http module
-use crate::mcp::http::{
-    HCM_BASE_URL,
-    HCM_PASSWORD,
-};
+use crate::mcp::http::HcmEnvironment;
 
 // Tool modules and commonly used tool types
 use crate::mcp::tools::{
     absence_balance::{self, AbsenceBalanceRequest},
-    projected_balance,
+    projected_balance::{self, ProjectedBalanceRequest},
     absence_types,
     person_id::{self, Employee},
 };
 
+/// A tool implementation's future, borrowing the caller it runs for.
+type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<CallToolResult, ErrorData>> + Send + 'a>>;
+
 #[derive(Clone)]
 pub struct OracleHCMMCPFactory {
     tool_router: ToolRouter<Self>,
+    /// HCM environment the session was bound to by `initialize`
+    environment: Arc<OnceLock<&'static HcmEnvironment>>,
 }
 
 #[tool_router]
@@ -56,22 +74,55 @@ impl OracleHCMMCPFactory {
         // Eagerly evaluate required LazyLock configurations to ensure they're valid
         // This fails fast if any required config is missing or invalid
         
-        // Required: Base URL for Oracle HCM API
-        let _ = HCM_BASE_URL.as_ref()
-            .map_err(|e| anyhow!("Failed to load HCM_BASE_URL: {e}"))?;
-        
-        // Required: Password for authentication
-        let _ = HCM_PASSWORD.as_ref()
-            .map_err(|e| anyhow!("Failed to load HCM_PASSWORD: {e}"))?;
+        // Every HCM environment needs a base URL and an authentication provider with
+        // its credentials (HCM_PASSWORD for Basic auth); delegation is validated when enabled
+        for environment in HcmEnvironment::all()? {
+            environment.validate()?;
+        }
+
+        // Initialize with tool router loaded from macro-generated code, with the
+        // organisation's tool names and wording (MCP_TOOL_NAMES etc.) applied
+        Ok(Self {
+            tool_router: naming::apply(Self::tool_router())?,
+            environment: Arc::default(),
+        })
+    }
 
-        // Initialize with tool router loaded from macro-generated code
-        Ok(Self { tool_router: Self::tool_router() })
+    /// The session's HCM environment, or the default one if it never initialized.
+    fn environment(&self) -> Result<&'static HcmEnvironment, ErrorData> {
+        match self.environment.get() {
+            Some(environment) => Ok(environment),
+            None => Ok(HcmEnvironment::current()?),
+        }
+    }
+
+    /// Runs a tool implementation for the authenticated caller (if any), who is
+    /// also set as the task's current caller for on-behalf-of HCM delegation,
+    /// with the session's HCM environment as the task's current environment.
+    /// The call runs in a span continuing the inbound request's trace, is
+    /// counted and timed for the `/metrics` endpoint, and is appended to the
+    /// audit log.
+    async fn run<P: Serialize>(
+        &self,
+        tool: &'static str,
+        context: RequestContext<RoleServer>,
+        params: Parameters<P>,
+        call: impl for<'a> FnOnce(Parameters<P>, Option<&'a Caller>) -> ToolFuture<'a>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let environment = self.environment()?;
+        let caller = Caller::from_context(&context);
+        let audit = AuditEntry::new(tool, &context, &params.0);
+        let span = tool_span(tool, &context);
+        let call = Caller::scope(caller.clone(), call(params, caller.as_ref()));
+        HcmEnvironment::scope(environment, audit.record(observe_tool_call(tool, call.instrument(span)))).await
     }
 
     // Thin delegating methods so the `tool_router` proc-macro (which scans
     // this impl block) can discover and register the tools. These simply
-    // forward to the actual implementations in `mcp::tools::*` so the
-    // implementation remains modular.
+    // forward to the actual implementations in `mcp::tools::*` through
+    // `run`, so the implementation remains modular. The authenticated caller
+    // (if any) is passed along so each tool can enforce row-level
+    // authorization. The tool future is boxed so the wrappers around it stay small.
 
     #[tool(
         description = "Get all available absence balances for a particular employee, based on their PersonId (the balances are based off a system calculation date, and not projected balances)."
@@ -79,18 +130,26 @@ impl OracleHCMMCPFactory {
     async fn get_all_absence_balances_for_employee_hcm_person_id(
         &self,
         params: Parameters<AbsenceBalanceRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        absence_balance::get_all_absence_balances_for_employee_hcm_person_id(params).await
+        self.run("get_all_absence_balances_for_employee_hcm_person_id", context, params, |params, caller| {
+            Box::pin(absence_balance::get_all_absence_balances_for_employee_hcm_person_id(params, caller))
+        })
+        .await
     }
 
     #[tool(
-        description = "Get projected balance for a particular PersonId as well as a projection date/effective date in DD-MM-YYYY format (Balance As Of Date), for a particular AbsenceTypeId"
+        description = "Get projected balance for a particular PersonId as well as a projection date/effective date in DD-MM-YYYY format (Balance As Of Date), for a particular AbsenceTypeId. The unit of measure and working hours per day come from the absence type and the employee's working hours in HCM, unless given."
     )]
     async fn get_projected_balance(
         &self,
-        params: Parameters<AbsenceBalanceRequest>,
+        params: Parameters<ProjectedBalanceRequest>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        projected_balance::get_projected_balance(params).await
+        self.run("get_projected_balance", context, params, |params, caller| {
+            Box::pin(projected_balance::get_projected_balance(params, caller))
+        })
+        .await
     }
 
     #[tool(
@@ -99,22 +158,29 @@ impl OracleHCMMCPFactory {
     async fn get_absence_types_for_employee_hcm_person_id(
         &self,
         params: Parameters<Employee>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        absence_types::get_absence_types_for_employee_hcm_person_id(params).await
+        self.run("get_absence_types_for_employee_hcm_person_id", context, params, |params, caller| {
+            Box::pin(absence_types::get_absence_types_for_employee_hcm_person_id(params, caller))
+        })
+        .await
     }
 
     #[tool(
-        description = "Get Oracle HCM PersonId for a provided Westpac M/F/L id. Example: M061230 is a Westpac Employee ID, but it's corresponding PersonId in Oracle HCM is needed for API/or other Tool calls to HCM."
+        description = "Get Oracle HCM PersonId for a provided {worker_number}. Example: for the {worker_number} {worker_number_example}, its corresponding PersonId in Oracle HCM is needed for API/or other Tool calls to HCM."
     )]
-    async fn get_oracle_hcm_person_id_from_westpac_id(
+    async fn get_oracle_hcm_person_id_from_worker_number(
         &self,
         params: Parameters<Employee>,
+        context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        person_id::get_oracle_hcm_person_id_from_westpac_id(params).await
+        self.run("get_oracle_hcm_person_id_from_worker_number", context, params, |params, caller| {
+            Box::pin(person_id::get_oracle_hcm_person_id_from_worker_number(params, caller))
+        })
+        .await
     }
 }
 
-#[tool_handler]
 impl ServerHandler for OracleHCMMCPFactory {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
@@ -123,23 +189,54 @@ impl ServerHandler for OracleHCMMCPFactory {
                 .enable_tools()
                 .build(),
             server_info: Implementation::from_build_env(),
-            instructions: Some(
-                "Oracle HCM (also known as People HQ at Westpac) MCP Server with tools prompts and resources"
-                    .to_string(),
-            ),
+            instructions: Some(naming::instructions()),
         }
     }
 
+    // Hand-written rather than `#[tool_handler]`, so tools stay callable by
+    // their built-in and earlier names after MCP_TOOL_NAMES renames them
+    async fn call_tool(
+        &self,
+        mut request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if !self.tool_router.has_route(&request.name) {
+            request.name = naming::resolve(&request.name).into();
+        }
+        let tcc = ToolCallContext::new(self, request, context);
+        self.tool_router.call(tcc).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        Ok(ListToolsResult::with_all_items(self.tool_router.list_all()))
+    }
+
     async fn initialize(
         &self,
         _request: InitializeRequestParam,
         context: RequestContext<RoleServer>,
     ) -> Result<InitializeResult, ErrorData> {
-        if let Some(http_request_part) = context.extensions.get::<request::Parts>() {
-            let initialize_headers = &http_request_part.headers;
+        let parts = context.extensions.get::<request::Parts>();
+        if let Some(http_request_part) = parts {
+            let initialize_headers = redact::headers(&http_request_part.headers);
             let initialize_uri = &http_request_part.uri;
             info!(?initialize_headers, %initialize_uri, "initialize from http server");
         }
+        let caller = Caller::from_context(&context);
+        if let Some(caller) = &caller {
+            let issuer = caller.claim_str("iss").unwrap_or_default();
+            info!(subject = %caller.subject, %issuer, "initialize from authenticated caller");
+        }
+
+        // Bind the session to its HCM environment for all later tool calls
+        let environment = HcmEnvironment::select(parts, caller.as_ref())?;
+        if self.environment.set(environment).is_ok() {
+            info!(environment = %environment.name, "session bound to HCM environment");
+        }
         Ok(self.get_info())
     }
 }
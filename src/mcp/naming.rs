@@ -0,0 +1,264 @@
+//! Organisation-specific naming and wording of the MCP tools.
+//!
+//! Each Fusion customer has its own name for HCM's `WorkerNumber`, e.g. a
+//! "Acme staff number", and agents pick tools better when descriptions use it.
+//! Tool and parameter descriptions are therefore written with placeholders,
+//! filled in from configuration by [`apply`] when a session's tools are built:
+//! - `{worker_number}`: `MCP_WORKER_NUMBER_LABEL`
+//! - `{worker_number_example}`: `MCP_WORKER_NUMBER_EXAMPLE`
+//! - `{person_id_example}`: `MCP_PERSON_ID_EXAMPLE`
+//!
+//! Tools can also be renamed with `MCP_TOOL_NAMES`. Only the configured names
+//! are listed, but built-in names and the names of earlier releases (see
+//! `LEGACY_NAMES`) stay callable as aliases, so existing agents keep working.
+//! Metrics, spans and audit records always use the built-in names.
+//!
+//! # Configuration
+//! - `MCP_ORGANIZATION_NAME` (optional): Organisation name, e.g. "Acme", used by the defaults below
+//! - `MCP_WORKER_NUMBER_LABEL` (optional): What the organisation calls a worker number,
+//!   defaults to "<organisation> employee ID"
+//! - `MCP_WORKER_NUMBER_EXAMPLE` (optional): Example worker number, defaults to "E012345"
+//! - `MCP_PERSON_ID_EXAMPLE` (optional): Example Oracle HCM `PersonId`, defaults to "300000578701661"
+//! - `MCP_INSTRUCTIONS` (optional): Server instructions sent to clients when they initialize
+//! - `MCP_TOOL_NAMES` (optional): Tool names as `builtin=name`, comma-separated,
+//!   e.g. "`get_oracle_hcm_person_id_from_worker_number=get_person_id_from_employee_id`"
+
+use std::{collections::HashMap, sync::{Arc, LazyLock}};
+use anyhow::{Result, anyhow, bail};
+use rmcp::handler::server::router::tool::ToolRouter;
+use serde_json::Value;
+
+use crate::mcp::http::load_env_var_or;
+
+/// Tool names of earlier releases, and the built-in tools they now call.
+const LEGACY_NAMES: &[(&str, &str)] = &[(
+    "get_oracle_hcm_person_id_from_westpac_id",
+    "get_oracle_hcm_person_id_from_worker_number",
+)];
+
+/// Longest tool name MCP clients are expected to accept.
+const MAX_NAME_LEN: usize = 128;
+
+static ORGANIZATION: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_ORGANIZATION_NAME", "").trim().to_string());
+
+static WORKER_NUMBER_LABEL: LazyLock<String> = LazyLock::new(|| {
+    let default = if ORGANIZATION.is_empty() {
+        "employee ID".to_string()
+    } else {
+        format!("{} employee ID", *ORGANIZATION)
+    };
+    load_env_var_or("MCP_WORKER_NUMBER_LABEL", &default)
+});
+
+static WORKER_NUMBER_EXAMPLE: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_WORKER_NUMBER_EXAMPLE", "E012345"));
+
+static PERSON_ID_EXAMPLE: LazyLock<String> =
+    LazyLock::new(|| load_env_var_or("MCP_PERSON_ID_EXAMPLE", "300000578701661"));
+
+static INSTRUCTIONS: LazyLock<String> = LazyLock::new(|| {
+    let default = if ORGANIZATION.is_empty() {
+        "Oracle HCM MCP Server with tools prompts and resources".to_string()
+    } else {
+        format!("Oracle HCM MCP Server for {} with tools prompts and resources", *ORGANIZATION)
+    };
+    load_env_var_or("MCP_INSTRUCTIONS", &default)
+});
+
+/// Configured tool names by built-in name.
+static TOOL_NAMES: LazyLock<Result<HashMap<String, String>>> =
+    LazyLock::new(|| parse_tool_names(&load_env_var_or("MCP_TOOL_NAMES", "")));
+
+/// Parses `MCP_TOOL_NAMES`: `builtin=name` entries, comma-separated.
+fn parse_tool_names(configured: &str) -> Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+    for entry in configured.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let Some((builtin, name)) = entry.split_once('=') else {
+            bail!("MCP_TOOL_NAMES entries must be builtin=name, got {entry}");
+        };
+        names.insert(builtin.trim().to_string(), name.trim().to_string());
+    }
+    Ok(names)
+}
+
+/// What the organisation calls a worker number, e.g. "employee ID".
+pub fn worker_number_label() -> &'static str {
+    &WORKER_NUMBER_LABEL
+}
+
+/// Instructions sent to clients when they initialize.
+pub fn instructions() -> String {
+    INSTRUCTIONS.clone()
+}
+
+/// The name a tool is listed under, for a call made by any of its names.
+pub fn resolve(name: &str) -> String {
+    TOOL_NAMES.as_ref().map_or_else(
+        |_| builtin_name(name).to_string(),
+        |names| resolve_in(names, name),
+    )
+}
+
+/// The built-in name behind a name of an earlier release, or `name` itself.
+fn builtin_name(name: &str) -> &str {
+    LEGACY_NAMES
+        .iter()
+        .find(|(legacy, _)| *legacy == name)
+        .map_or(name, |(_, builtin)| builtin)
+}
+
+/// Like [`resolve`], with `names` as the configured tool names.
+fn resolve_in(names: &HashMap<String, String>, name: &str) -> String {
+    let builtin = builtin_name(name);
+    names.get(builtin).map_or_else(|| builtin.to_string(), Clone::clone)
+}
+
+/// Renames `router`'s tools as configured and fills in their descriptions.
+///
+/// # Errors
+/// Fails if `MCP_TOOL_NAMES` is malformed, renames an unknown tool, or gives
+/// a tool a name that is invalid or taken by another tool or alias.
+pub fn apply<S: Send + Sync + 'static>(router: ToolRouter<S>) -> Result<ToolRouter<S>> {
+    rename(router, TOOL_NAMES.as_ref().map_err(|e| anyhow!("{e}"))?)
+}
+
+/// Like [`apply`], with `names` as the configured tool names.
+fn rename<S: Send + Sync + 'static>(router: ToolRouter<S>, names: &HashMap<String, String>) -> Result<ToolRouter<S>> {
+    if let Some(unknown) = names.keys().find(|builtin| !router.has_route(builtin)) {
+        bail!("MCP_TOOL_NAMES renames {unknown}, which is not a tool");
+    }
+
+    let builtins: Vec<String> = router.map.keys().map(ToString::to_string).collect();
+    let mut renamed = ToolRouter::new();
+    for mut route in router {
+        let builtin = route.attr.name.to_string();
+        let name = names.get(&builtin).unwrap_or(&builtin).clone();
+        validate_name(&name)?;
+        let taken = builtins.iter().any(|other| *other != builtin && *other == name)
+            || LEGACY_NAMES.iter().any(|(legacy, target)| *legacy == name && *target != builtin)
+            || renamed.has_route(&name);
+        if taken {
+            bail!("MCP_TOOL_NAMES gives {builtin} the name {name}, which another tool already uses");
+        }
+
+        route.attr.name = name.into();
+        route.attr.description = route.attr.description.map(|description| fill(&description).into());
+        let mut schema = Value::Object((*route.attr.input_schema).clone());
+        fill_strings(&mut schema);
+        if let Value::Object(schema) = schema {
+            route.attr.input_schema = Arc::new(schema);
+        }
+        renamed.add_route(route);
+    }
+    Ok(renamed)
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        bail!("MCP_TOOL_NAMES: {name:?} is not a valid tool name (letters, digits, '_', '-' and '.', at most {MAX_NAME_LEN})");
+    }
+    Ok(())
+}
+
+/// Replaces the placeholders in `text`.
+fn fill(text: &str) -> String {
+    text.replace("{worker_number}", &WORKER_NUMBER_LABEL)
+        .replace("{worker_number_example}", &WORKER_NUMBER_EXAMPLE)
+        .replace("{person_id_example}", &PERSON_ID_EXAMPLE)
+}
+
+/// Fills in the placeholders of every string in a JSON schema, such as property descriptions.
+fn fill_strings(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = fill(text),
+        Value::Array(items) => items.iter_mut().for_each(fill_strings),
+        Value::Object(entries) => entries.values_mut().for_each(fill_strings),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::OracleHCMMCPFactory;
+    use serde_json::json;
+
+    const PERSON_ID_TOOL: &str = "get_oracle_hcm_person_id_from_worker_number";
+    const TYPES_TOOL: &str = "get_absence_types_for_employee_hcm_person_id";
+    const LEGACY_PERSON_ID_TOOL: &str = "get_oracle_hcm_person_id_from_westpac_id";
+
+    fn names(configured: &str) -> HashMap<String, String> {
+        parse_tool_names(configured).unwrap_or_else(|e| unreachable!("{e}"))
+    }
+
+    fn renamed(configured: &str) -> Result<ToolRouter<OracleHCMMCPFactory>> {
+        rename(OracleHCMMCPFactory::tool_router(), &names(configured))
+    }
+
+    #[test]
+    fn legacy_names_resolve_to_the_listed_name() {
+        assert_eq!(resolve_in(&names(""), LEGACY_PERSON_ID_TOOL), PERSON_ID_TOOL);
+        let configured = names(&format!("{PERSON_ID_TOOL}=get_person_id_from_employee_id"));
+        assert_eq!(resolve_in(&configured, LEGACY_PERSON_ID_TOOL), "get_person_id_from_employee_id");
+        assert_eq!(resolve_in(&configured, PERSON_ID_TOOL), "get_person_id_from_employee_id");
+        assert_eq!(resolve_in(&configured, TYPES_TOOL), TYPES_TOOL);
+    }
+
+    #[test]
+    fn renamed_tools_are_listed_under_their_new_names() -> Result<()> {
+        let router = renamed(&format!(" {PERSON_ID_TOOL} = get_person_id_from_employee_id ,"))?;
+        assert!(router.has_route("get_person_id_from_employee_id"));
+        assert!(!router.has_route(PERSON_ID_TOOL));
+        assert!(router.has_route(TYPES_TOOL));
+        Ok(())
+    }
+
+    #[test]
+    fn a_tool_may_take_its_own_legacy_name() -> Result<()> {
+        let router = renamed(&format!("{PERSON_ID_TOOL}={LEGACY_PERSON_ID_TOOL}"))?;
+        assert!(router.has_route(LEGACY_PERSON_ID_TOOL));
+        Ok(())
+    }
+
+    #[test]
+    fn names_taken_by_other_tools_or_aliases_are_rejected() {
+        assert!(renamed(&format!("{TYPES_TOOL}={PERSON_ID_TOOL}")).is_err());
+        assert!(renamed(&format!("{TYPES_TOOL}={LEGACY_PERSON_ID_TOOL}")).is_err());
+        assert!(renamed(&format!("{TYPES_TOOL}=get_person,{PERSON_ID_TOOL}=get_person")).is_err());
+    }
+
+    #[test]
+    fn unknown_tools_and_invalid_names_are_rejected() {
+        assert!(renamed("get_payslips=payslips").is_err());
+        assert!(renamed(&format!("{TYPES_TOOL}=absence types")).is_err());
+        assert!(renamed(&format!("{TYPES_TOOL}=")).is_err());
+        assert!(renamed(&format!("{TYPES_TOOL}={}", "a".repeat(MAX_NAME_LEN + 1))).is_err());
+        assert!(parse_tool_names(TYPES_TOOL).is_err());
+    }
+
+    #[test]
+    fn placeholders_are_filled_throughout_nested_schemas() -> Result<()> {
+        let mut schema = json!({
+            "description": "Looks up a {worker_number}",
+            "properties": {
+                "worker_number": { "description": "e.g. {worker_number_example}" },
+                "ids": { "items": [{ "description": "e.g. {person_id_example}" }] },
+            },
+        });
+        fill_strings(&mut schema);
+        assert_eq!(schema["description"], format!("Looks up a {}", worker_number_label()));
+        assert_eq!(schema["properties"]["worker_number"]["description"], format!("e.g. {}", *WORKER_NUMBER_EXAMPLE));
+        assert_eq!(schema["properties"]["ids"]["items"][0]["description"], format!("e.g. {}", *PERSON_ID_EXAMPLE));
+
+        // No placeholder is left in any tool's description or schema
+        for tool in renamed("")?.list_all() {
+            let listed = serde_json::to_string(&tool)?;
+            assert!(!listed.contains("{worker_number") && !listed.contains("{person_id_example}"), "{listed}");
+        }
+        Ok(())
+    }
+}
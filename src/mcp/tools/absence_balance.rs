@@ -9,7 +9,11 @@
 //!
 //! Balances are returned in hours and include carry-over status.
 
-use crate::mcp::http::{hcm_api_call, Method};
+use crate::mcp::{
+    authz::authorize,
+    caller::Caller,
+    http::{hcm_collection, Filter, HcmQuery, PageOptions},
+};
 use anyhow::Result;
 use chrono::NaiveDate;
 use rmcp::{
@@ -23,7 +27,7 @@ use serde_json::json;
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct AbsenceBalanceRequest {
-    #[schemars(description = "Unique PersonID in Oracle HCM, e.g. 300000578701661")]
+    #[schemars(description = "Unique PersonID in Oracle HCM, e.g. {person_id_example}")]
     pub hcm_person_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[schemars(
@@ -44,6 +48,7 @@ pub struct AbsenceBalanceRequest {
 
 pub async fn get_all_absence_balances_for_employee_hcm_person_id(
     Parameters(args): Parameters<AbsenceBalanceRequest>,
+    caller: Option<&Caller>,
 ) -> Result<CallToolResult, ErrorData> {
     if args.hcm_person_id.is_empty() {
         return Err(ErrorData::new(
@@ -53,41 +58,48 @@ pub async fn get_all_absence_balances_for_employee_hcm_person_id(
         ));
     }
 
-    let path = format!(
-        "/planBalances?onlyData=true&q=personId={};planDisplayStatusFlag=true",
-        args.hcm_person_id
-    );
+    authorize(caller, &args.hcm_person_id).await?;
+
+    // planBalances only understands the simple (framework version 1) filter syntax
+    let query = HcmQuery::legacy("/planBalances")
+        .filter(
+            Filter::new()
+                .eq("personId", args.hcm_person_id.as_str())
+                .eq("planDisplayStatusFlag", true),
+        )
+        // A unique sort key keeps offset paging from skipping or repeating plans
+        .order_by("personPlanEnrollmentId")
+        .only_data();
 
-    let json = hcm_api_call(&path, Method::GET, None, false, None)
+    // Follows hasMore so employees with many plans get every balance
+    let plans = hcm_collection(query, PageOptions::default())
         .await?;
 
-    let absence_balances = json["items"]
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|item| {
-                    let name = item["planName"].as_str()?;
-                    let carry_over = item["multiYearCarryOverFlag"].as_bool()?;
-                    let plan_status = item["planStatusMeaning"].as_str()?;
-                    let formatted_balance = item["formattedBalance"].as_str()?;
-                    let balance_calc_date = item["balanceCalculationDate"]
-                        .as_str()
-                        .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
-                        .map(|d| d.format("%d-%m-%Y").to_string())?;
+    let absence_balances = plans
+        .items
+        .iter()
+        .filter_map(|item| {
+            let name = item["planName"].as_str()?;
+            let carry_over = item["multiYearCarryOverFlag"].as_bool()?;
+            let plan_status = item["planStatusMeaning"].as_str()?;
+            let formatted_balance = item["formattedBalance"].as_str()?;
+            let balance_calc_date = item["balanceCalculationDate"]
+                .as_str()
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+                .map(|d| d.format("%d-%m-%Y").to_string())?;
 
-                    Some(json!({
-                        "planName": name,
-                        "carryOver": carry_over,
-                        "planStatus": plan_status,
-                        "formattedBalance": formatted_balance,
-                        "balanceCalculationDate": balance_calc_date,
-                    }))
-                })
-                .collect::<Vec<_>>()
+            Some(json!({
+                "planName": name,
+                "carryOver": carry_over,
+                "planStatus": plan_status,
+                "formattedBalance": formatted_balance,
+                "balanceCalculationDate": balance_calc_date,
+            }))
         })
-        .unwrap_or_default();
+        .collect::<Vec<_>>();
 
     Ok(CallToolResult::structured(json!({
         "absence_balances": absence_balances,
+        "truncated": plans.truncated,
     })))
 }
\ No newline at end of file
@@ -7,8 +7,10 @@
 //! - Location-specific leave types
 
 use crate::mcp::{
+    authz::authorize,
+    caller::Caller,
     error::HcmError,
-    http::{hcm_api_call, Method},
+    http::{hcm_collection, HcmQuery, PageOptions},
     tools::person_id::Employee,
 };
 use anyhow::Result;
@@ -21,6 +23,7 @@ use serde_json::json;
 
 pub async fn get_absence_types_for_employee_hcm_person_id(
     Parameters(args): Parameters<Employee>,
+    caller: Option<&Caller>,
 ) -> Result<CallToolResult, ErrorData> {
     let person_id = args
         .hcm_person_id
@@ -29,30 +32,34 @@ pub async fn get_absence_types_for_employee_hcm_person_id(
             HcmError::InvalidParams("HCM PersonId is required and cannot be empty.".to_string())
         })?;
 
-    let path = format!(
-        "/absenceTypesLOV?onlyData=true&finder=findByWord;PersonId={person_id}"
-    );
+    authorize(caller, &person_id).await?;
 
-    let json = hcm_api_call(&path, Method::GET, None, true, None)
+    let query = HcmQuery::new("/absenceTypesLOV")
+        .finder("findByWord", [("PersonId", person_id.as_str())])
+        .fields(&["AbsenceTypeId", "EmployerId", "AbsenceTypeName"])
+        .order_by("AbsenceTypeName")
+        .only_data();
+
+    let types = hcm_collection(query, PageOptions::default())
         .await?;
 
-    let absence_types = json["items"]
-        .as_array()
-        .map(|arr| {
-            arr.iter()
-                .filter_map(|item| {
-                    let id = item["AbsenceTypeId"].as_str()?;
-                    let employer_id = item["EmployerId"].as_str()?;
-                    let name = item["AbsenceTypeName"].as_str()?;
-                    Some(json!({
-                        "AbsenceTypeId": id,
-                        "EmployerId": employer_id,
-                        "AbsenceTypeName": name
-                    }))
-                })
-                .collect::<Vec<_>>()
+    let absence_types = types
+        .items
+        .iter()
+        .filter_map(|item| {
+            let id = item["AbsenceTypeId"].as_str()?;
+            let employer_id = item["EmployerId"].as_str()?;
+            let name = item["AbsenceTypeName"].as_str()?;
+            Some(json!({
+                "AbsenceTypeId": id,
+                "EmployerId": employer_id,
+                "AbsenceTypeName": name
+            }))
         })
-        .unwrap_or_default();
+        .collect::<Vec<_>>();
 
-    Ok(CallToolResult::structured(json!({ "absence_types": absence_types })))
+    Ok(CallToolResult::structured(json!({
+        "absence_types": absence_types,
+        "truncated": types.truncated,
+    })))
 }
\ No newline at end of file
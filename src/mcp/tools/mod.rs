@@ -3,7 +3,7 @@
 //! Each module implements a specific tool:
 //! - `absence_balance`: Get and calculate leave balances
 //! - `absence_types`: Query available absence/leave types
-//! - `person_id`: Map Westpac IDs to HCM person IDs
+//! - `person_id`: Map worker numbers (employee IDs) to HCM person IDs
 //! - `projected_balance`: Calculate future leave balances
 //!
 //! All tools use the shared HTTP client and error handling.
@@ -1,18 +1,26 @@
-//! Tool for mapping Westpac employee IDs to Oracle HCM person IDs.
+//! Tool for mapping worker numbers (employee IDs) to Oracle HCM person IDs.
 //!
 //! Oracle HCM uses numeric person IDs (e.g., "300000578701661") as the primary identifier
-//! for employees. However, Westpac uses alphanumeric employee IDs (e.g., "M061230").
-//! This tool provides the mapping between these two identifier systems.
+//! for employees. However, people know each other by their worker number, the
+//! organisation's own employee ID (e.g., "E012345"). This tool provides
+//! the mapping between these two identifier systems. What the organisation calls
+//! a worker number is configurable, see `naming`.
 //!
 //! # Use Case
 //! Most HCM API operations require a person ID. When an AI assistant knows only
-//! the Westpac employee ID, this tool must be called first to obtain the corresponding
+//! the employee ID, this tool must be called first to obtain the corresponding
 //! HCM person ID before calling other tools.
 //!
 //! # API Details
 //! Uses the `/publicWorkers` endpoint with a filter on `assignments.WorkerNumber`.
 
-use crate::mcp::http::{hcm_api_call, Method};
+use crate::mcp::{
+    authz::{authorize, is_hr_admin},
+    caller::Caller,
+    error::{ErrorKind, HcmError},
+    http::{hcm_collection, Filter, HcmQuery, PageOptions},
+    naming::worker_number_label,
+};
 use anyhow::Result;
 use rmcp::{
     handler::server::wrapper::Parameters,
@@ -24,29 +32,65 @@ use schemars::JsonSchema;
 use serde_json::json;
 
 /// Input parameter for person ID lookup.
-/// Contains the Westpac employee ID to search for.
+/// Contains the worker number to search for.
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Employee {
-    /// Westpac employee identifier (e.g., "M061230", "F123456", "L789012")
-    /// Case-insensitive - will be converted to uppercase for API query
-    #[schemars(description = "Unique Westpac Employee ID, e.g. M061230")]
-    pub wbc_employee_id: String,
+    /// The organisation's employee identifier, HCM's `WorkerNumber` (e.g., "E012345")
+    /// Case-insensitive - will be converted to uppercase for API query.
+    /// Still accepted as `wbc_employee_id`, its name in earlier releases.
+    #[serde(alias = "wbc_employee_id")]
+    #[schemars(description = "Unique {worker_number}, e.g. {worker_number_example}")]
+    pub worker_number: String,
     
     /// Oracle HCM person identifier (numeric string)
     /// This field is optional in the input but required in other tools
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[schemars(description = "Unique PersonID in Oracle HCM, e.g. 300000578701661")]
+    #[schemars(description = "Unique PersonID in Oracle HCM, e.g. {person_id_example}")]
     pub hcm_person_id: Option<String>,
 }
 
-/// Looks up an Oracle HCM person ID from a Westpac employee ID.
+/// Resolves a worker number (employee ID) to its Oracle HCM person ID.
+///
+/// Shared by the lookup tool and the authorization layer, which maps an
+/// authenticated caller's worker number to their `PersonId`.
+///
+/// # Errors
+/// * `NotFound` - If no worker with that number exists
+/// * Any error from [`hcm_collection`]
+pub async fn lookup_person_id(worker_number: &str) -> Result<String, HcmError> {
+    // Build API query - convert to uppercase as HCM stores IDs in uppercase
+    let query = HcmQuery::new("/publicWorkers")
+        .filter(Filter::new().eq("assignments.WorkerNumber", worker_number.to_uppercase()))
+        .fields(&["PersonId"])
+        .only_data();
+
+    // Fetch a single item since employee IDs are unique
+    let workers = hcm_collection(query, PageOptions::first(1))
+        .await?;
+
+    // Extract PersonId from the first worker
+    workers
+        .items
+        .first()
+        .and_then(|item| item["PersonId"].as_str())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            HcmError::not_found(format!(
+                "PersonID not found for {}: {worker_number}",
+                worker_number_label()
+            ))
+        })
+}
+
+/// Looks up an Oracle HCM person ID from a worker number (employee ID).
 ///
 /// # Arguments
-/// * `args` - Contains the `wbc_employee_id` to search for
+/// * `args` - Contains the `worker_number` to search for
+/// * `caller` - The authenticated caller, checked against the resolved `PersonId`
 ///
 /// # Returns
 /// * `Ok(CallToolResult)` - JSON containing the `PersonId` field
-/// * `Err(ErrorData)` - If employee not found or API error
+/// * `Err(ErrorData)` - If employee not found, access is denied, or API error
 ///
 /// # Example Response
 /// ```json
@@ -56,50 +100,47 @@ pub struct Employee {
 /// ```
 ///
 /// # Errors
-/// * `INVALID_PARAMS` - If `wbc_employee_id` is empty or employee not found
+/// * `INVALID_PARAMS` - If `worker_number` is empty
+/// * `RESOURCE_NOT_FOUND` - If no employee has that ID (HR admins and unauthenticated transports only)
+/// * `FORBIDDEN` - If the caller may not see this employee; other callers also get
+///   this for unknown IDs, so they cannot probe which worker numbers exist
 /// * `INTERNAL_ERROR` - If API call fails
-pub async fn get_oracle_hcm_person_id_from_westpac_id(
+pub async fn get_oracle_hcm_person_id_from_worker_number(
     Parameters(args): Parameters<Employee>,
+    caller: Option<&Caller>,
 ) -> Result<CallToolResult, ErrorData> {
     // Validate input
-    if args.wbc_employee_id.is_empty() {
+    if args.worker_number.is_empty() {
         return Err(ErrorData::new(
             ErrorCode::INVALID_PARAMS,
-            "Westpac Employee ID cannot be empty.".to_string(),
+            format!("{} cannot be empty.", worker_number_label()),
             None,
         ));
     }
 
-    // Build API query - convert to uppercase as HCM stores IDs in uppercase
-    // Limit to 1 result since employee IDs are unique
-    let path = format!(
-        "/publicWorkers?q=assignments.WorkerNumber='{}'&onlyData=true&limit=1",
-        args.wbc_employee_id.to_uppercase()
-    );
+    // Restricted callers get one answer for "no such worker" and "not yours to see",
+    // otherwise the difference reveals which worker numbers exist
+    let restricted = caller.is_some_and(|caller| !is_hr_admin(caller));
+    let denied = || {
+        HcmError::forbidden(format!(
+            "Caller is not permitted to access {} {}",
+            worker_number_label(),
+            args.worker_number
+        ))
+    };
 
-    // Make API call with REST-Framework-Version header
-    let response_json = hcm_api_call(&path, Method::GET, None, true, None)
-        .await?;
+    let person_id = match lookup_person_id(&args.worker_number).await {
+        Ok(person_id) => person_id,
+        Err(e) if restricted && e.kind() == ErrorKind::NotFound => return Err(denied().into()),
+        Err(e) => return Err(e.into()),
+    };
 
-    // Extract PersonId from response
-    // Response format: {"items": [{"PersonId": "123", ...}]}
-    let person_id = response_json["items"]
-        .as_array()
-        .and_then(|items| items.first())
-        .and_then(|item| item["PersonId"].as_str());
+    // The mapping itself identifies the worker, so it is subject to the same access rules
+    match authorize(caller, &person_id).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::Forbidden => return Err(denied().into()),
+        Err(e) => return Err(e.into()),
+    }
 
-    // Return PersonId or error if not found
-    person_id.map_or_else(
-        || {
-            Err(ErrorData::new(
-                ErrorCode::INVALID_PARAMS,
-                format!(
-                    "PersonID not found for Westpac Employee ID: {}",
-                    args.wbc_employee_id
-                ),
-                None,
-            ))
-        },
-        |id| Ok(CallToolResult::structured(json!({ "PersonId": id }))),
-    )
-}
\ No newline at end of file
+    Ok(CallToolResult::structured(json!({ "PersonId": person_id })))
+}
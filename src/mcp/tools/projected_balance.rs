@@ -7,11 +7,36 @@
 //! - Scheduled leave
 //! - Public holidays
 //! - Other adjustments
+//!
+//! HCM projects the balance as if a one-day absence were entered on the
+//! projection date, so the request needs that day's duration in the absence
+//! type's unit of measure. The unit comes from the absence type (`UOM` in
+//! `absenceTypesLOV`), and for hour-based types the hours per day from the
+//! standard working hours of the worker's primary assignment, so part-timers
+//! and other countries' schedules are projected correctly. Weekly hours are
+//! spread over the working days of the assignment's work schedule
+//! (`workScheduleAssignments`), or five days when it has none. Day-based types
+//! always use a duration of one day. Callers can override both per call; when
+//! HCM has no value the configured fallbacks are used.
+//!
+//! # Configuration
+//! - `HCM_PROJECTION_UOM` (optional): Unit of measure used when the absence type has none,
+//!   "H" (hours) or "D" (days), defaults to "H"
+//! - `HCM_PROJECTION_HOURS_PER_DAY` (optional): Hours per day used when the worker's
+//!   working hours aren't known, defaults to 7.6
 
-use crate::mcp::{
-    error::HcmError,
-    http::{hcm_api_call, Body, Method},
-    tools::absence_balance::AbsenceBalanceRequest,
+use crate::{
+    config::tuning,
+    mcp::{
+        authz::authorize,
+        caller::Caller,
+        error::HcmError,
+        http::{
+            child_items, hcm_api_call, hcm_collection, Body, Bulkhead, CallOptions, Filter, HcmQuery, Method,
+            PageOptions,
+        },
+        tools::absence_balance::AbsenceBalanceRequest,
+    },
 };
 use anyhow::Result;
 use chrono::{NaiveDate, Local};
@@ -20,16 +45,66 @@ use rmcp::{
     model::{CallToolResult, ErrorCode},
     ErrorData,
 };
-use serde_json::json;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Working days in a week when the assignment has no work schedule, for spreading weekly hours over days.
+const DEFAULT_WORKING_DAYS_PER_WEEK: f64 = 5.0;
+
+/// Input for a balance projection: the balance request, plus optional
+/// overrides of the values otherwise looked up in HCM.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ProjectedBalanceRequest {
+    #[serde(flatten)]
+    pub balance: AbsenceBalanceRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Unit of measure for the projection, H (hours) or D (days). Defaults to the absence type's unit of measure."
+    )]
+    pub uom: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schemars(
+        description = "Working hours per day for hour-based projections, e.g. 7.6. Defaults to the employee's standard working hours in HCM."
+    )]
+    pub hours_per_day: Option<f64>,
+}
+
+/// Where a projection's unit of measure or hours per day came from.
+#[derive(Clone, Copy)]
+enum Source {
+    /// Given by the caller
+    Override,
+    /// Looked up in HCM
+    Hcm,
+    /// `HCM_PROJECTION_*` fallback
+    Configured,
+}
+
+impl Source {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Override => "override",
+            Self::Hcm => "hcm",
+            Self::Configured => "configured",
+        }
+    }
+}
 
 pub async fn get_projected_balance(
-    Parameters(AbsenceBalanceRequest {
-        hcm_person_id,
-        legal_entity_id,
-        absence_type_id,
-        balance_as_of_date,
-    }): Parameters<AbsenceBalanceRequest>,
+    Parameters(ProjectedBalanceRequest {
+        balance: AbsenceBalanceRequest {
+            hcm_person_id,
+            legal_entity_id,
+            absence_type_id,
+            balance_as_of_date,
+        },
+        uom,
+        hours_per_day,
+    }): Parameters<ProjectedBalanceRequest>,
+    caller: Option<&Caller>,
 ) -> Result<CallToolResult, ErrorData> {
     if hcm_person_id.is_empty() {
         return Err(ErrorData::new(
@@ -39,6 +114,24 @@ pub async fn get_projected_balance(
         ));
     }
 
+    if hours_per_day.is_some_and(|hours| !(hours > 0.0 && hours <= 24.0)) {
+        return Err(ErrorData::new(
+            ErrorCode::INVALID_PARAMS,
+            "Hours per day must be more than 0 and at most 24.".to_string(),
+            None,
+        ));
+    }
+    let uom = uom.map(|uom| uom.trim().to_uppercase());
+    if uom.as_deref().is_some_and(|uom| !matches!(uom, "H" | "D")) {
+        return Err(ErrorData::new(
+            ErrorCode::INVALID_PARAMS,
+            "Unit of measure must be H (hours) or D (days).".to_string(),
+            None,
+        ));
+    }
+
+    authorize(caller, &hcm_person_id).await?;
+
     let formatted_balance_as_of_date = balance_as_of_date
         .as_ref()
         .and_then(|d| NaiveDate::parse_from_str(d, "%d-%m-%Y").ok())
@@ -46,6 +139,25 @@ pub async fn get_projected_balance(
         .format("%Y-%m-%d")
         .to_string();
 
+    // HCM is only asked for what the caller didn't give
+    let hcm_uom = match uom {
+        Some(_) => None,
+        None => absence_type_uom(&hcm_person_id, absence_type_id.as_deref()).await,
+    };
+    let (uom, uom_source) = choose(uom, hcm_uom, tuning().projection_uom.to_string());
+
+    // A day-based projection is for one day, whatever the worker's hours
+    let (duration, duration_source) = if uom == "H" {
+        let hcm_hours = match hours_per_day {
+            Some(_) => None,
+            None => working_hours_per_day(&hcm_person_id).await,
+        };
+        choose(hours_per_day, hcm_hours, tuning().projection_hours_per_day)
+    } else {
+        (1.0, uom_source)
+    };
+    debug!(%uom, uom_source = uom_source.as_str(), duration, duration_source = duration_source.as_str(), "projection duration");
+
     let request_body = json!({
         "entry": {
             "personId": hcm_person_id,
@@ -54,10 +166,10 @@ pub async fn get_projected_balance(
             "openEndedFlag": "N",
             "startDate": formatted_balance_as_of_date,
             "endDate": formatted_balance_as_of_date,
-            "uom": "H",
-            "duration": 7.6,
-            "startDateDuration": 7.6,
-            "endDateDuration": 7.6
+            "uom": uom,
+            "duration": duration,
+            "startDateDuration": duration,
+            "endDateDuration": duration
         }
     });
 
@@ -67,8 +179,12 @@ pub async fn get_projected_balance(
         "/absences/action/loadProjectedBalance",
         Method::POST,
         Some(body),
-        true,
-        Some(Duration::from_secs(60)),
+        // loadProjectedBalance only calculates, so repeating it is safe
+        // Runs in its own pool so slow projections can't starve lookups
+        CallOptions::new()
+            .timeout(Duration::from_mins(1))
+            .retry_non_idempotent()
+            .bulkhead(Bulkhead::Projection),
     )
     .await?;
 
@@ -84,6 +200,177 @@ pub async fn get_projected_balance(
 
     Ok(CallToolResult::structured(json!({
         "absence_type_id": absence_type_id,
-        "projected_balance": projected_balance
+        "projected_balance": projected_balance,
+        "uom": uom,
+        "uom_source": uom_source.as_str(),
+        "duration": duration,
+        "duration_source": duration_source.as_str()
     })))
-}
\ No newline at end of file
+}
+
+/// Picks the caller's override, else HCM's value, else the configured fallback, with where it came from.
+fn choose<T>(override_value: Option<T>, hcm: Option<T>, configured: T) -> (T, Source) {
+    match (override_value, hcm) {
+        (Some(value), _) => (value, Source::Override),
+        (None, Some(value)) => (value, Source::Hcm),
+        (None, None) => (configured, Source::Configured),
+    }
+}
+
+/// The unit of measure of an absence type available to the worker, from
+/// `absenceTypesLOV`. `None` if it can't be determined.
+async fn absence_type_uom(person_id: &str, absence_type_id: Option<&str>) -> Option<String> {
+    let absence_type_id = absence_type_id?;
+    let query = HcmQuery::new("/absenceTypesLOV")
+        .finder("findByWord", [("PersonId", person_id)])
+        .fields(&["AbsenceTypeId", "UOM"])
+        .only_data();
+
+    let types = match hcm_collection(query, PageOptions::default()).await {
+        Ok(types) => types,
+        Err(e) => {
+            warn!("Failed to look up the absence type's unit of measure, using HCM_PROJECTION_UOM: {e}");
+            return None;
+        }
+    };
+    types
+        .items
+        .iter()
+        .find(|item| id_str(&item["AbsenceTypeId"]).as_deref() == Some(absence_type_id))
+        .and_then(|item| item["UOM"].as_str())
+        .map(|uom| uom.trim().to_uppercase())
+        .filter(|uom| !uom.is_empty())
+}
+
+/// The standard working hours per day of the worker's primary assignment,
+/// from `workers`. `None` if HCM has no usable hours.
+async fn working_hours_per_day(person_id: &str) -> Option<f64> {
+    let query = HcmQuery::new("/workers")
+        .filter(Filter::new().eq("PersonId", person_id))
+        .expand(&["workRelationships.assignments"])
+        .only_data()
+        .limit(1);
+    let options = CallOptions::for_query(&query);
+    let json = match hcm_api_call(&query.build(), Method::GET, None, options).await {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Failed to look up the worker's working hours, using HCM_PROJECTION_HOURS_PER_DAY: {e}");
+            return None;
+        }
+    };
+
+    // Prefer the primary assignment of the primary work relationship
+    let relationships: Vec<&Value> = child_items(&json["items"][0]["workRelationships"]).collect();
+    let assignments: Vec<&Value> = relationships
+        .iter()
+        .filter(|relationship| relationship["PrimaryFlag"].as_bool() == Some(true))
+        .chain(relationships.iter())
+        .flat_map(|relationship| child_items(&relationship["assignments"]))
+        .collect();
+    let assignment = assignments
+        .iter()
+        .find(|assignment| assignment["PrimaryAssignmentFlag"].as_bool() == Some(true))
+        .or_else(|| assignments.first())?;
+
+    let hours = assignment["WorkingHours"].as_f64().filter(|hours| *hours > 0.0)?;
+    let frequency = assignment["Frequency"].as_str()?;
+    // Only weekly hours need the schedule's working days
+    let days_per_week = match (frequency, id_str(&assignment["AssignmentId"])) {
+        ("W", Some(assignment_id)) => schedule_days_per_week(&assignment_id).await,
+        _ => None,
+    };
+    hours_per_day(hours, frequency, days_per_week)
+}
+
+/// Working days per week of the work schedule assigned to an assignment,
+/// from `workScheduleAssignments`. `None` if it has no schedule or the lookup fails.
+async fn schedule_days_per_week(assignment_id: &str) -> Option<f64> {
+    let query = HcmQuery::new("/workScheduleAssignments")
+        .filter(Filter::new().eq("AssignmentId", assignment_id.to_string()))
+        .fields(&["WorkDaysPerWeek"])
+        .only_data()
+        .limit(1);
+    let options = CallOptions::for_query(&query);
+    match hcm_api_call(&query.build(), Method::GET, None, options).await {
+        Ok(json) => json["items"][0]["WorkDaysPerWeek"]
+            .as_f64()
+            .filter(|days| (1.0..=7.0).contains(days)),
+        Err(e) => {
+            warn!("Failed to look up the assignment's work schedule, spreading weekly hours over 5 days: {e}");
+            None
+        }
+    }
+}
+
+/// Converts an assignment's working hours at `frequency` ("D" daily or "W" weekly) to hours per day,
+/// spreading weekly hours over the schedule's working days, or five without a schedule.
+fn hours_per_day(hours: f64, frequency: &str, days_per_week: Option<f64>) -> Option<f64> {
+    let per_day = match frequency {
+        "D" => hours,
+        "W" => hours / days_per_week.unwrap_or(DEFAULT_WORKING_DAYS_PER_WEEK),
+        // Monthly or yearly hours don't say how long a working day is
+        _ => return None,
+    };
+    // Durations are entered to the hundredth of an hour
+    Some((per_day * 100.0).round() / 100.0).filter(|hours| *hours <= 24.0)
+}
+
+/// Reads an ID that HCM may serialize as a number or a string.
+fn id_str(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn override_wins_over_hcm_and_configured() {
+        let (uom, source) = choose(Some("D".to_string()), Some("H".to_string()), "H".to_string());
+        assert_eq!(uom, "D");
+        assert_eq!(source.as_str(), "override");
+
+        let (hours, source) = choose(Some(6.0_f64), None, 7.6);
+        assert!((hours - 6.0).abs() < f64::EPSILON);
+        assert_eq!(source.as_str(), "override");
+    }
+
+    #[test]
+    fn hcm_value_is_used_without_an_override() {
+        let (hours, source) = choose(None, Some(8.0_f64), 7.6);
+        assert!((hours - 8.0).abs() < f64::EPSILON);
+        assert_eq!(source.as_str(), "hcm");
+    }
+
+    #[test]
+    fn configured_value_is_the_last_resort() {
+        let (uom, source) = choose(None, None, "H".to_string());
+        assert_eq!(uom, "H");
+        assert_eq!(source.as_str(), "configured");
+    }
+
+    #[test]
+    fn weekly_hours_are_spread_over_the_schedule_working_days() {
+        assert_eq!(hours_per_day(38.0, "W", Some(4.0)), Some(9.5));
+        assert_eq!(hours_per_day(22.8, "W", Some(3.0)), Some(7.6));
+    }
+
+    #[test]
+    fn weekly_hours_without_a_schedule_are_spread_over_five_days() {
+        assert_eq!(hours_per_day(38.0, "W", None), Some(7.6));
+    }
+
+    #[test]
+    fn daily_hours_ignore_the_schedule() {
+        assert_eq!(hours_per_day(7.5, "D", Some(4.0)), Some(7.5));
+    }
+
+    #[test]
+    fn monthly_hours_or_impossible_days_are_unknown() {
+        assert_eq!(hours_per_day(160.0, "M", None), None);
+        assert_eq!(hours_per_day(30.0, "D", None), None);
+    }
+}
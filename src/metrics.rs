@@ -0,0 +1,255 @@
+//! Prometheus metrics for MCP tool calls and Oracle HCM requests.
+//!
+//! Served in the Prometheus text format on `GET /metrics` (see `server`):
+//! - `mcp_tool_calls_total` / `mcp_tool_call_duration_seconds`: Tool calls by `tool` and `outcome`
+//!   (`ok`, `tool_error`, or the error kind, e.g. `forbidden`)
+//! - `mcp_active_sessions`: Open streamable HTTP sessions
+//! - `hcm_request_duration_seconds`: HCM calls by `resource` and `status` (the final HTTP
+//!   status after retries, or `timeout`, `connect` or `error` when no response arrived)
+//! - `hcm_retries_total`: Retried HCM requests by `reason` (status or failure)
+//! - `hcm_rejected_calls_total`: Calls refused before reaching HCM by `reason`
+//!   (`breaker_open`, `rate_limited`, `bulkhead_full`)
+//! - `hcm_breaker_state`: Circuit breaker state by HCM `environment`, 0 closed, 1 open, 2 half-open
+//! - `hcm_breaker_transitions_total`: Breaker state changes by `environment` and `state`
+//! - `hcm_cache_lookups_total`: Response cache lookups by `resource` and `result` (`hit`, `miss`,
+//!   or `stale` when an expired response is revalidated with its `ETag`)
+//! - `hcm_cache_revalidations_total`: Conditional requests by `resource` and `result`
+//!   (`not_modified` when HCM answered 304, otherwise `modified`)
+//! - `hcm_cache_entries`: Cached HCM responses
+//! - `hcm_coalesced_requests_total`: `GET`s answered by an identical in-flight request, by `resource`
+//!
+//! Labels only carry tool, resource and environment names, statuses and error kinds, never
+//! request data, so series stay bounded and free of PII.
+
+use anyhow::Result;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use reqwest::Response;
+use reqwest_middleware::Error;
+use rmcp::{ErrorData, model::CallToolResult};
+use std::{
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use crate::mcp::error::outcome;
+
+/// Latency buckets in seconds, from quick lookups up to slow projections.
+const DURATION_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Circuit breaker states as reported by `hcm_breaker_state`.
+#[derive(Debug, Clone, Copy)]
+pub enum BreakerState {
+    Closed = 0,
+    Open = 1,
+    HalfOpen = 2,
+}
+
+impl BreakerState {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Closed => "closed",
+            Self::Open => "open",
+            Self::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct Metrics {
+    registry: Registry,
+    tool_calls: IntCounterVec,
+    tool_duration: HistogramVec,
+    active_sessions: IntGauge,
+    hcm_duration: HistogramVec,
+    retries: IntCounterVec,
+    rejections: IntCounterVec,
+    breaker_state: IntGaugeVec,
+    breaker_transitions: IntCounterVec,
+    cache_lookups: IntCounterVec,
+    cache_revalidations: IntCounterVec,
+    cache_entries: IntGauge,
+    coalesced: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let histogram = |name: &str, help: &str| {
+            HistogramOpts::new(name, help).buckets(DURATION_BUCKETS.to_vec())
+        };
+        let metrics = Self {
+            registry: Registry::new(),
+            tool_calls: IntCounterVec::new(
+                Opts::new("mcp_tool_calls_total", "MCP tool calls"),
+                &["tool", "outcome"],
+            )?,
+            tool_duration: HistogramVec::new(
+                histogram("mcp_tool_call_duration_seconds", "MCP tool call latency"),
+                &["tool", "outcome"],
+            )?,
+            active_sessions: IntGauge::new("mcp_active_sessions", "Open MCP sessions")?,
+            hcm_duration: HistogramVec::new(
+                histogram("hcm_request_duration_seconds", "Oracle HCM request latency, including retries"),
+                &["resource", "status"],
+            )?,
+            retries: IntCounterVec::new(
+                Opts::new("hcm_retries_total", "Retried Oracle HCM requests"),
+                &["reason"],
+            )?,
+            rejections: IntCounterVec::new(
+                Opts::new("hcm_rejected_calls_total", "HCM calls refused before reaching HCM"),
+                &["reason"],
+            )?,
+            breaker_state: IntGaugeVec::new(
+                Opts::new("hcm_breaker_state", "HCM circuit breaker state (0 closed, 1 open, 2 half-open)"),
+                &["environment"],
+            )?,
+            breaker_transitions: IntCounterVec::new(
+                Opts::new("hcm_breaker_transitions_total", "HCM circuit breaker state changes"),
+                &["environment", "state"],
+            )?,
+            cache_lookups: IntCounterVec::new(
+                Opts::new("hcm_cache_lookups_total", "HCM response cache lookups"),
+                &["resource", "result"],
+            )?,
+            cache_revalidations: IntCounterVec::new(
+                Opts::new("hcm_cache_revalidations_total", "Conditional HCM requests for expired cache entries"),
+                &["resource", "result"],
+            )?,
+            cache_entries: IntGauge::new("hcm_cache_entries", "Cached HCM responses")?,
+            coalesced: IntCounterVec::new(
+                Opts::new("hcm_coalesced_requests_total", "HCM GETs answered by an identical in-flight request"),
+                &["resource"],
+            )?,
+        };
+
+        metrics.registry.register(Box::new(metrics.tool_calls.clone()))?;
+        metrics.registry.register(Box::new(metrics.tool_duration.clone()))?;
+        metrics.registry.register(Box::new(metrics.active_sessions.clone()))?;
+        metrics.registry.register(Box::new(metrics.hcm_duration.clone()))?;
+        metrics.registry.register(Box::new(metrics.retries.clone()))?;
+        metrics.registry.register(Box::new(metrics.rejections.clone()))?;
+        metrics.registry.register(Box::new(metrics.breaker_state.clone()))?;
+        metrics.registry.register(Box::new(metrics.breaker_transitions.clone()))?;
+        metrics.registry.register(Box::new(metrics.cache_lookups.clone()))?;
+        metrics.registry.register(Box::new(metrics.cache_revalidations.clone()))?;
+        metrics.registry.register(Box::new(metrics.cache_entries.clone()))?;
+        metrics.registry.register(Box::new(metrics.coalesced.clone()))?;
+        Ok(metrics)
+    }
+}
+
+/// All series, registered on first use. Recording is skipped if registration failed.
+static METRICS: LazyLock<prometheus::Result<Metrics>> = LazyLock::new(Metrics::new);
+
+/// Renders every series in the Prometheus text exposition format.
+///
+/// # Errors
+/// Returns an error if the metrics couldn't be registered or encoded.
+pub fn render() -> Result<String> {
+    let metrics = METRICS.as_ref().map_err(|e| anyhow::anyhow!("Metrics unavailable: {e}"))?;
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&metrics.registry.gather(), &mut buffer)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Content type of [`render`]'s output.
+pub const CONTENT_TYPE: &str = prometheus::TEXT_FORMAT;
+
+/// Runs a tool call, counting it and timing it by outcome.
+pub async fn observe_tool_call<F>(tool: &'static str, call: F) -> Result<CallToolResult, ErrorData>
+where
+    F: Future<Output = Result<CallToolResult, ErrorData>>,
+{
+    let started = Instant::now();
+    let result = call.await;
+    if let Ok(metrics) = METRICS.as_ref() {
+        let outcome = outcome(&result);
+        metrics.tool_calls.with_label_values(&[tool, outcome]).inc();
+        metrics
+            .tool_duration
+            .with_label_values(&[tool, outcome])
+            .observe(started.elapsed().as_secs_f64());
+    }
+    result
+}
+
+/// Sets the number of open MCP sessions.
+pub fn set_active_sessions(count: usize) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.active_sessions.set(i64::try_from(count).unwrap_or(i64::MAX));
+    }
+}
+
+/// Records one HCM call to `resource` and how it ended.
+pub fn record_hcm_request(resource: &str, outcome: &Result<Response, Error>, elapsed: Duration) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics
+            .hcm_duration
+            .with_label_values(&[resource, &status_label(outcome)])
+            .observe(elapsed.as_secs_f64());
+    }
+}
+
+/// Records a retry of a failed HCM request.
+pub fn record_retry(outcome: &Result<Response, Error>) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.retries.with_label_values(&[&status_label(outcome)]).inc();
+    }
+}
+
+/// Records an HCM call refused by the breaker, a rate limit or a bulkhead.
+pub fn record_rejection(reason: &'static str) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.rejections.with_label_values(&[reason]).inc();
+    }
+}
+
+/// Records a state change of `environment`'s circuit breaker.
+pub fn record_breaker_state(environment: &str, state: BreakerState) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.breaker_state.with_label_values(&[environment]).set(state as i64);
+        metrics.breaker_transitions.with_label_values(&[environment, state.name()]).inc();
+    }
+}
+
+/// Records a response cache lookup for `resource`: `hit`, `stale` or `miss`.
+pub fn record_cache_lookup(resource: &str, result: &'static str) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.cache_lookups.with_label_values(&[resource, result]).inc();
+    }
+}
+
+/// Records whether HCM confirmed a stale cache entry with `304 Not Modified`.
+pub fn record_cache_revalidation(resource: &str, not_modified: bool) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        let result = if not_modified { "not_modified" } else { "modified" };
+        metrics.cache_revalidations.with_label_values(&[resource, result]).inc();
+    }
+}
+
+/// Sets the number of cached HCM responses.
+pub fn set_cache_entries(count: usize) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.cache_entries.set(i64::try_from(count).unwrap_or(i64::MAX));
+    }
+}
+
+/// Records a `GET` that shared an identical in-flight request instead of calling HCM.
+pub fn record_coalesced(resource: &str) {
+    if let Ok(metrics) = METRICS.as_ref() {
+        metrics.coalesced.with_label_values(&[resource]).inc();
+    }
+}
+
+/// The HTTP status code, or the kind of failure when no response arrived.
+fn status_label(outcome: &Result<Response, Error>) -> String {
+    match outcome {
+        Ok(response) => response.status().as_u16().to_string(),
+        Err(Error::Reqwest(e)) if e.is_timeout() => "timeout".to_string(),
+        Err(Error::Reqwest(e)) if e.is_connect() => "connect".to_string(),
+        Err(_) => "error".to_string(),
+    }
+}
@@ -0,0 +1,452 @@
+//! Bearer-token authentication for the `/mcp` endpoint.
+//!
+//! Every request must carry an `Authorization: Bearer <jwt>` header. The token
+//! is validated against a JWKS (fetched from a URL or loaded from a local file):
+//! - **Signature**: verified with the JWKS key matching the token's `kid`; when
+//!   the key declares an `alg`, the token must be signed with exactly that algorithm
+//! - **Issuer / audience**: must match the configured values
+//! - **Expiry**: `exp` is required; `nbf` is honoured when present
+//!
+//! Only asymmetric algorithms are accepted, so a JWKS can never be used as an
+//! HMAC secret. Unknown `kid`s trigger a (rate-limited) JWKS refresh so that
+//! signing-key rotation at the identity provider is picked up automatically.
+//!
+//! On success the validated identity is inserted into the request extensions
+//! as a [`Caller`], where the MCP handlers pick it up.
+
+use anyhow::{Context, Result, anyhow};
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header,
+    jwk::{Jwk, JwkSet},
+};
+use serde_json::{Map, Value, json};
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::Instant,
+};
+use tracing::{debug, info, warn};
+
+use crate::mcp::caller::Caller;
+
+/// Minimum time between JWKS refreshes triggered by an unknown `kid`
+const JWKS_MIN_REFRESH_INTERVAL: Duration = Duration::from_mins(1);
+
+/// JWKS entries are refreshed at least this often, even if all `kid`s are known
+const JWKS_MAX_AGE: Duration = Duration::from_hours(1);
+
+/// Where the signing keys come from.
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    Url(String),
+    File(PathBuf),
+}
+
+/// Settings for inbound bearer-token validation.
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub jwks: JwksSource,
+    pub issuer: String,
+    pub audience: String,
+    /// Clock skew tolerated for `exp`/`nbf`
+    pub leeway: Duration,
+}
+
+/// Cached signing keys and when they were loaded.
+struct CachedJwks {
+    keys: JwkSet,
+    loaded_at: Instant,
+}
+
+/// Validates bearer tokens against the configured JWKS, issuer and audience.
+pub struct JwtValidator {
+    config: AuthConfig,
+    http: reqwest::Client,
+    jwks: RwLock<CachedJwks>,
+    /// When the JWKS was last fetched or tried; held while refreshing, so one
+    /// request fetches while the others keep validating with the cached keys
+    refreshing: Mutex<Instant>,
+}
+
+impl JwtValidator {
+    /// Creates a validator and loads the JWKS once, failing fast if it is unreachable or invalid.
+    pub async fn new(config: AuthConfig) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to build JWKS HTTP client")?;
+        let keys = fetch_jwks(&http, &config.jwks).await?;
+        info!("Loaded {} signing key(s) for bearer-token authentication", keys.keys.len());
+
+        Ok(Self {
+            config,
+            http,
+            jwks: RwLock::new(CachedJwks { keys, loaded_at: Instant::now() }),
+            refreshing: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Validates a raw bearer token and returns the caller it identifies.
+    pub async fn validate(&self, token: &str) -> Result<Caller> {
+        let header = decode_header(token).map_err(|e| anyhow!("Malformed token: {e}"))?;
+        if !is_asymmetric(header.alg) {
+            return Err(anyhow!("Unsupported token algorithm {:?}", header.alg));
+        }
+        let kid = header.kid.ok_or_else(|| anyhow!("Token header has no kid"))?;
+
+        let (key, key_alg) = self.decoding_key(&kid).await?;
+        // The header is attacker-controlled; the key's own `alg` is authoritative.
+        if let Some(key_alg) = key_alg
+            && key_alg != header.alg
+        {
+            return Err(anyhow!(
+                "Token algorithm {:?} does not match key {kid} ({key_alg:?})",
+                header.alg
+            ));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+        validation.validate_nbf = true;
+        validation.leeway = self.config.leeway.as_secs();
+
+        let data = decode::<Map<String, Value>>(token, &key, &validation)
+            .map_err(|e| anyhow!("Token rejected: {e}"))?;
+        let subject = data
+            .claims
+            .get("sub")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Token has no string sub claim"))?
+            .to_string();
+
+        Ok(Caller {
+            subject,
+            claims: Arc::new(data.claims),
+            token: Arc::from(token),
+        })
+    }
+
+    /// Finds the decoding key for `kid`, refreshing the JWKS if it is unknown or stale.
+    ///
+    /// Also returns the algorithm the key is pinned to, if the JWKS declares one.
+    async fn decoding_key(&self, kid: &str) -> Result<(DecodingKey, Option<Algorithm>)> {
+        {
+            let cached = self.jwks.read().await;
+            if cached.loaded_at.elapsed() < JWKS_MAX_AGE
+                && let Some(jwk) = cached.keys.find(kid)
+            {
+                return key_entry(kid, jwk);
+            }
+        }
+
+        let mut attempted_at = self.refreshing.lock().await;
+        // Re-check after waiting: another request may have refreshed already.
+        // Unknown kids only trigger a refetch once per interval, so forged tokens
+        // cannot be used to hammer the identity provider.
+        let needs_refresh = {
+            let cached = self.jwks.read().await;
+            cached.loaded_at.elapsed() >= JWKS_MAX_AGE || cached.keys.find(kid).is_none()
+        };
+        if needs_refresh && attempted_at.elapsed() >= JWKS_MIN_REFRESH_INTERVAL {
+            debug!("Refreshing JWKS (kid {kid})");
+            *attempted_at = Instant::now();
+            // Fetched without the key lock, so validation with known keys isn't blocked
+            match fetch_jwks(&self.http, &self.config.jwks).await {
+                Ok(keys) => *self.jwks.write().await = CachedJwks { keys, loaded_at: Instant::now() },
+                // Keep serving the previous keys if the identity provider is briefly unreachable
+                Err(e) => warn!("JWKS refresh failed, keeping cached keys: {e}"),
+            }
+        }
+        drop(attempted_at);
+
+        let cached = self.jwks.read().await;
+        let key = cached
+            .keys
+            .find(kid)
+            .ok_or_else(|| anyhow!("Unknown signing key {kid}"))
+            .and_then(|jwk| key_entry(kid, jwk));
+        drop(cached);
+        key
+    }
+}
+
+/// Builds the decoding key for a JWK together with its declared signing algorithm.
+fn key_entry(kid: &str, jwk: &Jwk) -> Result<(DecodingKey, Option<Algorithm>)> {
+    let key = DecodingKey::from_jwk(jwk).map_err(|e| anyhow!("Invalid JWK {kid}: {e}"))?;
+    Ok((key, pinned_algorithm(kid, jwk)?))
+}
+
+/// Returns the signing algorithm a JWK is restricted to by its `alg` member.
+///
+/// Keys declared for encryption (e.g. `RSA-OAEP`) are rejected outright.
+fn pinned_algorithm(kid: &str, jwk: &Jwk) -> Result<Option<Algorithm>> {
+    jwk.common
+        .key_algorithm
+        .map(|alg| {
+            Algorithm::from_str(&alg.to_string())
+                .map_err(|_| anyhow!("JWK {kid} is not a signing key (alg {alg})"))
+        })
+        .transpose()
+}
+
+/// Returns `true` for signature algorithms backed by a public key.
+const fn is_asymmetric(alg: Algorithm) -> bool {
+    !matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+/// Loads a JWKS document from its source.
+async fn fetch_jwks(http: &reqwest::Client, source: &JwksSource) -> Result<JwkSet> {
+    match source {
+        JwksSource::Url(url) => http
+            .get(url)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| format!("Failed to fetch JWKS from {url}"))?
+            .json::<JwkSet>()
+            .await
+            .with_context(|| format!("Invalid JWKS document at {url}")),
+        JwksSource::File(path) => {
+            let text = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read JWKS file {}", path.display()))?;
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid JWKS document in {}", path.display()))
+        }
+    }
+}
+
+/// Axum middleware rejecting requests without a valid bearer token.
+///
+/// Successful requests continue with a [`Caller`] in their extensions.
+pub async fn require_bearer(
+    State(validator): State<Arc<JwtValidator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+
+    let Some(token) = token else {
+        return unauthorized("missing bearer token", "invalid_request");
+    };
+
+    match validator.validate(token).await {
+        Ok(caller) => {
+            debug!(subject = %caller.subject, "bearer token accepted");
+            request.extensions_mut().insert(caller);
+            next.run(request).await
+        }
+        Err(e) => {
+            warn!("Rejected bearer token: {e}");
+            unauthorized("invalid bearer token", "invalid_token")
+        }
+    }
+}
+
+/// Extracts the token from an `Authorization` value using the `Bearer` scheme.
+///
+/// Auth schemes are case-insensitive (RFC 7235 §2.1).
+fn bearer_token(value: &str) -> Option<&str> {
+    let (scheme, token) = value.trim_start().split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Builds a 401 response with an RFC 6750 `WWW-Authenticate` challenge.
+fn unauthorized(message: &str, error: &'static str) -> Response {
+    let mut response = (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({ "error": error, "error_description": message })),
+    )
+        .into_response();
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static(match error {
+            "invalid_request" => "Bearer error=\"invalid_request\"",
+            _ => "Bearer error=\"invalid_token\"",
+        }),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwk(alg: Option<&str>) -> Jwk {
+        let mut value = json!({
+            "kty": "RSA",
+            "kid": "k1",
+            "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw",
+            "e": "AQAB",
+        });
+        if let Some(alg) = alg {
+            value["alg"] = json!(alg);
+        }
+        serde_json::from_value(value).unwrap_or_else(|e| unreachable!("test JWK: {e}"))
+    }
+
+    #[test]
+    fn bearer_scheme_is_case_insensitive() {
+        assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("bearer abc"), Some("abc"));
+        assert_eq!(bearer_token("BEARER  abc "), Some("abc"));
+        assert_eq!(bearer_token("bEaReR abc"), Some("abc"));
+    }
+
+    #[test]
+    fn other_schemes_and_empty_tokens_are_rejected() {
+        assert_eq!(bearer_token("Basic dXNlcjpwYXNz"), None);
+        assert_eq!(bearer_token("Bearer"), None);
+        assert_eq!(bearer_token("Bearer   "), None);
+        assert_eq!(bearer_token("Bearerabc"), None);
+    }
+
+    #[test]
+    fn key_alg_pins_the_accepted_algorithm() -> Result<()> {
+        assert_eq!(pinned_algorithm("k1", &jwk(Some("RS256")))?, Some(Algorithm::RS256));
+        assert_eq!(pinned_algorithm("k1", &jwk(Some("PS512")))?, Some(Algorithm::PS512));
+        assert_eq!(pinned_algorithm("k1", &jwk(None))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn encryption_keys_are_not_signing_keys() {
+        assert!(pinned_algorithm("k1", &jwk(Some("RSA-OAEP"))).is_err());
+    }
+
+    const SIGNING_KEY: &str = include_str!("../mcp/http/testdata/jwt_assertion_key.pem");
+    /// Modulus of `SIGNING_KEY`'s public key
+    const MODULUS: &str = "yL2HjTlwvvbOIFlE1pjcuRFn80CQjxd5EgalzflznzMsl1Fu6qkgBFeAPnONVecpP_NjEyMwUIWj5n_P_brrg_KqIy-v5r5TqdrwwH_oqNeZTVVyI2YvTj27qSjWe6vf-pmP1ddL7ueNyZof1LShPy8spYLwCCKlu3FkFb7he-SZkz_IuPH5tDg1NGdRKQbRzTdYyKedi2Pv0dk2twN-HOTKnzFV2T-t2CPHL09O4FlsU_FBmuGYG-gSzlkyC5qpOsi6ho2n9qTLb5JIuukXX4JogrHCvzYiKA5M3XSGV5GErvAgkT3LVa8gqO8neiAA7p0CTLdoZ_HmgFGhGQjDOQ";
+
+    /// A JWKS file publishing the test key under each of `kids`, rewritten by [`Self::publish`].
+    struct JwksFile(PathBuf);
+
+    impl JwksFile {
+        fn new(test: &str, kids: &[&str]) -> Self {
+            let file = Self(std::env::temp_dir().join(format!("oracle-hcm-mcp-jwks-{}-{test}.json", std::process::id())));
+            file.publish(kids);
+            file
+        }
+
+        fn publish(&self, kids: &[&str]) {
+            let keys: Vec<Value> = kids
+                .iter()
+                .map(|kid| json!({ "kty": "RSA", "kid": kid, "alg": "RS256", "n": MODULUS, "e": "AQAB" }))
+                .collect();
+            std::fs::write(&self.0, json!({ "keys": keys }).to_string())
+                .unwrap_or_else(|e| unreachable!("write JWKS: {e}"));
+        }
+    }
+
+    impl Drop for JwksFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    async fn validator(jwks: &JwksFile) -> JwtValidator {
+        JwtValidator::new(AuthConfig {
+            jwks: JwksSource::File(jwks.0.clone()),
+            issuer: "https://idp.example.com".to_string(),
+            audience: "oracle-hcm-mcp".to_string(),
+            leeway: Duration::from_secs(30),
+        })
+        .await
+        .unwrap_or_else(|e| unreachable!("validator: {e}"))
+    }
+
+    /// A token signed with the test key, with valid claims overridden by `claims`.
+    fn token(kid: &str, claims: &Value) -> String {
+        let mut all = json!({
+            "iss": "https://idp.example.com",
+            "aud": "oracle-hcm-mcp",
+            "sub": "alice",
+            "exp": chrono::Utc::now().timestamp() + 600,
+        });
+        if let (Some(all), Some(claims)) = (all.as_object_mut(), claims.as_object()) {
+            all.extend(claims.clone());
+        }
+        let mut header = jsonwebtoken::Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let key = jsonwebtoken::EncodingKey::from_rsa_pem(SIGNING_KEY.as_bytes())
+            .unwrap_or_else(|e| unreachable!("test key: {e}"));
+        jsonwebtoken::encode(&header, &all, &key).unwrap_or_else(|e| unreachable!("sign: {e}"))
+    }
+
+    #[tokio::test]
+    async fn valid_token_identifies_the_caller() -> Result<()> {
+        let jwks = JwksFile::new("valid", &["k1"]);
+        let caller = validator(&jwks).await.validate(&token("k1", &json!({}))).await?;
+        assert_eq!(caller.subject, "alice");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wrong_issuer_audience_or_expiry_is_rejected() {
+        let jwks = JwksFile::new("claims", &["k1"]);
+        let validator = validator(&jwks).await;
+        let expired = chrono::Utc::now().timestamp() - 60;
+
+        for claims in [
+            json!({ "iss": "https://other-idp.example.com" }),
+            json!({ "aud": "another-service" }),
+            json!({ "exp": expired }),
+            json!({ "exp": null }),
+        ] {
+            let result = validator.validate(&token("k1", &claims)).await;
+            assert!(result.is_err(), "accepted a token with {claims}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unknown_kids_refresh_the_jwks_at_most_once_per_interval() -> Result<()> {
+        let jwks = JwksFile::new("rotation", &["k1"]);
+        let validator = validator(&jwks).await;
+        jwks.publish(&["k1", "k2"]);
+
+        // Loaded moments ago, so the rotated key isn't fetched yet
+        assert!(validator.validate(&token("k2", &json!({}))).await.is_err());
+
+        tokio::time::advance(JWKS_MIN_REFRESH_INTERVAL).await;
+        validator.validate(&token("k2", &json!({}))).await?;
+
+        // A forged kid right after the refresh doesn't fetch again
+        jwks.publish(&["k1", "k2", "k3"]);
+        assert!(validator.validate(&token("forged", &json!({}))).await.is_err());
+        assert!(validator.validate(&token("k3", &json!({}))).await.is_err());
+
+        tokio::time::advance(JWKS_MIN_REFRESH_INTERVAL).await;
+        validator.validate(&token("k3", &json!({}))).await?;
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_refresh_keeps_the_cached_keys() -> Result<()> {
+        let jwks = JwksFile::new("failed", &["k1"]);
+        let validator = validator(&jwks).await;
+        std::fs::write(&jwks.0, "not a JWKS").unwrap_or_else(|e| unreachable!("write: {e}"));
+
+        tokio::time::advance(JWKS_MAX_AGE).await;
+        validator.validate(&token("k1", &json!({}))).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+//! Liveness and readiness probes for container orchestrators.
+//!
+//! - `GET /healthz`: 200 whenever the process is serving requests
+//! - `GET /readyz`: 200 when tool calls can be served, 503 otherwise, with a
+//!   JSON report of each check's outcome, "ok" or "failed" (the errors are
+//!   only logged, as the route is unauthenticated):
+//!   - **config**: HCM configuration (base URL, authentication, delegation) is valid
+//!   - **hcm** (optional): a one-row `publicWorkers` lookup succeeds, proving
+//!     HCM is reachable and the credentials work; with several HCM environments
+//!     each is checked separately, as **hcm.<name>**
+//!
+//! Every environment's result is reported, but by default only the default
+//! environment's decides readiness, so a test pod being down doesn't take
+//! prod sessions out of rotation. `MCP_READYZ_REQUIRE=all` makes every
+//! environment required.
+//!
+//! Readiness results are cached, so frequent probes don't add load on HCM.
+//! The image has no curl, so [`probe`] backs the binary's `healthcheck`
+//! subcommand, which calls these routes on the local listener.
+
+use anyhow::{Context, Result, bail};
+use axum::{
+    Json, Router,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use clap::ValueEnum;
+use serde_json::{Map, Value, json};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::mcp::{
+    OracleHCMMCPFactory,
+    error::HcmError,
+    http::{CallOptions, HcmEnvironment, HcmQuery, Method, hcm_api_call},
+};
+
+/// How long the `healthcheck` subcommand waits for the server.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings for the readiness check.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthConfig {
+    /// Also check that HCM is reachable and the credentials work
+    pub check_hcm: bool,
+    /// Which environments' HCM checks must pass for the server to be ready
+    pub require: Required,
+    /// How long a readiness result is reused
+    pub cache_for: Duration,
+}
+
+/// HCM environments that must pass their check for `/readyz` to succeed.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Required {
+    /// Only `HCM_DEFAULT_ENVIRONMENT`; failures elsewhere are reported but don't fail readiness
+    Default,
+    /// Every configured environment
+    All,
+}
+
+impl Required {
+    /// Whether an environment's HCM check decides readiness.
+    const fn includes(self, is_default: bool) -> bool {
+        match self {
+            Self::Default => is_default,
+            Self::All => true,
+        }
+    }
+}
+
+/// Outcome of one environment's HCM check.
+struct HcmCheck {
+    /// Key in the report, "hcm" or "hcm.<name>"
+    name: String,
+    /// Whether a failure makes the server not ready
+    required: bool,
+    result: Result<(), String>,
+}
+
+/// Readiness checks with their last result.
+struct Readiness {
+    config: HealthConfig,
+    cached: Mutex<Option<(Instant, StatusCode, Value)>>,
+}
+
+impl Readiness {
+    /// Returns the cached report, or runs the checks if it has expired.
+    async fn report(&self) -> (StatusCode, Value) {
+        // Held while checking, so concurrent probes wait for one check instead of each calling HCM
+        let mut cached = self.cached.lock().await;
+        if let Some((checked_at, status, report)) = cached.as_ref()
+            && checked_at.elapsed() < self.config.cache_for
+        {
+            return (*status, report.clone());
+        }
+
+        let (status, report) = self.check().await;
+        *cached = Some((Instant::now(), status, report.clone()));
+        drop(cached);
+        (status, report)
+    }
+
+    async fn check(&self) -> (StatusCode, Value) {
+        let config = OracleHCMMCPFactory::new().map(|_| ()).map_err(|e| e.to_string());
+        let mut hcm = Vec::new();
+        if self.config.check_hcm {
+            let environments = HcmEnvironment::all().unwrap_or_default();
+            for environment in environments {
+                // Each environment is checked on its own, so one failing pod doesn't hide the others
+                let result = match environment.validate() {
+                    Ok(()) => HcmEnvironment::scope(environment, check_hcm()).await.map_err(|e| e.to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+                hcm.push(HcmCheck {
+                    name: match environments {
+                        [_] => "hcm".to_string(),
+                        _ => format!("hcm.{}", environment.name),
+                    },
+                    required: self.config.require.includes(environment.is_default()),
+                    result,
+                });
+            }
+        }
+        summarize(&config, hcm)
+    }
+}
+
+/// Builds the `/readyz` report: ready when the configuration is valid and every required HCM check passed.
+///
+/// `/readyz` is unauthenticated, so a failed check is reported only as "failed";
+/// the error, which can name hosts, settings or accounts, is logged instead.
+fn summarize(config: &Result<(), String>, hcm: Vec<HcmCheck>) -> (StatusCode, Value) {
+    let mut checks = Map::new();
+    let mut ready = true;
+    let checks_with_config = std::iter::once(("config".to_string(), true, config.clone()))
+        .chain(hcm.into_iter().map(|check| (check.name, check.required, check.result)));
+    for (name, required, result) in checks_with_config {
+        let status = match result {
+            Ok(()) => "ok",
+            Err(e) => {
+                if required {
+                    ready = false;
+                    warn!("Readiness check {name} failed: {e}");
+                } else {
+                    warn!("Optional readiness check {name} failed: {e}");
+                }
+                "failed"
+            }
+        };
+        checks.insert(name, json!(status));
+    }
+
+    if ready {
+        return (StatusCode::OK, json!({ "status": "ready", "checks": checks }));
+    }
+    (StatusCode::SERVICE_UNAVAILABLE, json!({ "status": "not_ready", "checks": checks }))
+}
+
+/// Makes the cheapest authenticated HCM call: one worker, one field, never from the cache.
+async fn check_hcm() -> Result<(), HcmError> {
+    let query = HcmQuery::new("/publicWorkers")
+        .fields(&["PersonId"])
+        .only_data()
+        .limit(1);
+    // There is no caller to delegate for, so the check runs as the service account
+    let options = CallOptions::for_query(&query).bypass_cache().service_account();
+    hcm_api_call(&query.build(), Method::GET, None, options).await?;
+    Ok(())
+}
+
+/// Routes for `/healthz` and `/readyz`.
+pub fn router(config: HealthConfig) -> Router {
+    let readiness = Arc::new(Readiness {
+        config,
+        cached: Mutex::new(None),
+    });
+    Router::new()
+        .route("/healthz", get(|| async { Json(json!({ "status": "alive" })) }))
+        .route("/readyz", get(readyz))
+        .with_state(readiness)
+}
+
+async fn readyz(State(readiness): State<Arc<Readiness>>) -> Response {
+    let (status, report) = readiness.report().await;
+    (status, Json(report)).into_response()
+}
+
+/// Calls a health route and fails unless it answers with a success status.
+///
+/// # Errors
+/// Returns an error if the server is unreachable or reports a failure.
+pub async fn probe(url: &str) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        // The listener's certificate names its public host, not the loopback address probed here
+        .danger_accept_invalid_certs(true)
+        .build()?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("{url} is unreachable"))?;
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        bail!("{url} returned {status}: {body}");
+    }
+    println!("{body}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, required: bool, result: Result<(), &str>) -> HcmCheck {
+        HcmCheck {
+            name: name.to_string(),
+            required,
+            result: result.map_err(str::to_string),
+        }
+    }
+
+    #[test]
+    fn only_the_default_environment_is_required_by_default() {
+        assert!(Required::Default.includes(true));
+        assert!(!Required::Default.includes(false));
+        assert!(Required::All.includes(true));
+        assert!(Required::All.includes(false));
+    }
+
+    #[test]
+    fn every_environment_is_reported_after_a_failure() {
+        let (status, report) = summarize(
+            &Ok(()),
+            vec![
+                check("hcm.test", false, Err("HCM is unavailable")),
+                check("hcm.prod", true, Ok(())),
+                check("hcm.dev", false, Ok(())),
+            ],
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(report["status"], "ready");
+        assert_eq!(report["checks"]["hcm.test"], "failed");
+        assert_eq!(report["checks"]["hcm.prod"], "ok");
+        assert_eq!(report["checks"]["hcm.dev"], "ok");
+    }
+
+    #[test]
+    fn failed_required_environment_is_not_ready() {
+        let (status, report) = summarize(
+            &Ok(()),
+            vec![check("hcm.test", false, Ok(())), check("hcm.prod", true, Err("Unauthorized"))],
+        );
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["status"], "not_ready");
+        assert_eq!(report["checks"]["hcm.test"], "ok");
+        assert_eq!(report["checks"]["hcm.prod"], "failed");
+    }
+
+    #[test]
+    fn invalid_config_is_not_ready() {
+        let (status, report) = summarize(&Err("Failed to load HCM_BASE_URL".to_string()), vec![]);
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(report["checks"]["config"], "failed");
+        assert!(!report.to_string().contains("HCM_BASE_URL"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Streamable HTTP server for the Oracle HCM MCP tools.
+//!
+//! Builds the axum router around the MCP service and runs it on the configured
+//! listener, either as plain HTTP or as native HTTPS via rustls (see `tls`).
+//! When configured, `/mcp` and `/cache/invalidate` require a valid bearer token (see `auth`),
+//! and `/cache/invalidate` also the HR-admin role (`MCP_AUTHZ_ADMIN_CLAIM`).
+//! `/metrics` serves Prometheus metrics (see `metrics`) and `/healthz` and
+//! `/readyz` serve probes (see `health`), all without authentication.
+
+pub mod auth;
+pub mod health;
+pub mod tls;
+
+use anyhow::Result;
+use axum::{
+    Extension, Json, Router,
+    extract::{Query, State},
+    http::{StatusCode, header::CONTENT_TYPE},
+    middleware::from_fn_with_state,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    serve,
+};
+use axum_server::Handle;
+use rmcp::transport::{
+    StreamableHttpServerConfig, StreamableHttpService,
+    streamable_http_server::session::local::LocalSessionManager,
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::{io::Error as IoError, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{net::TcpListener, signal::ctrl_c};
+use tracing::{info, warn};
+
+use crate::{
+    mcp::{OracleHCMMCPFactory, authz::is_hr_admin, caller::Caller, http::invalidate_cache},
+    metrics,
+};
+use auth::{AuthConfig, JwtValidator};
+use health::HealthConfig;
+use tls::TlsFiles;
+
+/// How long in-flight requests get to finish after a shutdown signal on the HTTPS listener
+const TLS_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Listener settings for the streamable HTTP transport.
+#[derive(Debug, Clone)]
+pub struct ListenerConfig {
+    pub addr: SocketAddr,
+    /// Serve HTTPS with these files instead of plain HTTP
+    pub tls: Option<TlsFiles>,
+}
+
+/// Builds the HTTP router with the MCP endpoint, guarded by bearer-token
+/// authentication when `auth` is configured, plus the metrics and health routes.
+async fn router(auth: Option<AuthConfig>, health: HealthConfig) -> Result<Router> {
+    // Create the MCP service with Oracle HCM tools
+    // Uses local session management (in-memory, suitable for single-instance deployment)
+    let sessions = Arc::new(LocalSessionManager::default());
+    let service = StreamableHttpService::new(
+        || {
+            // Factory function called for each new MCP session
+            // Validates configuration early and fails fast if env vars are missing
+            OracleHCMMCPFactory::new()
+                .map_err(IoError::other)
+        },
+        Arc::clone(&sessions),
+        StreamableHttpServerConfig::default(),
+    );
+
+    // All MCP communication happens through POST /mcp
+    let mcp = Router::new()
+        .nest_service("/mcp", service)
+        .route("/cache/invalidate", post(invalidate_cache_handler));
+
+    // Metrics scrapers and health probes carry no bearer token, so these sit outside the auth layer
+    let operational = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(sessions)
+        .merge(health::router(health));
+
+    let Some(auth) = auth else {
+        warn!("Bearer-token authentication is disabled; anyone who can reach /mcp can call every tool");
+        return Ok(mcp.merge(operational));
+    };
+    let validator = Arc::new(JwtValidator::new(auth).await?);
+    Ok(mcp
+        .layer(from_fn_with_state(validator, auth::require_bearer))
+        .merge(operational))
+}
+
+/// Serves all metrics in the Prometheus text format, refreshing the session count first.
+async fn metrics_handler(State(sessions): State<Arc<LocalSessionManager>>) -> Response {
+    metrics::set_active_sessions(sessions.sessions.read().await.len());
+    match metrics::render() {
+        Ok(body) => ([(CONTENT_TYPE, metrics::CONTENT_TYPE)], body).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// Query parameters of `POST /cache/invalidate`.
+#[derive(Debug, Deserialize)]
+struct InvalidateParams {
+    /// Only drop this resource's entries, e.g. "publicWorkers"
+    resource: Option<String>,
+}
+
+/// Drops cached HCM responses, for one resource or all of them.
+///
+/// Authenticated callers must be HR admins, since emptying the cache sends every
+/// conversation back to HCM; without authentication there is no caller to check.
+async fn invalidate_cache_handler(
+    caller: Option<Extension<Caller>>,
+    Query(params): Query<InvalidateParams>,
+) -> Response {
+    if let Some(Extension(caller)) = caller
+        && !is_hr_admin(&caller)
+    {
+        warn!(subject = %caller.subject, "cache invalidation denied: caller is not an HR admin");
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "insufficient_scope", "error_description": "HR-admin role required" })),
+        )
+            .into_response();
+    }
+    let invalidated = invalidate_cache(params.resource.as_deref());
+    Json(json!({ "invalidated": invalidated })).into_response()
+}
+
+/// Serves the MCP tool router over streamable HTTP (or HTTPS) until Ctrl+C.
+///
+/// On shutdown the listener stops accepting connections and waits for
+/// in-flight requests to complete.
+pub async fn serve_http(
+    listener: ListenerConfig,
+    auth: Option<AuthConfig>,
+    health: HealthConfig,
+) -> Result<()> {
+    let router = router(auth, health).await?;
+    let addr = listener.addr;
+
+    let Some(tls_files) = listener.tls else {
+        let tcp_listener = TcpListener::bind(addr).await?;
+        info!("Server listening on {}", addr);
+        info!("MCP endpoint available at http://{}/mcp", addr);
+
+        serve(tcp_listener, router)
+            .with_graceful_shutdown(async {
+                let _ = ctrl_c().await;
+                info!("Received shutdown signal, draining connections...");
+            })
+            .await?;
+        return Ok(());
+    };
+
+    let rustls_config = tls::load(&tls_files).await?;
+    tls::spawn_reloader(rustls_config.clone(), tls_files);
+
+    let handle = Handle::new();
+    let shutdown_handle = handle.clone();
+    tokio::spawn(async move {
+        let _ = ctrl_c().await;
+        info!("Received shutdown signal, draining connections...");
+        shutdown_handle.graceful_shutdown(Some(TLS_SHUTDOWN_GRACE_PERIOD));
+    });
+
+    info!("Server listening on {} (TLS)", addr);
+    info!("MCP endpoint available at https://{}/mcp", addr);
+
+    axum_server::bind_rustls(addr, rustls_config)
+        .handle(handle)
+        .serve(router.into_make_service())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn caller(roles: &[&str]) -> Extension<Caller> {
+        let Value::Object(claims) = json!({ "roles": roles }) else {
+            unreachable!("claims must be an object")
+        };
+        Extension(Caller {
+            subject: "alice".to_string(),
+            claims: Arc::new(claims),
+            token: Arc::from("token"),
+        })
+    }
+
+    fn params() -> Query<InvalidateParams> {
+        Query(InvalidateParams { resource: Some("cacheInvalidationTest".to_string()) })
+    }
+
+    #[tokio::test]
+    async fn cache_invalidation_needs_the_hr_admin_role() {
+        let response = invalidate_cache_handler(Some(caller(&["employee"])), params()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = invalidate_cache_handler(Some(caller(&["hr-admin"])), params()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn cache_invalidation_is_open_without_authentication() {
+        let response = invalidate_cache_handler(None, params()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
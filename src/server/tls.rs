@@ -0,0 +1,139 @@
+//! Native HTTPS support for the streamable HTTP listener.
+//!
+//! Certificates and private keys are loaded from PEM files and watched for
+//! changes. When either file is rotated (e.g. by cert-manager or a renewal
+//! job), the new pair is loaded into the running `RustlsConfig`. Established
+//! connections keep their existing TLS session, and MCP sessions live in the
+//! session manager rather than on the connection, so nothing is dropped.
+
+use anyhow::{Context, Result, anyhow};
+use axum_server::tls_rustls::RustlsConfig;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs::metadata, time::interval};
+use tracing::{error, info, warn};
+
+/// Certificate and key file locations for the HTTPS listener.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// How often the files are checked for rotation
+    pub reload_interval: Duration,
+}
+
+/// Loads the initial rustls configuration from PEM files.
+///
+/// Installs the `ring` crypto provider as the process default first; this is
+/// the same provider `reqwest` uses for outbound HCM calls.
+pub async fn load(files: &TlsFiles) -> Result<RustlsConfig> {
+    // Ignore the error: it only means a provider was already installed
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(&files.cert, &files.key)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load TLS certificate {} / key {}",
+                files.cert.display(),
+                files.key.display()
+            )
+        })
+}
+
+/// Returns the modification times of the certificate and key files. Any
+/// difference counts as a rotation, since a renewed file can carry an older
+/// mtime (e.g. copied with its original timestamp, or a Kubernetes secret
+/// symlink swapped back).
+async fn last_modified(files: &TlsFiles) -> Result<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| {
+        let path = path.to_path_buf();
+        async move {
+            metadata(&path)
+                .await
+                .and_then(|m| m.modified())
+                .map_err(|e| anyhow!("Unable to stat {}: {e}", path.display()))
+        }
+    };
+
+    Ok((modified(&files.cert).await?, modified(&files.key).await?))
+}
+
+/// Spawns a background task that reloads `config` whenever the certificate or
+/// key file changes on disk.
+///
+/// A failed reload (e.g. a half-written file mid-rotation) is logged and the
+/// previous certificate keeps serving; the next tick retries.
+pub fn spawn_reloader(config: RustlsConfig, files: TlsFiles) {
+    tokio::spawn(async move {
+        let mut loaded_at = match last_modified(&files).await {
+            Ok(modified) => modified,
+            Err(e) => {
+                warn!("TLS hot-reload disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticker = interval(files.reload_interval);
+        loop {
+            ticker.tick().await;
+
+            let modified = match last_modified(&files).await {
+                Ok(modified) => modified,
+                Err(e) => {
+                    warn!("TLS reload check failed: {e}");
+                    continue;
+                }
+            };
+            if modified == loaded_at {
+                continue;
+            }
+
+            match config.reload_from_pem_file(&files.cert, &files.key).await {
+                Ok(()) => {
+                    loaded_at = modified;
+                    info!("Reloaded TLS certificate from {}", files.cert.display());
+                }
+                Err(e) => error!("Failed to reload TLS certificate, keeping previous one: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Writes `contents` to `path`, stamping it with `modified`.
+    fn write(path: &Path, contents: &str, modified: SystemTime) {
+        fs::write(path, contents).unwrap_or_else(|e| unreachable!("write: {e}"));
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(modified))
+            .unwrap_or_else(|e| unreachable!("set mtime: {e}"));
+    }
+
+    #[tokio::test]
+    async fn rotation_to_an_older_mtime_is_noticed() -> Result<()> {
+        let dir = std::env::temp_dir();
+        let files = TlsFiles {
+            cert: dir.join(format!("oracle-hcm-mcp-tls-{}.crt", std::process::id())),
+            key: dir.join(format!("oracle-hcm-mcp-tls-{}.key", std::process::id())),
+            reload_interval: Duration::from_secs(1),
+        };
+        let now = SystemTime::now();
+        write(&files.cert, "cert", now);
+        write(&files.key, "key", now);
+        let loaded_at = last_modified(&files).await?;
+
+        write(&files.cert, "renewed cert", now - Duration::from_hours(1));
+        let modified = last_modified(&files).await?;
+        let _ = (fs::remove_file(&files.cert), fs::remove_file(&files.key));
+        assert_ne!(modified, loaded_at);
+        Ok(())
+    }
+}
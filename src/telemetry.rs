@@ -0,0 +1,416 @@
+//! OpenTelemetry export of traces and logs over OTLP.
+//!
+//! Alongside the formatted log output, spans can be exported as OTLP traces
+//! and log events as OTLP logs. Nothing is exported unless an OTLP endpoint or
+//! exporter is configured through the standard `OTEL_*` variables.
+//!
+//! Traces follow W3C Trace Context end to end:
+//! - **Inbound**: each tool call runs in an `mcp-tool` span that continues the
+//!   trace from the `traceparent` header of the `/mcp` request (see [`tool_span`])
+//! - **Outbound**: `mcp::http` injects `traceparent` into every HCM request from
+//!   its `hcm-api-request` span, so one trace covers agent → MCP → Fusion
+//!
+//! Exported span attributes, span events and log records are redacted field by
+//! field with `mcp::redact`, the same rules as the formatted log output.
+//!
+//! # Configuration
+//! - `OTEL_EXPORTER_OTLP_ENDPOINT` (optional): Collector base URL, e.g. "<http://localhost:4318>"; enables trace export
+//! - `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` / `OTEL_EXPORTER_OTLP_LOGS_ENDPOINT` (optional): Full per-signal URLs,
+//!   e.g. "<http://localhost:4318/v1/traces>"
+//! - `OTEL_EXPORTER_OTLP_PROTOCOL` (optional): Only "http/protobuf" is supported, the default
+//! - `OTEL_EXPORTER_OTLP_HEADERS` (optional): Headers sent to the collector, e.g. "api-key=..."
+//! - `OTEL_EXPORTER_OTLP_TIMEOUT` (optional): Export timeout in milliseconds, defaults to 10000
+//! - `OTEL_TRACES_EXPORTER` (optional): "otlp" or "none", defaults to "otlp" when an endpoint is set
+//! - `OTEL_LOGS_EXPORTER` (optional): "otlp" or "none", defaults to "none"
+//! - `OTEL_SERVICE_NAME` (optional): Service name on exported data, defaults to "oracle-hcm-mcp"
+//! - `OTEL_RESOURCE_ATTRIBUTES` (optional): Extra resource attributes, e.g. "deployment.environment=prod"
+//! - `OTEL_TRACES_SAMPLER` / `OTEL_TRACES_SAMPLER_ARG` (optional): Sampler, defaults to "`parentbased_always_on`"
+//! - `OTEL_SDK_DISABLED` (optional): "true" turns off all export
+
+use anyhow::{Result, bail};
+use axum::http::request;
+use opentelemetry::{
+    Context, KeyValue, Value, global,
+    logs::{AnyValue, LogRecord, Logger, LoggerProvider, Severity},
+    trace::{Status, TraceContextExt, TracerProvider},
+};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{LogExporter, Protocol, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{
+    Resource,
+    error::OTelSdkResult,
+    logs::{SdkLogger, SdkLoggerProvider},
+    propagation::TraceContextPropagator,
+    trace::{BatchSpanProcessor, SdkTracerProvider, Span as SdkSpan, SpanData, SpanProcessor},
+};
+use rmcp::{RoleServer, service::RequestContext};
+use std::{env, fmt, time::Duration};
+use tracing::{Event, Level, Span, Subscriber, field::Field, info, info_span, warn};
+use tracing_opentelemetry::{OpenTelemetrySpanExt, OtelData};
+use tracing_subscriber::{
+    EnvFilter, Layer,
+    field::Visit,
+    fmt::writer::BoxMakeWriter,
+    layer::{self, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+use crate::mcp::{http::load_env_var_or, redact};
+
+/// Instrumentation scope and default service name on exported data.
+const SERVICE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Crates whose events are never exported as OTLP logs: the exporter's own
+/// HTTP stack logs while exporting, which would feed back into the exporter.
+const EXPORTER_TARGETS: &[&str] = &[
+    "opentelemetry",
+    "opentelemetry_sdk",
+    "opentelemetry_otlp",
+    "opentelemetry_http",
+    "hyper",
+    "hyper_util",
+    "h2",
+    "reqwest",
+];
+
+/// Installed tracing subscriber and the OTLP providers behind it.
+///
+/// Call [`Telemetry::shutdown`] before exiting so buffered spans and logs are flushed.
+pub struct Telemetry {
+    tracer_provider: Option<SdkTracerProvider>,
+    logger_provider: Option<SdkLoggerProvider>,
+}
+
+impl Telemetry {
+    /// Installs the global tracing subscriber: formatted logs to `log_writer`,
+    /// plus OTLP trace and log export when configured.
+    ///
+    /// # Errors
+    /// Returns an error if the OTLP configuration is invalid or an exporter can't be built.
+    pub fn init(log_writer: BoxMakeWriter) -> Result<Self> {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let tracer_provider = if export_enabled("OTEL_TRACES_EXPORTER", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", true) {
+            Some(tracer_provider()?)
+        } else {
+            None
+        };
+        let logger_provider = if export_enabled("OTEL_LOGS_EXPORTER", "OTEL_EXPORTER_OTLP_LOGS_ENDPOINT", false) {
+            Some(logger_provider()?)
+        } else {
+            None
+        };
+
+        let trace_layer = tracer_provider
+            .as_ref()
+            .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+        let log_layer = logger_provider
+            .as_ref()
+            .map(|provider| OtlpLogLayer { logger: provider.logger(SERVICE_NAME) });
+
+        // Initialize structured logging
+        // Supports RUST_LOG env var for filtering (e.g., RUST_LOG=debug,oracle_hcm_mcp=trace)
+        // Default level is "info" for production use
+        tracing_subscriber::registry()
+            .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+            .with(tracing_subscriber::fmt::layer().with_writer(log_writer))
+            .with(trace_layer)
+            .with(log_layer)
+            .init();
+
+        if tracer_provider.is_some() {
+            info!("Exporting traces over OTLP");
+        }
+        if logger_provider.is_some() {
+            info!("Exporting logs over OTLP");
+        }
+        Ok(Self { tracer_provider, logger_provider })
+    }
+
+    /// Flushes and stops the OTLP exporters.
+    pub fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider
+            && let Err(e) = provider.shutdown()
+        {
+            warn!("Failed to flush OTLP traces: {e}");
+        }
+        if let Some(provider) = self.logger_provider
+            && let Err(e) = provider.shutdown()
+        {
+            warn!("Failed to flush OTLP logs: {e}");
+        }
+    }
+}
+
+/// Whether a signal should be exported: `OTEL_SDK_DISABLED` wins, then the
+/// signal's `OTEL_*_EXPORTER` setting, then (if `default_on`) whether any endpoint is set.
+fn export_enabled(exporter_key: &str, endpoint_key: &str, default_on: bool) -> bool {
+    if load_env_var_or("OTEL_SDK_DISABLED", "false").eq_ignore_ascii_case("true") {
+        return false;
+    }
+    if env::var_os(exporter_key).is_some() {
+        return load_env_var_or(exporter_key, "none").eq_ignore_ascii_case("otlp");
+    }
+    default_on && (env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some() || env::var_os(endpoint_key).is_some())
+}
+
+/// Only OTLP over HTTP with protobuf payloads is built in.
+fn check_protocol() -> Result<()> {
+    let protocol = load_env_var_or("OTEL_EXPORTER_OTLP_PROTOCOL", "http/protobuf");
+    if protocol != "http/protobuf" {
+        bail!("OTEL_EXPORTER_OTLP_PROTOCOL={protocol} is not supported, use http/protobuf (usually port 4318)");
+    }
+    Ok(())
+}
+
+/// Service name and version, unless `OTEL_SERVICE_NAME` overrides the name.
+fn resource() -> Resource {
+    let builder = Resource::builder()
+        .with_attribute(KeyValue::new("service.version", env!("CARGO_PKG_VERSION")));
+    if env::var_os("OTEL_SERVICE_NAME").is_some() {
+        builder.build()
+    } else {
+        builder.with_service_name(SERVICE_NAME).build()
+    }
+}
+
+fn tracer_provider() -> Result<SdkTracerProvider> {
+    check_protocol()?;
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpBinary)
+        .build()?;
+    Ok(exporting_to(exporter))
+}
+
+/// Batches spans to `exporter`, redacting them on the way out.
+fn exporting_to(exporter: SpanExporter) -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_span_processor(RedactingSpanProcessor(BatchSpanProcessor::builder(exporter).build()))
+        .with_resource(resource())
+        .build()
+}
+
+fn logger_provider() -> Result<SdkLoggerProvider> {
+    check_protocol()?;
+    let exporter = LogExporter::builder()
+        .with_http()
+        .with_protocol(Protocol::HttpBinary)
+        .build()?;
+    Ok(SdkLoggerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource())
+        .build())
+}
+
+// ============================================================================
+// Trace context propagation
+// ============================================================================
+
+/// Creates the span a tool call runs in, continuing the caller's trace from the
+/// `traceparent`/`tracestate` headers of the MCP HTTP request when present.
+///
+/// Over stdio (or without those headers) the span starts a new trace.
+pub fn tool_span(tool: &'static str, context: &RequestContext<RoleServer>) -> Span {
+    let span = info_span!("mcp-tool", otel.kind = "server", tool);
+    let parent = context
+        .extensions
+        .get::<request::Parts>()
+        .map(|parts| global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(&parts.headers))));
+    if let Some(parent) = parent.filter(|parent| parent.span().span_context().is_valid()) {
+        // Fails only when trace export is off, in which case there's nothing to link
+        let _ = span.set_parent(parent);
+    }
+    span
+}
+
+// ============================================================================
+// Redaction of exported data
+// ============================================================================
+
+/// Span processor that redacts attributes, event names and error descriptions
+/// before handing spans on to the exporting processor.
+#[derive(Debug)]
+struct RedactingSpanProcessor<P>(P);
+
+impl<P: SpanProcessor> SpanProcessor for RedactingSpanProcessor<P> {
+    fn on_start(&self, span: &mut SdkSpan, cx: &Context) {
+        self.0.on_start(span, cx);
+    }
+
+    fn on_end(&self, mut span: SpanData) {
+        redact_attributes(&mut span.attributes);
+        for event in &mut span.events.events {
+            // tracing-opentelemetry names span events after the log message
+            if let Some(name) = redact::field("message", &event.name) {
+                event.name = name.into();
+            }
+            redact_attributes(&mut event.attributes);
+        }
+        if let Status::Error { description } = &mut span.status
+            && let Some(scrubbed) = redact::field("error", description)
+        {
+            *description = scrubbed.into();
+        }
+        self.0.on_end(span);
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        self.0.force_flush()
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        self.0.shutdown_with_timeout(timeout)
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.0.set_resource(resource);
+    }
+}
+
+fn redact_attributes(attributes: &mut [KeyValue]) {
+    for attribute in attributes {
+        if let Some(redacted) = redact::field(attribute.key.as_str(), &attribute.value.as_str()) {
+            attribute.value = Value::from(redacted);
+        }
+    }
+}
+
+/// Exports `tracing` events as OTLP log records, correlated with the current
+/// span and redacted field by field.
+struct OtlpLogLayer {
+    logger: SdkLogger,
+}
+
+impl<S> Layer<S> for OtlpLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: layer::Context<'_, S>) {
+        let metadata = event.metadata();
+        let target = metadata.target();
+        let crate_name = target.split("::").next().unwrap_or(target);
+        if EXPORTER_TARGETS.contains(&crate_name) {
+            return;
+        }
+
+        let mut record = self.logger.create_log_record();
+        record.set_target(target.to_string());
+        record.set_severity_number(severity(*metadata.level()));
+        record.set_severity_text(metadata.level().as_str());
+        event.record(&mut RecordVisitor(&mut record));
+
+        let span_ids = ctx.event_span(event).and_then(|span| {
+            let extensions = span.extensions();
+            let ids = extensions.get::<OtelData>().and_then(|otel| otel.trace_id().zip(otel.span_id()));
+            drop(extensions);
+            ids
+        });
+        if let Some((trace_id, span_id)) = span_ids {
+            record.set_trace_context(trace_id, span_id, None);
+        }
+
+        self.logger.emit(record);
+    }
+}
+
+const fn severity(level: Level) -> Severity {
+    match level {
+        Level::TRACE => Severity::Trace,
+        Level::DEBUG => Severity::Debug,
+        Level::INFO => Severity::Info,
+        Level::WARN => Severity::Warn,
+        Level::ERROR => Severity::Error,
+    }
+}
+
+/// Copies event fields into a log record: the message as the body, the rest as attributes.
+struct RecordVisitor<'a, R>(&'a mut R);
+
+impl<R: LogRecord> RecordVisitor<'_, R> {
+    fn add(&mut self, field: &Field, value: String) {
+        let value = redact::field(field.name(), &value).unwrap_or(value);
+        if field.name() == "message" {
+            self.0.set_body(AnyValue::from(value));
+        } else {
+            self.0.add_attribute(field.name(), value);
+        }
+    }
+}
+
+impl<R: LogRecord> Visit for RecordVisitor<'_, R> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.add(field, format!("{value:?}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Bytes, extract::State};
+    use opentelemetry::trace::{Span as _, Tracer};
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    type Received = Arc<Mutex<Vec<Bytes>>>;
+
+    /// Starts a local OTLP/HTTP collector stand-in that keeps every request body.
+    async fn collector() -> (String, Received) {
+        let received = Received::default();
+        let app = Router::new()
+            .fallback(|State(received): State<Received>, body: Bytes| async move {
+                received.lock().unwrap_or_else(PoisonError::into_inner).push(body);
+            })
+            .with_state(Arc::clone(&received));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap_or_else(|e| unreachable!("bind: {e}"));
+        let address = listener.local_addr().unwrap_or_else(|e| unreachable!("addr: {e}"));
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{address}/v1/traces"), received)
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn spans_reach_the_collector_redacted() {
+        let (endpoint, received) = collector().await;
+
+        // The exporter's blocking HTTP client must not run on a runtime thread
+        let exported = tokio::task::spawn_blocking(move || {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(endpoint)
+                .build()
+                .unwrap_or_else(|e| unreachable!("exporter: {e}"));
+            let provider = exporting_to(exporter);
+            let mut span = provider.tracer(SERVICE_NAME).start("hcm-api-request");
+            span.set_attribute(KeyValue::new("client_secret", "hunter2"));
+            span.set_attribute(KeyValue::new("url", "/publicWorkers?q=PersonId%20%3D%20300000578701661&limit=1"));
+            span.set_attribute(KeyValue::new("http.status_code", 200));
+            span.end();
+            let flushed = provider.force_flush();
+            let _ = provider.shutdown();
+            flushed
+        })
+        .await
+        .unwrap_or_else(|e| unreachable!("export task: {e}"));
+        assert!(exported.is_ok(), "{exported:?}");
+
+        let bodies = received.lock().unwrap_or_else(PoisonError::into_inner).clone();
+        assert!(!bodies.is_empty(), "collector received nothing");
+        let body: Vec<u8> = bodies.concat();
+        assert!(contains(&body, "hcm-api-request"));
+        assert!(contains(&body, SERVICE_NAME));
+        assert!(contains(&body, "/publicWorkers?q=[REDACTED]&limit=1"));
+        assert!(!contains(&body, "hunter2"));
+        assert!(!contains(&body, "300000578701661"));
+    }
+}